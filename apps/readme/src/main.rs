@@ -20,12 +20,12 @@ use anyrender_vello::VelloWindowRenderer as WindowRenderer;
 #[cfg(feature = "cpu-base")]
 use anyrender_vello_cpu::VelloCpuWindowRenderer as WindowRenderer;
 
-use blitz_dom::DocumentConfig;
 use blitz_dom::net::Resource;
-use blitz_html::HtmlDocument;
+use blitz_dom::{DocumentConfig, ResourceCache};
+use blitz_html::{HtmlDocument, decode_html};
 use blitz_net::Provider;
 use blitz_traits::navigation::{NavigationOptions, NavigationProvider};
-use blitz_traits::net::Request;
+use blitz_traits::net::{Request, RequestDestination};
 use markdown::{BLITZ_MD_STYLES, GITHUB_MD_STYLES, markdown_to_html};
 use notify::{Error as NotifyError, Event as NotifyEvent, RecursiveMode, Watcher as _};
 use readme_application::{ReadmeApplication, ReadmeEvent};
@@ -98,6 +98,8 @@ fn main() {
         proxy: proxy.clone(),
     };
     let navigation_provider = Arc::new(navigation_provider);
+    // Shared between reloads so that images and fonts don't need to be decoded again
+    let resource_cache = ResourceCache::default();
 
     let doc = HtmlDocument::from_html(
         &html,
@@ -106,6 +108,7 @@ fn main() {
             ua_stylesheets: Some(stylesheets),
             net_provider: Some(net_provider.clone()),
             navigation_provider: Some(navigation_provider.clone()),
+            resource_cache: Some(resource_cache.clone()),
             ..Default::default()
         },
     );
@@ -119,6 +122,7 @@ fn main() {
         raw_url.clone(),
         net_provider,
         navigation_provider,
+        resource_cache,
     );
     application.add_window(window);
 
@@ -168,7 +172,7 @@ async fn fetch_url(
 ) -> (String, String, bool, Option<PathBuf>) {
     let (tx, rx) = oneshot::channel();
 
-    let request = Request::get(url);
+    let request = Request::get(url).with_destination(RequestDestination::Document);
     net_provider.fetch_with_callback(
         request,
         Box::new(move |result| {
@@ -177,7 +181,8 @@ async fn fetch_url(
         }),
     );
 
-    let (response_url, bytes) = rx.await.unwrap();
+    let response = rx.await.unwrap();
+    let response_url = response.url;
 
     // Detect markdown file
     // let content_type = response
@@ -188,7 +193,8 @@ async fn fetch_url(
     let is_md = response_url.ends_with(".md");
 
     // Get the file content
-    let file_content = std::str::from_utf8(&bytes).unwrap().to_string();
+    let (file_content, _encoding) = decode_html(&response.body, response.content_type.as_deref());
+    let file_content = file_content.into_owned();
 
     (response_url, file_content, is_md, None)
 }
@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
 use crate::WindowRenderer;
-use blitz_dom::DocumentConfig;
 use blitz_dom::net::Resource;
-use blitz_html::HtmlDocument;
+use blitz_dom::{DocumentConfig, ResourceCache};
+use blitz_html::{HtmlDocument, decode_html};
 use blitz_net::Provider;
 use blitz_shell::{BlitzApplication, BlitzShellEvent, View, WindowConfig};
 use blitz_traits::navigation::{NavigationOptions, NavigationProvider};
@@ -26,6 +26,7 @@ pub struct ReadmeApplication {
     raw_url: String,
     keyboard_modifiers: Modifiers,
     navigation_provider: Arc<dyn NavigationProvider>,
+    resource_cache: ResourceCache,
     url_history: Vec<String>,
 }
 
@@ -35,6 +36,7 @@ impl ReadmeApplication {
         raw_url: String,
         net_provider: Arc<Provider<Resource>>,
         navigation_provider: Arc<dyn NavigationProvider>,
+        resource_cache: ResourceCache,
     ) -> Self {
         let handle = Handle::current();
         Self {
@@ -44,6 +46,7 @@ impl ReadmeApplication {
             net_provider,
             keyboard_modifiers: Default::default(),
             navigation_provider,
+            resource_cache,
             url_history: Vec::new(),
         }
     }
@@ -80,12 +83,13 @@ impl ReadmeApplication {
         self.net_provider.fetch_with_callback(
             dbg!(options.into_request()),
             Box::new(move |result| {
-                let (url, bytes) = result.unwrap();
-                let contents = std::str::from_utf8(&bytes).unwrap().to_string();
+                let response = result.unwrap();
+                let (contents, _encoding) =
+                    decode_html(&response.body, response.content_type.as_deref());
                 proxy
                     .send_event(BlitzShellEvent::NavigationLoad {
-                        url,
-                        contents,
+                        url: response.url,
+                        contents: contents.into_owned(),
                         is_md: false,
                         retain_scroll_position: false,
                     })
@@ -116,6 +120,7 @@ impl ReadmeApplication {
                 ua_stylesheets: Some(stylesheets),
                 net_provider: Some(self.net_provider.clone()),
                 navigation_provider: Some(self.navigation_provider.clone()),
+                resource_cache: Some(self.resource_cache.clone()),
                 ..Default::default()
             },
         );
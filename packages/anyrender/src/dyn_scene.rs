@@ -0,0 +1,221 @@
+//! An object-safe version of [`PaintScene`], allowing scenes to be passed as trait objects
+
+use kurbo::{Affine, BezPath, Rect, Shape, Stroke};
+use peniko::{BlendMode, BrushRef, Color, Fill, Font, StyleRef};
+
+use crate::{Glyph, NormalizedCoord, Paint, PaintScene};
+
+/// The tolerance used to convert shapes into paths when drawing to a `dyn DynPaintScene`
+const PATH_TOLERANCE: f64 = 0.1;
+
+/// An object-safe version of [`PaintScene`], in which shapes are passed as [`BezPath`]s and glyphs as an
+/// iterator trait object.
+///
+/// This is implemented for every [`PaintScene`]. And `dyn DynPaintScene` itself implements [`PaintScene`],
+/// so code which is given a `&mut dyn DynPaintScene` can draw to it using the regular [`PaintScene`] methods.
+pub trait DynPaintScene {
+    fn dyn_reset(&mut self);
+
+    fn dyn_push_layer(&mut self, blend: BlendMode, alpha: f32, transform: Affine, clip: &BezPath);
+
+    fn dyn_pop_layer(&mut self);
+
+    fn dyn_stroke(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: BrushRef<'_>,
+        brush_transform: Option<Affine>,
+        shape: &BezPath,
+    );
+
+    fn dyn_fill(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: Paint<'_>,
+        brush_transform: Option<Affine>,
+        shape: &BezPath,
+    );
+
+    #[allow(clippy::too_many_arguments)]
+    fn dyn_draw_glyphs<'a>(
+        &'a mut self,
+        font: &'a Font,
+        font_size: f32,
+        hint: bool,
+        normalized_coords: &'a [NormalizedCoord],
+        style: StyleRef<'a>,
+        brush: BrushRef<'a>,
+        brush_alpha: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        glyphs: &mut dyn Iterator<Item = Glyph>,
+    );
+
+    fn dyn_draw_box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        brush: Color,
+        radius: f64,
+        std_dev: f64,
+    );
+}
+
+impl<S: PaintScene> DynPaintScene for S {
+    fn dyn_reset(&mut self) {
+        self.reset();
+    }
+
+    fn dyn_push_layer(&mut self, blend: BlendMode, alpha: f32, transform: Affine, clip: &BezPath) {
+        self.push_layer(blend, alpha, transform, clip);
+    }
+
+    fn dyn_pop_layer(&mut self) {
+        self.pop_layer();
+    }
+
+    fn dyn_stroke(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: BrushRef<'_>,
+        brush_transform: Option<Affine>,
+        shape: &BezPath,
+    ) {
+        self.stroke(style, transform, brush, brush_transform, shape);
+    }
+
+    fn dyn_fill(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: Paint<'_>,
+        brush_transform: Option<Affine>,
+        shape: &BezPath,
+    ) {
+        self.fill(style, transform, brush, brush_transform, shape);
+    }
+
+    fn dyn_draw_glyphs<'a>(
+        &'a mut self,
+        font: &'a Font,
+        font_size: f32,
+        hint: bool,
+        normalized_coords: &'a [NormalizedCoord],
+        style: StyleRef<'a>,
+        brush: BrushRef<'a>,
+        brush_alpha: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        glyphs: &mut dyn Iterator<Item = Glyph>,
+    ) {
+        self.draw_glyphs(
+            font,
+            font_size,
+            hint,
+            normalized_coords,
+            style,
+            brush,
+            brush_alpha,
+            transform,
+            glyph_transform,
+            glyphs,
+        );
+    }
+
+    fn dyn_draw_box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        brush: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        self.draw_box_shadow(transform, rect, brush, radius, std_dev);
+    }
+}
+
+impl PaintScene for dyn DynPaintScene + '_ {
+    fn reset(&mut self) {
+        self.dyn_reset();
+    }
+
+    fn push_layer(
+        &mut self,
+        blend: impl Into<BlendMode>,
+        alpha: f32,
+        transform: Affine,
+        clip: &impl Shape,
+    ) {
+        let clip = clip.to_path(PATH_TOLERANCE);
+        self.dyn_push_layer(blend.into(), alpha, transform, &clip);
+    }
+
+    fn pop_layer(&mut self) {
+        self.dyn_pop_layer();
+    }
+
+    fn stroke<'a>(
+        &mut self,
+        style: &Stroke,
+        transform: Affine,
+        brush: impl Into<BrushRef<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let shape = shape.to_path(PATH_TOLERANCE);
+        self.dyn_stroke(style, transform, brush.into(), brush_transform, &shape);
+    }
+
+    fn fill<'a>(
+        &mut self,
+        style: Fill,
+        transform: Affine,
+        brush: impl Into<Paint<'a>>,
+        brush_transform: Option<Affine>,
+        shape: &impl Shape,
+    ) {
+        let shape = shape.to_path(PATH_TOLERANCE);
+        self.dyn_fill(style, transform, brush.into(), brush_transform, &shape);
+    }
+
+    fn draw_glyphs<'a, 's: 'a>(
+        &'s mut self,
+        font: &'a Font,
+        font_size: f32,
+        hint: bool,
+        normalized_coords: &'a [NormalizedCoord],
+        style: impl Into<StyleRef<'a>>,
+        brush: impl Into<BrushRef<'a>>,
+        brush_alpha: f32,
+        transform: Affine,
+        glyph_transform: Option<Affine>,
+        mut glyphs: impl Iterator<Item = Glyph>,
+    ) {
+        self.dyn_draw_glyphs(
+            font,
+            font_size,
+            hint,
+            normalized_coords,
+            style.into(),
+            brush.into(),
+            brush_alpha,
+            transform,
+            glyph_transform,
+            &mut glyphs,
+        );
+    }
+
+    fn draw_box_shadow(
+        &mut self,
+        transform: Affine,
+        rect: Rect,
+        brush: Color,
+        radius: f64,
+        std_dev: f64,
+    ) {
+        self.dyn_draw_box_shadow(transform, rect, brush, radius, std_dev);
+    }
+}
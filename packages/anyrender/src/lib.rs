@@ -10,6 +10,9 @@
 //!   - Applications and libraries draw by pushing commands into a [`PaintScene`]
 //!   - Backends execute those commands to produce an output
 //!
+//! [`PaintScene`] is not object-safe. The [`DynPaintScene`] trait can be used where a scene needs to be passed
+//! as a trait object.
+//!
 //! ### Rendering to surface or buffer
 //!
 //! In addition to PaintScene, there is:
@@ -31,6 +34,8 @@ use kurbo::{Affine, Rect, Shape, Stroke};
 use peniko::{BlendMode, BrushRef, Color, Fill, Font, Image, StyleRef};
 use std::sync::Arc;
 
+pub mod dyn_scene;
+pub use dyn_scene::DynPaintScene;
 pub mod wasm_send_sync;
 pub use wasm_send_sync::*;
 pub mod types;
@@ -0,0 +1,345 @@
+//! A cache of decoded images and fonts which can be shared between documents
+
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use blitz_traits::net::http::HeaderValue;
+use blitz_traits::net::http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use blitz_traits::net::{Bytes, Request, ResponseHead, StatusCode};
+use url::Url;
+
+use crate::node::{ImageFrame, RasterImageData};
+
+/// The default value of [`ResourceCache::max_size`]: 256MiB
+pub const DEFAULT_MAX_CACHE_SIZE: usize = 256 * 1024 * 1024;
+
+/// A cache of decoded images and (decompressed) web fonts which can be shared between documents
+/// using [`DocumentConfig::resource_cache`](crate::DocumentConfig::resource_cache).
+///
+/// Resources are keyed by their URL and the validator of the response (its `ETag` or `Last-Modified`
+/// header). Requests for cached resources are made conditional, so the server can respond with
+/// `304 Not Modified` instead of sending the resource again. Responses without a validator (or from
+/// `NetProvider`s which don't pass response headers to the handler) are validated by a digest of their
+/// body instead: they are still fetched, but aren't decoded again.
+///
+/// Decoded images and fonts are reference counted, so documents using the same resource share a single
+/// copy of it.
+///
+/// When the total (approximate) size of the cached resources exceeds [`max_size`](Self::max_size)
+/// the least recently used resources are evicted. Handles are cheap to clone.
+#[derive(Clone)]
+pub struct ResourceCache {
+    inner: Arc<Mutex<CacheInner>>,
+}
+
+struct CacheInner {
+    /// The cached version of each resource
+    entries: HashMap<Url, CacheEntry>,
+    /// The URL of each entry by when it was last used, least recently used first
+    recency: BTreeMap<u64, Url>,
+    size: usize,
+    max_size: usize,
+    /// Incremented every time the cache is accessed
+    clock: u64,
+}
+
+struct CacheEntry {
+    validator: Validator,
+    value: CachedValue,
+    size: usize,
+    last_used: u64,
+}
+
+#[derive(Clone)]
+enum CachedValue {
+    Image(DecodedImage),
+    Font(Bytes),
+}
+
+/// A decoded image (as stored in a [`ResourceCache`])
+#[derive(Clone)]
+pub(crate) enum DecodedImage {
+    Raster(RasterImageData),
+    Animated(Vec<ImageFrame>),
+    #[cfg(feature = "svg")]
+    Svg(Arc<usvg::Tree>),
+}
+
+/// Identifies a version of a resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Validator {
+    ETag(HeaderValue),
+    LastModified(HeaderValue),
+    /// A digest of the body of a response without a validator header
+    Digest {
+        len: usize,
+        digest: u64,
+    },
+}
+
+/// Identifies a cached resource by its URL and validator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CacheKey {
+    url: Url,
+    /// `None` for a `304 Not Modified` response, which matches whichever version is cached
+    validator: Option<Validator>,
+}
+
+impl CacheKey {
+    /// The key of a response, from its headers (if known) or otherwise from its body
+    pub(crate) fn new(url: &Url, head: Option<&ResponseHead>, bytes: &[u8]) -> Self {
+        let validator = match head {
+            Some(head) if head.status == StatusCode::NOT_MODIFIED => None,
+            Some(head) if head.headers.contains_key(ETAG) => {
+                Some(Validator::ETag(head.headers[ETAG].clone()))
+            }
+            Some(head) if head.headers.contains_key(LAST_MODIFIED) => {
+                Some(Validator::LastModified(head.headers[LAST_MODIFIED].clone()))
+            }
+            _ => {
+                let mut hasher = DefaultHasher::new();
+                bytes.hash(&mut hasher);
+                Some(Validator::Digest {
+                    len: bytes.len(),
+                    digest: hasher.finish(),
+                })
+            }
+        };
+        Self {
+            url: url.clone(),
+            validator,
+        }
+    }
+
+    /// Whether the key is for a `304 Not Modified` response, which can only be served from the cache
+    pub(crate) fn is_not_modified(&self) -> bool {
+        self.validator.is_none()
+    }
+}
+
+impl Default for ResourceCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CACHE_SIZE)
+    }
+}
+
+impl ResourceCache {
+    /// Create an empty cache which holds up to (approximately) `max_size` bytes of resources
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CacheInner {
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+                size: 0,
+                max_size,
+                clock: 0,
+            })),
+        }
+    }
+
+    /// The maximum (approximate) number of bytes of resources that are held by the cache
+    pub fn max_size(&self) -> usize {
+        self.inner.lock().unwrap().max_size
+    }
+
+    /// The (approximate) number of bytes of resources currently held by the cache
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+
+    /// The number of resources currently held by the cache
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all resources from the cache
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+        inner.size = 0;
+    }
+
+    /// Make a request for a cached resource conditional on the cached version's validator, so that
+    /// the server can respond with `304 Not Modified` rather than sending the resource again
+    pub(crate) fn revalidate(&self, mut request: Request) -> Request {
+        let inner = self.inner.lock().unwrap();
+        match inner
+            .entries
+            .get(&request.url)
+            .map(|entry| &entry.validator)
+        {
+            Some(Validator::ETag(etag)) => {
+                request.headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            Some(Validator::LastModified(date)) => {
+                request.headers.insert(IF_MODIFIED_SINCE, date.clone());
+            }
+            Some(Validator::Digest { .. }) | None => {}
+        }
+        request
+    }
+
+    pub(crate) fn get_image(&self, key: &CacheKey) -> Option<DecodedImage> {
+        match self.get(key)? {
+            CachedValue::Image(image) => Some(image),
+            CachedValue::Font(_) => None,
+        }
+    }
+
+    /// Insert a decoded image. `source_size` is the size of the undecoded image.
+    pub(crate) fn insert_image(&self, key: CacheKey, image: DecodedImage, source_size: usize) {
+        let size = match &image {
+            DecodedImage::Raster(image) => image.data.len(),
+            DecodedImage::Animated(frames) => {
+                frames.iter().map(|frame| frame.image.data.len()).sum()
+            }
+            // The size of the parsed tree isn't known, so use the size of the source as an estimate
+            #[cfg(feature = "svg")]
+            DecodedImage::Svg(_) => source_size,
+        };
+        #[cfg(not(feature = "svg"))]
+        let _ = source_size;
+        self.insert(key, CachedValue::Image(image), size);
+    }
+
+    pub(crate) fn get_font(&self, key: &CacheKey) -> Option<Bytes> {
+        match self.get(key)? {
+            CachedValue::Font(bytes) => Some(bytes),
+            CachedValue::Image(_) => None,
+        }
+    }
+
+    pub(crate) fn insert_font(&self, key: CacheKey, bytes: Bytes) {
+        let size = bytes.len();
+        self.insert(key, CachedValue::Font(bytes), size);
+    }
+
+    fn get(&self, key: &CacheKey) -> Option<CachedValue> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.clock += 1;
+        let clock = inner.clock;
+        let entry = inner.entries.get_mut(&key.url)?;
+        if key
+            .validator
+            .as_ref()
+            .is_some_and(|v| *v != entry.validator)
+        {
+            return None;
+        }
+        let previous_use = std::mem::replace(&mut entry.last_used, clock);
+        let value = entry.value.clone();
+        inner.recency.remove(&previous_use);
+        inner.recency.insert(clock, key.url.clone());
+        Some(value)
+    }
+
+    fn insert(&self, key: CacheKey, value: CachedValue, size: usize) {
+        let Some(validator) = key.validator else {
+            return;
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if size > inner.max_size {
+            return;
+        }
+
+        inner.clock += 1;
+        let entry = CacheEntry {
+            validator,
+            value,
+            size,
+            last_used: inner.clock,
+        };
+        inner.size += size;
+        inner.recency.insert(entry.last_used, key.url.clone());
+        if let Some(previous) = inner.entries.insert(key.url, entry) {
+            inner.size -= previous.size;
+            inner.recency.remove(&previous.last_used);
+        }
+
+        while inner.size > inner.max_size {
+            let Some((_, lru_url)) = inner.recency.pop_first() else {
+                break;
+            };
+            let evicted = inner.entries.remove(&lru_url).unwrap();
+            inner.size -= evicted.size;
+        }
+    }
+}
+
+#[test]
+fn evicts_least_recently_used_resources() {
+    let url = |path: &str| {
+        Url::parse("https://example.com/")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    };
+    let cache = ResourceCache::new(10);
+
+    let a = CacheKey::new(&url("a.woff2"), None, b"aaaa");
+    let b = CacheKey::new(&url("b.woff2"), None, b"bbbb");
+    let c = CacheKey::new(&url("c.woff2"), None, b"cccc");
+    cache.insert_font(a.clone(), Bytes::from_static(b"aaaa"));
+    cache.insert_font(b.clone(), Bytes::from_static(b"bbbb"));
+    assert_eq!(cache.size(), 8);
+
+    // Changed contents at the same URL are a different resource
+    assert!(
+        cache
+            .get_font(&CacheKey::new(&url("a.woff2"), None, b"AAAA"))
+            .is_none()
+    );
+    assert!(cache.get_image(&a).is_none());
+
+    // `a` was used more recently than `b`, so `b` is evicted
+    assert!(cache.get_font(&a).is_some());
+    cache.insert_font(c.clone(), Bytes::from_static(b"cccc"));
+    assert_eq!(cache.len(), 2);
+    assert_eq!(cache.size(), 8);
+    assert!(cache.get_font(&b).is_none());
+    assert!(cache.get_font(&a).is_some());
+    assert!(cache.get_font(&c).is_some());
+}
+
+#[test]
+fn revalidates_resources_with_validator_headers() {
+    use blitz_traits::net::HeaderMap;
+
+    let url = Url::parse("https://example.com/font.woff2").unwrap();
+    let head = |status: StatusCode, etag: &'static str| {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static(etag));
+        ResponseHead::new(status, headers)
+    };
+    let cache = ResourceCache::default();
+
+    // Only responses with a validator header make requests conditional
+    cache.insert_font(CacheKey::new(&url, None, b"v0"), Bytes::from_static(b"v0"));
+    let request = cache.revalidate(Request::get(url.clone()));
+    assert!(request.headers.is_empty());
+
+    let v1 = CacheKey::new(&url, Some(&head(StatusCode::OK, "\"v1\"")), b"v1");
+    cache.insert_font(v1.clone(), Bytes::from_static(b"v1"));
+    assert_eq!(cache.len(), 1);
+    let request = cache.revalidate(Request::get(url.clone()));
+    assert_eq!(request.headers[IF_NONE_MATCH], "\"v1\"");
+
+    // A `304 Not Modified` response is served from the cache
+    let not_modified = CacheKey::new(&url, Some(&head(StatusCode::NOT_MODIFIED, "\"v1\"")), b"");
+    assert!(not_modified.is_not_modified());
+    assert_eq!(cache.get_font(&not_modified).as_deref(), Some(&b"v1"[..]));
+
+    // A different ETag is a different version of the resource, which replaces the cached one
+    let v2 = CacheKey::new(&url, Some(&head(StatusCode::OK, "\"v2\"")), b"v1");
+    assert!(cache.get_font(&v2).is_none());
+    cache.insert_font(v2.clone(), Bytes::from_static(b"v2"));
+    assert_eq!(cache.len(), 1);
+    assert!(cache.get_font(&v1).is_none());
+    assert_eq!(cache.get_font(&v2).as_deref(), Some(&b"v2"[..]));
+}
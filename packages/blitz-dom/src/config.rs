@@ -1,4 +1,5 @@
 use crate::net::Resource;
+use crate::{HtmlParserProvider, ImageDecoder, ResourceCache, ResourcePolicy};
 use blitz_traits::{
     navigation::NavigationProvider,
     net::NetProvider,
@@ -18,10 +19,20 @@ pub struct DocumentConfig {
     pub ua_stylesheets: Option<Vec<String>>,
     /// Net provider to handle network requests for resources
     pub net_provider: Option<Arc<dyn NetProvider<Resource>>>,
+    /// Policy restricting which resources (stylesheets, images, fonts, etc) may be loaded
+    pub resource_policy: Option<ResourcePolicy>,
     /// Navigation provider to handle link clicks and form submissions
     pub navigation_provider: Option<Arc<dyn NavigationProvider>>,
     /// Shell provider to redraw requests, clipboard, etc
     pub shell_provider: Option<Arc<dyn ShellProvider>>,
+    /// HTML parser provider used to parse HTML fragments (e.g. by [`DocumentMutator::set_inner_html`](crate::DocumentMutator::set_inner_html))
+    pub html_parser_provider: Option<Arc<dyn HtmlParserProvider>>,
     /// Parley `FontContext`
     pub font_ctx: Option<FontContext>,
+    /// The worker pool on which images are decoded (and the maximum size that they may decode to).
+    /// Defaults to a pool shared by all documents.
+    pub image_decoder: Option<ImageDecoder>,
+    /// Cache of decoded images and web fonts. Pass the same cache to several documents (e.g. to every
+    /// document shown in an application) to share resources between them. No cache is used by default.
+    pub resource_cache: Option<ResourceCache>,
 }
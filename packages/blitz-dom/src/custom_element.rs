@@ -0,0 +1,242 @@
+//! Custom elements which are implemented in Rust (with their own intrinsic size, painting and event handling)
+
+use anyrender::DynPaintScene;
+use blitz_traits::events::DomEvent;
+use markup5ever::{LocalName, QualName, ns};
+use peniko::kurbo;
+use style::properties::ComputedValues;
+
+use crate::node::NodeFlags;
+use crate::shadow::is_valid_custom_element_name;
+use crate::{BaseDocument, ElementData};
+
+/// The Rust implementation of a custom element. Each element with a defined tag name gets it's own instance
+/// (see [`BaseDocument::define_custom_element`]).
+///
+/// Custom elements are laid out like replaced elements (such as `<img>`): they are sized from their
+/// [`intrinsic_size`](CustomElement::intrinsic_size) and their `width` and `height` attributes and
+/// CSS, and their children are not rendered.
+pub trait CustomElement: 'static {
+    /// The natural size of the element's content in CSS pixels (used like the natural size of an image)
+    fn intrinsic_size(&self) -> taffy::Size<f32>;
+
+    /// Paint the element's content box
+    fn paint(&self, ctx: &CustomElementPaintCtx<'_>, scene: &mut dyn DynPaintScene);
+
+    /// Handle an event targeted at the element. Returns whether the element needs to be repainted.
+    fn handle_event(&mut self, ctx: &CustomElementEventCtx, event: &mut DomEvent) -> bool {
+        let _ = (ctx, event);
+        false
+    }
+
+    /// Called when an attribute of the element is set (`value` is `Some`) or removed (`value` is `None`)
+    fn attribute_changed(&mut self, name: &QualName, value: Option<&str>) {
+        let _ = (name, value);
+    }
+}
+
+/// Creates an instance of a custom element from the element's data
+pub type CustomElementConstructor = Box<dyn Fn(&ElementData) -> Box<dyn CustomElement>>;
+
+/// The context passed to [`CustomElement::paint`]
+pub struct CustomElementPaintCtx<'a> {
+    pub node_id: usize,
+    pub element: &'a ElementData,
+    pub style: &'a ComputedValues,
+    /// The size of the element's content box in CSS pixels
+    pub size: kurbo::Size,
+    /// The scale factor (the number of device pixels per CSS pixel)
+    pub scale: f64,
+    /// Transforms CSS pixel coordinates relative to the top-left corner of the content box to scene coordinates
+    pub transform: kurbo::Affine,
+}
+
+/// The context passed to [`CustomElement::handle_event`]
+pub struct CustomElementEventCtx {
+    pub node_id: usize,
+    /// The element's content box in document coordinates (CSS pixels)
+    pub content_box: kurbo::Rect,
+}
+
+#[derive(Debug, Clone)]
+pub enum DefineCustomElementErr {
+    /// The name is not a valid custom element name (it must be lowercase and contain a hyphen)
+    InvalidName,
+    /// A custom element with the name is already defined
+    AlreadyDefined,
+}
+
+impl BaseDocument {
+    /// Define a custom element, so that every `<name>` element in the document is implemented by an instance
+    /// of [`CustomElement`] created with `constructor`. Existing elements with the name are upgraded immediately.
+    pub fn define_custom_element(
+        &mut self,
+        name: &str,
+        constructor: impl Fn(&ElementData) -> Box<dyn CustomElement> + 'static,
+    ) -> Result<(), DefineCustomElementErr> {
+        if !is_valid_custom_element_name(name) {
+            return Err(DefineCustomElementErr::InvalidName);
+        }
+        let name = LocalName::from(name);
+        if self.custom_element_definitions.contains_key(&name) {
+            return Err(DefineCustomElementErr::AlreadyDefined);
+        }
+        self.custom_element_definitions
+            .insert(name.clone(), Box::new(constructor));
+
+        let node_ids: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.flags.is_in_document())
+            .filter(|(_, node)| node.element_data().is_some_and(|el| el.name.local == name))
+            .map(|(id, _)| id)
+            .collect();
+        for node_id in node_ids {
+            self.upgrade_custom_element(node_id);
+        }
+
+        Ok(())
+    }
+
+    /// Whether a custom element with the name has been defined
+    pub fn is_custom_element_defined(&self, name: &str) -> bool {
+        self.custom_element_definitions
+            .contains_key(&LocalName::from(name))
+    }
+
+    /// The Rust implementation of a custom element node (if it is one)
+    pub fn custom_element(&self, node_id: usize) -> Option<&dyn CustomElement> {
+        self.custom_elements.get(&node_id).map(|element| &**element)
+    }
+
+    /// The Rust implementation of a custom element node (if it is one)
+    pub fn custom_element_mut(&mut self, node_id: usize) -> Option<&mut dyn CustomElement> {
+        match self.custom_elements.get_mut(&node_id) {
+            Some(element) => Some(&mut **element),
+            None => None,
+        }
+    }
+
+    /// Create the custom element instance for a node (if it's tag name is defined and it doesn't already have one)
+    pub(crate) fn upgrade_custom_element(&mut self, node_id: usize) {
+        if self.custom_elements.contains_key(&node_id) {
+            return;
+        }
+        let node = &mut self.nodes[node_id];
+        let Some(element) = node.element_data() else {
+            return;
+        };
+        if element.name.ns != ns!(html) {
+            return;
+        }
+        let Some(constructor) = self.custom_element_definitions.get(&element.name.local) else {
+            return;
+        };
+
+        let custom_element = constructor(element);
+        self.custom_elements.insert(node_id, custom_element);
+        node.flags.insert(NodeFlags::IS_CUSTOM_ELEMENT);
+    }
+
+    /// Dispatch an event to the custom element it targets (if any)
+    pub(crate) fn handle_custom_element_event(&mut self, event: &mut DomEvent) {
+        let node = &self.nodes[event.target];
+        let Some(custom_element) = self.custom_elements.get_mut(&event.target) else {
+            return;
+        };
+
+        let layout = node.final_layout;
+        let origin = node.absolute_position(
+            layout.border.left + layout.padding.left,
+            layout.border.top + layout.padding.top,
+        );
+        let ctx = CustomElementEventCtx {
+            node_id: node.id,
+            content_box: kurbo::Rect::from_origin_size(
+                (origin.x as f64, origin.y as f64),
+                (
+                    layout.content_box_width() as f64,
+                    layout.content_box_height() as f64,
+                ),
+            ),
+        };
+
+        if custom_element.handle_event(&ctx, event) {
+            self.shell_provider.request_redraw();
+        }
+    }
+}
+
+#[test]
+fn lays_out_custom_elements_from_their_intrinsic_size() {
+    use crate::{Attribute, DocumentConfig, local_name};
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct Chart {
+        bars: Rc<Cell<usize>>,
+    }
+    impl CustomElement for Chart {
+        fn intrinsic_size(&self) -> taffy::Size<f32> {
+            taffy::Size {
+                width: 20.0 * self.bars.get() as f32,
+                height: 50.0,
+            }
+        }
+
+        fn paint(&self, _ctx: &CustomElementPaintCtx<'_>, _scene: &mut dyn DynPaintScene) {}
+
+        fn attribute_changed(&mut self, name: &QualName, value: Option<&str>) {
+            if name.local == local_name!("value") {
+                let bars = value.map_or(0, |value| value.split(',').count());
+                self.bars.set(bars);
+            }
+        }
+    }
+
+    let bars = Rc::new(Cell::new(0));
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    assert!(matches!(
+        doc.define_custom_element("chart", |_| unreachable!()),
+        Err(DefineCustomElementErr::InvalidName)
+    ));
+    let chart_bars = bars.clone();
+    doc.define_custom_element("bar-chart", move |element| {
+        let value = element.attr(local_name!("value")).unwrap_or("");
+        chart_bars.set(value.split(',').count());
+        Box::new(Chart {
+            bars: chart_bars.clone(),
+        })
+    })
+    .unwrap();
+
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    let attrs = vec![Attribute {
+        name: QualName::new(None, ns!(), local_name!("value")),
+        value: "1,2,3".to_string(),
+    }];
+    let chart = mutr.create_element(name("bar-chart"), attrs);
+    let fallback = mutr.create_text_node("Fallback content");
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[chart]);
+    mutr.append_children(chart, &[fallback]);
+    drop(mutr);
+    doc.resolve();
+
+    assert!(doc.custom_element(chart).is_some());
+    assert_eq!(doc.nodes[chart].final_layout.size.width, 60.0);
+    assert_eq!(doc.nodes[chart].final_layout.size.height, 50.0);
+
+    doc.mutate().set_attribute(
+        chart,
+        QualName::new(None, ns!(), local_name!("value")),
+        "1,2,3,4",
+    );
+    doc.resolve();
+    assert_eq!(bars.get(), 4);
+    assert_eq!(doc.nodes[chart].final_layout.size.width, 80.0);
+}
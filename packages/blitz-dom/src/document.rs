@@ -1,35 +1,51 @@
+use crate::cache::ResourceCache;
+use crate::custom_element::{CustomElement, CustomElementConstructor};
+use crate::encoding::Encoding;
 use crate::events::handle_dom_event;
+use crate::find::FindState;
+use crate::image_decoder::ImageDecoder;
 use crate::layout::construct::collect_layout_children;
+use crate::mutation_observer::MutationObservers;
 use crate::mutator::ViewportMut;
-use crate::net::{Resource, StylesheetLoader};
-use crate::node::{ImageData, NodeFlags, RasterImageData, SpecialElementData, Status, TextBrush};
+use crate::net::{ImageHandler, Resource, ResourceTracker, StylesheetLoader, TrackingNetProvider};
+use crate::network_log::{LoggingNetProvider, NetworkLog};
+use crate::node::{
+    AnimatedImageData, ImageData, NodeFlags, RasterImageData, SpecialElementData, Status, TextBrush,
+};
+use crate::policy::{PolicyNetProvider, ResourcePolicy};
+use crate::preload::PreloadNetProvider;
+use crate::scroll::ScrollAnimation;
+use crate::selection::{SelectionGesture, TextSelection};
 use crate::stylo_to_cursor_icon::stylo_to_cursor_icon;
 use crate::traversal::TreeTraverser;
 use crate::url::DocumentUrl;
 use crate::util::ImageType;
 use crate::{
-    DEFAULT_CSS, DocumentConfig, DocumentMutator, ElementData, EventDriver, Node, NodeData,
-    NoopEventHandler, TextNodeData,
+    DEFAULT_CSS, DocumentConfig, DocumentMutator, ElementData, EventDriver, HtmlParserProvider,
+    Node, NodeData, NoopEventHandler, ScrollIntoViewOptions, TextNodeData,
 };
 use app_units::Au;
 use blitz_traits::devtools::DevtoolSettings;
-use blitz_traits::events::{DomEvent, HitResult, UiEvent};
+use blitz_traits::events::{DomEvent, DomEventData, UiEvent};
 use blitz_traits::navigation::{DummyNavigationProvider, NavigationProvider};
-use blitz_traits::net::{DummyNetProvider, NetProvider, SharedProvider};
+use blitz_traits::net::{
+    DummyNetProvider, NetProvider, Request, RequestDestination, SharedProvider,
+};
 use blitz_traits::shell::{ColorScheme, DummyShellProvider, ShellProvider, Viewport};
 use cursor_icon::CursorIcon;
-use markup5ever::local_name;
+use markup5ever::{LocalName, local_name};
 use parley::FontContext;
 use peniko::{Blob, kurbo};
 use selectors::{Element, matching::QuirksMode};
 use slab::Slab;
 use std::any::Any;
-use std::collections::{BTreeMap, Bound, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, Bound, HashMap, HashSet};
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::Context as TaskContext;
+use std::time::{Duration, Instant};
 use style::Atom;
 use style::attr::{AttrIdentifier, AttrValue};
 use style::data::{ElementData as StyloElementData, ElementStyles};
@@ -62,6 +78,12 @@ pub trait Document: Deref<Target = BaseDocument> + DerefMut + 'static {
         driver.handle_ui_event(event);
     }
 
+    /// Dispatch a [`DomEvent`] to the [`Document`], running any event handlers and default actions
+    fn handle_dom_event(&mut self, event: DomEvent) {
+        let mut driver = EventDriver::new((*self).mutate(), NoopEventHandler);
+        driver.handle_dom_event(event);
+    }
+
     /// Poll any pending async operations, and flush changes to the underlying [`BaseDocument`]
     fn poll(&mut self, task_context: Option<TaskContext>) -> bool {
         // Default implementation does nothing
@@ -69,6 +91,20 @@ pub trait Document: Deref<Target = BaseDocument> + DerefMut + 'static {
         false
     }
 
+    /// Update the document's [`DocumentReadyState`], firing the `DOMContentLoaded` and `load` events if they are due.
+    ///
+    /// If resources are still loading then the waker from the `task_context` (if provided) will be woken once
+    /// they have all finished loading. Returns `true` if any events were fired.
+    fn poll_ready_state(&mut self, task_context: Option<&TaskContext>) -> bool {
+        (*self).update_ready_state(task_context);
+        let events = (*self).take_lifecycle_events();
+        let fired_events = !events.is_empty();
+        for event in events {
+            self.handle_dom_event(event);
+        }
+        fired_events
+    }
+
     fn as_any_mut(&mut self) -> &mut dyn Any;
 
     /// Get the [`Document`]'s id
@@ -103,6 +139,18 @@ impl FontMetricsProvider for DummyFontMetricsProvider {
     }
 }
 
+/// How far a document has progressed through loading. Loosely represents <https://html.spec.whatwg.org/multipage/dom.html#current-document-readiness>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DocumentReadyState {
+    /// The document's content is still being parsed or built
+    #[default]
+    Loading,
+    /// The document's content has loaded, but resources (stylesheets, images, fonts, etc) are still loading
+    Interactive,
+    /// The document and all of it's resources have finished loading
+    Complete,
+}
+
 pub struct BaseDocument {
     /// ID of the document
     id: usize,
@@ -110,6 +158,8 @@ pub struct BaseDocument {
     // Config
     /// Base url for resolving linked resources (stylesheets, images, fonts, etc)
     pub(crate) url: DocumentUrl,
+    /// The character encoding of the document. Used as the fallback encoding for linked stylesheets
+    pub(crate) encoding: &'static Encoding,
     // Devtool settings. Currently used to render debug overlays
     pub(crate) devtool_settings: DevtoolSettings,
     // Viewport details such as the dimensions, HiDPI scale, and zoom factor,
@@ -145,13 +195,51 @@ pub struct BaseDocument {
     pub(crate) active_node_id: Option<usize>,
     /// The node which recieved a mousedown event (if any)
     pub(crate) mousedown_node_id: Option<usize>,
+    /// The selected text (outside of text inputs)
+    pub(crate) text_selection: Option<TextSelection>,
+    /// The state of the mouse gesture which is selecting text
+    pub(crate) selection_gesture: SelectionGesture,
+    /// The results of the last find-in-page search
+    pub(crate) find_state: FindState,
+    /// In-progress smooth scrolls
+    pub(crate) scroll_animations: Vec<ScrollAnimation>,
     /// Whether there are active animations (so we should re-render every frame)
     pub(crate) is_animating: bool,
+    /// The instant from which document time is measured
+    pub(crate) time_origin: Instant,
+    /// Nodes which have an animated image (either as their content or as a background image)
+    pub(crate) animated_images: HashSet<usize>,
+    /// Whether any of the animated images were visible in the viewport when the document was last resolved
+    pub(crate) has_visible_animated_images: bool,
+    /// `<img loading="lazy">` nodes whose image has not yet been fetched
+    pub(crate) lazy_images: HashSet<usize>,
+    /// The worker pool on which images are decoded
+    pub(crate) image_decoder: ImageDecoder,
+    /// Cache of decoded images and fonts (which may be shared with other documents)
+    pub(crate) resource_cache: Option<ResourceCache>,
+    /// The URL of the icon selected from the document's `<link rel="icon">` elements
+    pub(crate) window_icon_url: Option<Url>,
+    /// The color from the document's `<meta name="theme-color">` element
+    pub(crate) theme_color: Option<[u8; 4]>,
+    /// Requests for `<link rel="prefetch">` nodes which will be made once the document has loaded
+    pub(crate) pending_prefetches: Vec<Request>,
 
     /// Map of node ID's for fast lookups
     pub(crate) nodes_to_id: HashMap<String, usize>,
     /// Map of `<style>` and `<link>` node IDs to their associated stylesheet
+    /// (excluding those in shadow trees, which are only applied to the shadow tree)
     pub(crate) nodes_to_stylesheet: BTreeMap<usize, DocumentStyleSheet>,
+    /// Map from custom element names to the constructors of their Rust implementations
+    pub(crate) custom_element_definitions: HashMap<LocalName, CustomElementConstructor>,
+    /// Map from custom element node IDs to their Rust implementations
+    pub(crate) custom_elements: HashMap<usize, Box<dyn CustomElement>>,
+    /// The IDs of all shadow roots
+    pub(crate) shadow_roots: BTreeSet<usize>,
+    /// The (node, slot) pairs from the last time slots were assigned
+    pub(crate) slot_assignments: Vec<(usize, usize)>,
+    /// Whether a stylesheet in a shadow tree has been added, removed or changed since the cascade data of
+    /// shadow roots was last rebuilt
+    pub(crate) shadow_stylesheets_dirty: bool,
     /// Stylesheets added by the useragent
     /// where the key is the hashed CSS
     pub(crate) ua_stylesheets: HashMap<String, DocumentStyleSheet>,
@@ -160,14 +248,37 @@ pub struct BaseDocument {
     /// Set of changed nodes for updating the accessibility tree
     pub(crate) changed_nodes: HashSet<usize>,
 
+    /// Policy restricting which resources the document may load
+    pub(crate) resource_policy: Option<Arc<ResourcePolicy>>,
+    /// Tracks resources which have been requested but not yet loaded
+    pub(crate) resource_tracker: ResourceTracker,
+    /// How far the document has progressed through loading
+    pub(crate) ready_state: DocumentReadyState,
+    /// Lifecycle events (`DOMContentLoaded`, `load`) which are due to be fired
+    pub(crate) pending_lifecycle_events: Vec<DomEventData>,
+
     // Service providers
     /// Network provider. Can be used to fetch assets.
+    ///
+    /// This wraps the configured provider such that pending requests are tracked and (if the document
+    /// has a [`ResourcePolicy`]) requests which violate the policy are blocked.
     pub net_provider: Arc<dyn NetProvider<Resource>>,
+    /// The configured network provider (without resource tracking or the resource policy applied)
+    pub(crate) unrestricted_net_provider: Arc<dyn NetProvider<Resource>>,
+    /// Serves requests for resources from `<link rel="preload">` elements (wraps the restricted network provider)
+    pub(crate) preload_net_provider: PreloadNetProvider,
+    /// Log of the requests made by the document
+    pub(crate) network_log: NetworkLog,
+    /// Observers registered with `observe_mutations` (and the records which are waiting to be delivered to them)
+    pub(crate) mutation_observers: MutationObservers,
     /// Navigation provider. Can be used to navigate to a new page (bubbles up the event
     /// on e.g. clicking a Link)
     pub navigation_provider: Arc<dyn NavigationProvider>,
     /// Shell provider. Can be used to request a redraw or set the cursor icon
     pub shell_provider: Arc<dyn ShellProvider>,
+    /// HTML parser provider. Used to parse HTML fragments (e.g. when setting the inner HTML of an element).
+    /// If there is no provider then setting the HTML of a node has no effect.
+    pub html_parser_provider: Option<Arc<dyn HtmlParserProvider>>,
 }
 
 pub(crate) fn make_device(viewport: &Viewport) -> Device {
@@ -233,7 +344,9 @@ impl BaseDocument {
         let shell_provider = config
             .shell_provider
             .unwrap_or_else(|| Arc::new(DummyShellProvider));
+        let html_parser_provider = config.html_parser_provider;
 
+        let time_origin = Instant::now();
         let mut doc = Self {
             id,
             guard,
@@ -245,8 +358,14 @@ impl BaseDocument {
             devtool_settings: DevtoolSettings::default(),
             viewport_scroll: kurbo::Point::ZERO,
             url: base_url,
+            encoding: encoding_rs::UTF_8,
             ua_stylesheets: HashMap::new(),
             nodes_to_stylesheet: BTreeMap::new(),
+            custom_element_definitions: HashMap::new(),
+            custom_elements: HashMap::new(),
+            shadow_roots: BTreeSet::new(),
+            slot_assignments: Vec::new(),
+            shadow_stylesheets_dirty: false,
             font_ctx,
             layout_ctx: parley::LayoutContext::new(),
 
@@ -254,14 +373,38 @@ impl BaseDocument {
             focus_node_id: None,
             active_node_id: None,
             mousedown_node_id: None,
+            text_selection: None,
+            selection_gesture: SelectionGesture::default(),
+            find_state: FindState::default(),
+            scroll_animations: Vec::new(),
             is_animating: false,
+            time_origin,
+            animated_images: HashSet::new(),
+            has_visible_animated_images: false,
+            lazy_images: HashSet::new(),
+            image_decoder: config.image_decoder.unwrap_or_default(),
+            resource_cache: config.resource_cache,
+            window_icon_url: None,
+            theme_color: None,
+            pending_prefetches: Vec::new(),
             changed_nodes: HashSet::new(),
             controls_to_form: HashMap::new(),
-            net_provider,
+            resource_policy: config.resource_policy.map(Arc::new),
+            resource_tracker: ResourceTracker::default(),
+            ready_state: DocumentReadyState::Loading,
+            pending_lifecycle_events: Vec::new(),
+            net_provider: net_provider.clone(),
+            preload_net_provider: PreloadNetProvider::new(net_provider.clone()),
+            network_log: NetworkLog::new(time_origin),
+            mutation_observers: MutationObservers::default(),
+            unrestricted_net_provider: net_provider,
             navigation_provider,
             shell_provider,
+            html_parser_provider,
         };
 
+        doc.wrap_net_provider();
+
         // Initialise document with root Document node
         doc.create_node(NodeData::Document);
         doc.root_node_mut().flags.insert(NodeFlags::IS_IN_DOCUMENT);
@@ -293,7 +436,100 @@ impl BaseDocument {
 
     /// Set the Document's networking provider
     pub fn set_net_provider(&mut self, net_provider: SharedProvider<Resource>) {
-        self.net_provider = net_provider;
+        self.unrestricted_net_provider = net_provider;
+        self.wrap_net_provider();
+    }
+
+    /// Set the Document's resource policy. Passing `None` allows all resources to be loaded.
+    pub fn set_resource_policy(&mut self, resource_policy: Option<ResourcePolicy>) {
+        self.resource_policy = resource_policy.map(Arc::new);
+        self.wrap_net_provider();
+    }
+
+    /// Wrap the net provider such that it tracks pending resources, serves preloaded resources, logs requests and
+    /// enforces the document's resource policy (if any)
+    fn wrap_net_provider(&mut self) {
+        let net_provider: SharedProvider<Resource> = match &self.resource_policy {
+            Some(policy) => Arc::new(PolicyNetProvider {
+                inner: self.unrestricted_net_provider.clone(),
+                policy: policy.clone(),
+                document_url: (*self.url).clone(),
+            }),
+            None => self.unrestricted_net_provider.clone(),
+        };
+        let net_provider = Arc::new(LoggingNetProvider {
+            inner: net_provider,
+            log: self.network_log.clone(),
+        });
+        self.preload_net_provider = self.preload_net_provider.with_inner(net_provider);
+        self.net_provider = Arc::new(TrackingNetProvider {
+            inner: Arc::new(self.preload_net_provider.clone()),
+            tracker: self.resource_tracker.clone(),
+        });
+    }
+
+    /// The number of resources (stylesheets, images, fonts, etc) which have been requested but not yet loaded
+    pub fn pending_resource_count(&self) -> usize {
+        self.resource_tracker.pending_count()
+    }
+
+    /// A handle which can be used to observe the number of pending resources, or to wait
+    /// for all pending resources to finish loading
+    pub fn resource_tracker(&self) -> ResourceTracker {
+        self.resource_tracker.clone()
+    }
+
+    /// A handle to the log of the network requests made by the document
+    pub fn network_log(&self) -> NetworkLog {
+        self.network_log.clone()
+    }
+
+    /// How far the document has progressed through loading
+    pub fn ready_state(&self) -> DocumentReadyState {
+        self.ready_state
+    }
+
+    /// Mark the document's content as loaded (for example, once HTML parsing has finished).
+    ///
+    /// This moves the document into the [`DocumentReadyState::Interactive`] state and queues a `DOMContentLoaded`
+    /// event. Once all pending resources have been loaded the document will move into the [`DocumentReadyState::Complete`]
+    /// state and a `load` event will be queued. Queued events are fired by [`Document::poll_ready_state`].
+    pub fn mark_content_loaded(&mut self) {
+        if self.ready_state == DocumentReadyState::Loading {
+            self.ready_state = DocumentReadyState::Interactive;
+            self.pending_lifecycle_events
+                .push(DomEventData::ContentLoaded);
+        }
+        self.update_ready_state(None);
+    }
+
+    /// Move the document into the `Complete` state if all resources have loaded
+    pub(crate) fn update_ready_state(&mut self, task_context: Option<&TaskContext>) {
+        if self.ready_state == DocumentReadyState::Interactive {
+            if self.pending_resource_count() == 0 {
+                self.ready_state = DocumentReadyState::Complete;
+                self.pending_lifecycle_events.push(DomEventData::Load);
+                self.start_prefetches();
+            } else if let Some(task_context) = task_context {
+                self.resource_tracker.register_waker(task_context.waker());
+            }
+        }
+    }
+
+    /// Take any lifecycle events which are due to be fired
+    pub(crate) fn take_lifecycle_events(&mut self) -> Vec<DomEvent> {
+        let root_id = self.root_node().id;
+        self.pending_lifecycle_events
+            .drain(..)
+            .map(|data| DomEvent::new(root_id, data))
+            .collect()
+    }
+
+    /// Check whether the document's resource policy allows loading the specified URL
+    pub(crate) fn is_allowed_by_policy(&self, url: &Url, destination: RequestDestination) -> bool {
+        self.resource_policy
+            .as_ref()
+            .is_none_or(|policy| policy.check(&self.url, url, destination))
     }
 
     /// Set the Document's navigation provider
@@ -306,9 +542,27 @@ impl BaseDocument {
         self.shell_provider = shell_provider;
     }
 
+    /// Set the Document's HTML parser provider
+    pub fn set_html_parser_provider(&mut self, html_parser_provider: Arc<dyn HtmlParserProvider>) {
+        self.html_parser_provider = Some(html_parser_provider);
+    }
+
     /// Set base url for resolving linked resources (stylesheets, images, fonts, etc)
     pub fn set_base_url(&mut self, url: &str) {
         self.url = DocumentUrl::from(Url::parse(url).unwrap());
+        self.wrap_net_provider();
+    }
+
+    /// The character encoding of the document
+    pub fn encoding(&self) -> &'static Encoding {
+        self.encoding
+    }
+
+    /// Set the character encoding of the document (for example, after decoding the document's HTML).
+    ///
+    /// This is used as the fallback encoding for stylesheets which don't specify their own encoding.
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
     }
 
     pub fn guard(&self) -> &SharedRwLock {
@@ -467,6 +721,16 @@ impl BaseDocument {
 
         // Create new node
         let new_node_id = self.create_node(data);
+        // Shadow trees and slot assignments are not cloned, but the contents of `<template>`s are
+        let template_contents = self.nodes[new_node_id]
+            .element_data()
+            .and_then(|element| element.template_contents)
+            .map(|contents_id| self.deep_clone_node(contents_id));
+        if let Some(element) = self.nodes[new_node_id].element_data_mut() {
+            element.template_contents = template_contents;
+            element.shadow_root = None;
+            element.assigned_nodes.clear();
+        }
 
         // Recursively clone children
         let new_children: Vec<usize> = children
@@ -546,7 +810,12 @@ impl BaseDocument {
             origin,
             ServoArc::new(self.guard.wrap(MediaList::empty())),
             self.guard.clone(),
-            Some(&StylesheetLoader(self.id, self.net_provider.clone())),
+            Some(&StylesheetLoader(
+                self.id,
+                self.net_provider.clone(),
+                self.encoding,
+                self.resource_cache.clone(),
+            )),
             None,
             QuirksMode::NoQuirks,
             AllowImportRules::Yes,
@@ -562,6 +831,14 @@ impl BaseDocument {
     }
 
     pub fn add_stylesheet_for_node(&mut self, stylesheet: DocumentStyleSheet, node_id: usize) {
+        // Stylesheets in shadow trees are collected from the tree when styles are resolved
+        if self.containing_shadow_root(node_id).is_some() {
+            let element = &mut self.nodes[node_id].element_data_mut().unwrap();
+            element.special_data = SpecialElementData::Stylesheet(stylesheet);
+            self.shadow_stylesheets_dirty = true;
+            return;
+        }
+
         let old = self.nodes_to_stylesheet.insert(node_id, stylesheet.clone());
 
         if let Some(old) = old {
@@ -592,57 +869,28 @@ impl BaseDocument {
     }
 
     pub fn load_resource(&mut self, resource: Resource) {
+        self.apply_resource(resource);
+        self.resource_tracker.decrement();
+        self.update_ready_state(None);
+    }
+
+    fn apply_resource(&mut self, resource: Resource) {
         match resource {
             Resource::Css(node_id, css) => {
                 self.add_stylesheet_for_node(css, node_id);
             }
             Resource::Image(node_id, kind, width, height, image_data) => {
-                let node = self.get_node_mut(node_id).unwrap();
-
-                match kind {
-                    ImageType::Image => {
-                        node.element_data_mut().unwrap().special_data =
-                            SpecialElementData::Image(Box::new(ImageData::Raster(
-                                RasterImageData::new(width, height, image_data),
-                            )));
-
-                        // Clear layout cache
-                        node.cache.clear();
-                    }
-                    ImageType::Background(idx) => {
-                        if let Some(Some(bg_image)) = node
-                            .element_data_mut()
-                            .and_then(|el| el.background_images.get_mut(idx))
-                        {
-                            bg_image.status = Status::Ok;
-                            bg_image.image =
-                                ImageData::Raster(RasterImageData::new(width, height, image_data))
-                        }
-                    }
-                }
+                let image = ImageData::Raster(RasterImageData::new(width, height, image_data));
+                self.set_image_data(node_id, kind, image);
+            }
+            Resource::AnimatedImage(node_id, kind, frames) => {
+                let image = AnimatedImageData::new(frames, self.current_time());
+                self.set_image_data(node_id, kind, ImageData::Animated(image));
+                self.animated_images.insert(node_id);
             }
             #[cfg(feature = "svg")]
             Resource::Svg(node_id, kind, tree) => {
-                let node = self.get_node_mut(node_id).unwrap();
-
-                match kind {
-                    ImageType::Image => {
-                        node.element_data_mut().unwrap().special_data =
-                            SpecialElementData::Image(Box::new(ImageData::Svg(tree)));
-
-                        // Clear layout cache
-                        node.cache.clear();
-                    }
-                    ImageType::Background(idx) => {
-                        if let Some(Some(bg_image)) = node
-                            .element_data_mut()
-                            .and_then(|el| el.background_images.get_mut(idx))
-                        {
-                            bg_image.status = Status::Ok;
-                            bg_image.image = ImageData::Svg(tree);
-                        }
-                    }
-                }
+                self.set_image_data(node_id, kind, ImageData::Svg(tree));
             }
             Resource::Font(bytes) => {
                 // TODO: Implement FontInfoOveride
@@ -651,6 +899,10 @@ impl BaseDocument {
                     .collection
                     .register_fonts(Blob::new(Arc::new(bytes)) as _, None);
             }
+            // Icons which have been superseded while they were being fetched are ignored
+            Resource::WindowIcon(url, icon) if self.window_icon_url.as_ref() == Some(&url) => {
+                self.shell_provider.set_window_icon(Some(icon));
+            }
             Resource::None => {
                 // Do nothing
             }
@@ -733,6 +985,9 @@ impl BaseDocument {
             return;
         }
 
+        // Slots must be assigned before styling as assigned nodes inherit styles from their slot
+        self.assign_slots();
+
         // we need to resolve stylist first since it will need to drive our layout bits
         self.resolve_stylist();
 
@@ -744,25 +999,17 @@ impl BaseDocument {
 
         // Next we resolve layout with the data resolved by stlist
         self.resolve_layout();
-    }
 
-    // Takes (x, y) co-ordinates (relative to the )
-    pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
-        if TDocument::as_node(&&self.nodes[0])
-            .first_element_child()
-            .is_none()
-        {
-            println!("No DOM - not resolving");
-            return None;
-        }
-
-        self.root_element().hit(x, y)
+        self.advance_scroll_animations(self.current_time());
+        self.advance_animated_images(self.current_time());
+        self.load_lazy_images();
     }
 
     pub fn focus_next_node(&mut self) -> Option<usize> {
         let focussed_node_id = self.get_focussed_node_id()?;
         let id = self.next_node(&self.nodes[focussed_node_id], |node| node.is_focussable())?;
         self.set_focus_to(id);
+        self.scroll_into_view(id, ScrollIntoViewOptions::nearest());
         Some(id)
     }
 
@@ -909,8 +1156,14 @@ impl BaseDocument {
         &mut self.devtool_settings
     }
 
+    /// Whether the document is animating (and so should be re-rendered every frame)
     pub fn is_animating(&self) -> bool {
-        self.is_animating
+        self.is_animating || self.has_visible_animated_images || !self.scroll_animations.is_empty()
+    }
+
+    /// The document time: the time elapsed since the document was created
+    pub fn current_time(&self) -> Duration {
+        self.time_origin.elapsed()
     }
 
     /// Update the device and reset the stylist to process the new size
@@ -924,6 +1177,18 @@ impl BaseDocument {
             self.stylist.set_device(device, &guards)
         };
         self.stylist.force_stylesheet_origins_dirty(origins);
+        for &shadow_root_id in &self.shadow_roots {
+            if let NodeData::ShadowRoot(data) = &mut self.nodes[shadow_root_id].data {
+                data.author_styles.stylesheets.force_dirty();
+            }
+        }
+        self.shadow_stylesheets_dirty = true;
+
+        // The best image from a `srcset` depends on the scale factor and viewport size
+        self.update_image_sources();
+        // As may the `media` of icons and theme colors
+        self.update_window_icon();
+        self.update_theme_color();
     }
 
     pub fn stylist_device(&mut self) -> &Device {
@@ -1014,6 +1279,7 @@ impl BaseDocument {
     /// Will bubble scrolling up to parent node once it can no longer scroll further
     /// If we're already at the root node, bubbles scrolling up to the viewport
     pub fn scroll_node_by(&mut self, node_id: usize, x: f64, y: f64) {
+        self.cancel_scroll_animation(Some(node_id));
         let Some(node) = self.nodes.get_mut(node_id) else {
             return;
         };
@@ -1079,18 +1345,9 @@ impl BaseDocument {
 
     /// Scroll the viewport by the given values
     pub fn scroll_viewport_by(&mut self, x: f64, y: f64) {
-        let content_size = self.root_element().final_layout.size;
-        let new_scroll = (self.viewport_scroll.x - x, self.viewport_scroll.y - y);
-        let window_width = self.viewport.window_size.0 as f64 / self.viewport.scale() as f64;
-        let window_height = self.viewport.window_size.1 as f64 / self.viewport.scale() as f64;
-        self.viewport_scroll.x = f64::max(
-            0.0,
-            f64::min(new_scroll.0, content_size.width as f64 - window_width),
-        );
-        self.viewport_scroll.y = f64::max(
-            0.0,
-            f64::min(new_scroll.1, content_size.height as f64 - window_height),
-        )
+        self.cancel_scroll_animation(None);
+        let new_scroll = self.viewport_scroll - kurbo::Vec2::new(x, y);
+        self.viewport_scroll = self.clamp_viewport_scroll(new_scroll);
     }
 
     pub fn viewport_scroll(&self) -> kurbo::Point {
@@ -1111,6 +1368,147 @@ impl BaseDocument {
             .map(|node_id| &self.nodes[node_id])
     }
 
+    fn set_image_data(&mut self, node_id: usize, kind: ImageType, image: ImageData) {
+        let node = self.get_node_mut(node_id).unwrap();
+
+        match kind {
+            ImageType::Image => {
+                node.element_data_mut().unwrap().special_data =
+                    SpecialElementData::Image(Box::new(image));
+
+                // Clear layout cache
+                node.cache.clear();
+            }
+            ImageType::Background(idx) => {
+                if let Some(Some(bg_image)) = node
+                    .element_data_mut()
+                    .and_then(|el| el.background_images.get_mut(idx))
+                {
+                    bg_image.status = Status::Ok;
+                    bg_image.image = image;
+                }
+            }
+        }
+    }
+
+    /// Fetch and decode the image for an `<img>` node
+    pub(crate) fn fetch_image(&self, node_id: usize, url: Url) {
+        let mut handler = ImageHandler::new(node_id, ImageType::Image, self.image_decoder.clone());
+        let mut request = Request::get(url.clone())
+            .with_destination(RequestDestination::Image)
+            .with_initiator(node_id);
+        if let Some(cache) = &self.resource_cache {
+            handler = handler.with_cache(cache.clone(), url);
+            request = cache.revalidate(request);
+        }
+        self.net_provider
+            .fetch(self.id(), request, Box::new(handler));
+    }
+
+    /// Fetch the images of lazy-loaded `<img>` nodes which are within [`LAZY_LOAD_MARGIN`] of the viewport
+    pub(crate) fn load_lazy_images(&mut self) {
+        if self.lazy_images.is_empty() {
+            return;
+        }
+
+        let viewport_size = self.stylist.device().au_viewport_size();
+        let viewport = kurbo::Rect::from_origin_size(
+            self.viewport_scroll,
+            (
+                viewport_size.width.to_f64_px(),
+                viewport_size.height.to_f64_px(),
+            ),
+        )
+        .inflate(LAZY_LOAD_MARGIN, LAZY_LOAD_MARGIN);
+
+        let node_ids: Vec<usize> = self.lazy_images.iter().copied().collect();
+        for node_id in node_ids {
+            let Some(node) = self.nodes.get(node_id) else {
+                self.lazy_images.remove(&node_id);
+                continue;
+            };
+            let Some(source) = node.element_data().and_then(|el| el.image_source.clone()) else {
+                self.lazy_images.remove(&node_id);
+                continue;
+            };
+            if !node.flags.is_in_document() {
+                continue;
+            }
+
+            // Images which haven't loaded yet may have a zero-sized box, so the edges of the rects
+            // are considered to be intersecting if they touch
+            let position = node.absolute_position(0.0, 0.0);
+            let size = node.final_layout.size;
+            let (x, y) = (position.x as f64, position.y as f64);
+            let is_near_viewport = x <= viewport.x1
+                && x + size.width as f64 >= viewport.x0
+                && y <= viewport.y1
+                && y + size.height as f64 >= viewport.y0;
+            if is_near_viewport {
+                self.lazy_images.remove(&node_id);
+                self.fetch_image(node_id, source.url);
+            }
+        }
+    }
+
+    /// Advance animated images to the frame for document time `time`, and record whether any of them
+    /// are visible in the viewport (in which case the document needs to keep re-rendering).
+    pub(crate) fn advance_animated_images(&mut self, time: Duration) {
+        let viewport_size = self.stylist.device().au_viewport_size();
+        let viewport = kurbo::Rect::from_origin_size(
+            self.viewport_scroll,
+            (
+                viewport_size.width.to_f64_px(),
+                viewport_size.height.to_f64_px(),
+            ),
+        );
+
+        let mut has_visible_animated_images = false;
+        let node_ids: Vec<usize> = self.animated_images.iter().copied().collect();
+        for node_id in node_ids {
+            let Some(node) = self.nodes.get_mut(node_id) else {
+                self.animated_images.remove(&node_id);
+                continue;
+            };
+            let Some(element) = node.data.downcast_element_mut() else {
+                self.animated_images.remove(&node_id);
+                continue;
+            };
+
+            let mut is_animated = false;
+            if let Some(ImageData::Animated(image)) = element.image_data_mut() {
+                image.advance_to(time);
+                is_animated = true;
+            }
+            for bg_image in element.background_images.iter_mut().flatten() {
+                if let ImageData::Animated(image) = &mut bg_image.image {
+                    image.advance_to(time);
+                    is_animated = true;
+                }
+            }
+
+            if !is_animated {
+                self.animated_images.remove(&node_id);
+                continue;
+            }
+            if !node.flags.is_in_document() {
+                continue;
+            }
+
+            let position = node.absolute_position(0.0, 0.0);
+            let size = node.final_layout.size;
+            let rect = kurbo::Rect::from_origin_size(
+                (position.x as f64, position.y as f64),
+                (size.width as f64, size.height as f64),
+            );
+            if !rect.is_zero_area() && rect.overlaps(viewport) {
+                has_visible_animated_images = true;
+            }
+        }
+
+        self.has_visible_animated_images = has_visible_animated_images;
+    }
+
     pub(crate) fn compute_is_animating(&self) -> bool {
         TreeTraverser::new(self).any(|node_id| {
             let node = &self.nodes[node_id];
@@ -1126,6 +1524,9 @@ impl BaseDocument {
     }
 }
 
+/// How close (in CSS pixels) to the viewport a lazy-loaded image must be for it to be fetched
+const LAZY_LOAD_MARGIN: f64 = 1250.0;
+
 impl AsRef<BaseDocument> for BaseDocument {
     fn as_ref(&self) -> &BaseDocument {
         self
@@ -1137,3 +1538,60 @@ impl AsMut<BaseDocument> for BaseDocument {
         self
     }
 }
+
+#[test]
+fn fetches_lazy_images_near_the_viewport() {
+    use crate::Attribute;
+    use blitz_traits::net::BoxedHandler;
+    use markup5ever::{LocalName, QualName, ns};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct RecordingProvider(Mutex<Vec<String>>);
+    impl NetProvider<Resource> for RecordingProvider {
+        fn fetch(&self, _doc_id: usize, request: Request, _handler: BoxedHandler<Resource>) {
+            self.0.lock().unwrap().push(request.url.path().to_string());
+        }
+    }
+
+    let provider = Arc::new(RecordingProvider::default());
+    let mut doc = BaseDocument::new(DocumentConfig {
+        base_url: Some("https://example.com/".into()),
+        net_provider: Some(provider.clone()),
+        ..Default::default()
+    });
+
+    let qual_name = |name: &str| QualName::new(None, ns!(html), LocalName::from(name));
+    let attr = |name: &str, value: &str| Attribute {
+        name: qual_name(name),
+        value: value.to_string(),
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(qual_name("html"), vec![]);
+    let spacer = mutr.create_element(
+        qual_name("div"),
+        vec![attr("style", "display: block; height: 5000px")],
+    );
+    let image = |mutr: &mut DocumentMutator, src: &str, loading: &str| {
+        mutr.create_element(
+            qual_name("img"),
+            vec![
+                attr("src", src),
+                attr("loading", loading),
+                attr("style", "display: block; width: 10px; height: 10px"),
+            ],
+        )
+    };
+    let eager = image(&mut mutr, "eager.png", "eager");
+    let lazy = image(&mut mutr, "lazy.png", "lazy");
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[spacer, eager, lazy]);
+    drop(mutr);
+
+    doc.resolve();
+    assert_eq!(*provider.0.lock().unwrap(), ["/eager.png"]);
+
+    doc.set_viewport_scroll(kurbo::Point::new(0.0, 4000.0));
+    doc.resolve();
+    assert_eq!(*provider.0.lock().unwrap(), ["/eager.png", "/lazy.png"]);
+}
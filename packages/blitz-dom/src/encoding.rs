@@ -0,0 +1,79 @@
+//! Character encoding detection and decoding for text resources
+
+use std::borrow::Cow;
+
+pub use encoding_rs::Encoding;
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE};
+
+/// Get the encoding specified by the `charset` parameter of a MIME type (e.g. `text/html; charset=shift_jis`)
+pub fn encoding_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            return None;
+        }
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+        Encoding::for_label(value.as_bytes())
+    })
+}
+
+/// Decode a stylesheet to text following <https://drafts.csswg.org/css-syntax/#input-byte-stream>.
+///
+/// The encoding is determined by (in order of precedence): a byte order mark, the `charset` of the `content_type`,
+/// an `@charset` rule at the very start of the stylesheet, and finally the `environment_encoding` (which should be
+/// the encoding of the document that referenced the stylesheet).
+pub fn decode_css<'a>(
+    bytes: &'a [u8],
+    content_type: Option<&str>,
+    environment_encoding: &'static Encoding,
+) -> Cow<'a, str> {
+    let encoding = content_type
+        .and_then(encoding_from_content_type)
+        .or_else(|| charset_rule_encoding(bytes))
+        .unwrap_or(environment_encoding);
+
+    // Note: `decode` gives precedence to a BOM if one is present
+    let (css, _encoding, _had_errors) = encoding.decode(bytes);
+    css
+}
+
+/// Get the encoding specified by an `@charset "...";` rule at the very start of a stylesheet
+fn charset_rule_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    const PREFIX: &[u8] = b"@charset \"";
+    let rest = bytes.strip_prefix(PREFIX)?;
+    let len = rest.iter().take(1024).position(|&b| b == b'"')?;
+    if rest.get(len + 1) != Some(&b';') {
+        return None;
+    }
+
+    // A stylesheet which declares itself as UTF-16 must actually be ASCII-compatible
+    // for us to have been able to read the rule, so use UTF-8 instead.
+    match Encoding::for_label(&rest[..len])? {
+        encoding if encoding == UTF_16BE || encoding == UTF_16LE => Some(UTF_8),
+        encoding => Some(encoding),
+    }
+}
+
+#[test]
+fn css_encoding_precedence() {
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    // "é" in windows-1252
+    let latin1 = b"@charset \"iso-8859-1\"; a::before { content: \"\xE9\" }";
+    assert!(decode_css(latin1, None, UTF_8).contains('é'));
+    assert!(decode_css(latin1, Some("text/css; charset=utf-8"), UTF_8).contains('\u{FFFD}'));
+
+    let no_rule = b"a::before { content: \"\xE9\" }";
+    assert!(decode_css(no_rule, None, WINDOWS_1252).contains('é'));
+    assert!(
+        decode_css(
+            no_rule,
+            Some("text/css;charset=\"windows-1252\""),
+            SHIFT_JIS
+        )
+        .contains('é')
+    );
+
+    let bom = b"\xEF\xBB\xBFa::before { content: \"\xC3\xA9\" }";
+    assert!(decode_css(bom, Some("text/css; charset=windows-1252"), WINDOWS_1252).contains('é'));
+}
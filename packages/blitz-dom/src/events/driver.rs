@@ -2,6 +2,12 @@ use crate::{BaseDocument, DocumentMutator};
 use blitz_traits::events::{BlitzMouseButtonEvent, DomEvent, DomEventData, EventState, UiEvent};
 use std::collections::VecDeque;
 
+/// Handles events as they are dispatched to the document.
+///
+/// The `chain` is the event's target followed by it's ancestors in the flat tree (which pass through the `<slot>`s
+/// that nodes are assigned to and cross from shadow trees into their hosts). Events are retargeted as they cross
+/// out of shadow trees (see [`BaseDocument::retarget`]): the handler is called once for each part of the chain
+/// with the `event.target` that is observed from those nodes.
 pub trait EventHandler {
     fn handle_event(
         &mut self,
@@ -116,8 +122,23 @@ impl<'doc, Handler: EventHandler> EventDriver<'doc, Handler> {
             };
 
             let mut event_state = EventState::default();
-            self.handler
-                .handle_event(&chain, &mut event, &mut self.mutr, &mut event_state);
+            let target = event.target;
+            let mut start = 0;
+            while start < chain.len() && !event_state.propagation_is_stopped() {
+                event.target = self.doc().retarget(target, chain[start]);
+                let end = chain[start..]
+                    .iter()
+                    .position(|&node_id| self.doc().retarget(target, node_id) != event.target)
+                    .map_or(chain.len(), |len| start + len);
+                self.handler.handle_event(
+                    &chain[start..end],
+                    &mut event,
+                    &mut self.mutr,
+                    &mut event_state,
+                );
+                start = end;
+            }
+            event.target = target;
 
             if !event_state.is_cancelled() {
                 self.doc_mut()
@@ -126,3 +147,58 @@ impl<'doc, Handler: EventHandler> EventDriver<'doc, Handler> {
         }
     }
 }
+
+#[test]
+fn retargets_events_from_shadow_trees() {
+    use crate::DocumentConfig;
+    use crate::node::ShadowRootMode;
+    use blitz_traits::events::{MouseEventButton, MouseEventButtons};
+    use keyboard_types::Modifiers;
+    use markup5ever::{LocalName, QualName, ns};
+
+    // Records the target observed by each node in the chain
+    struct RecordingHandler(Vec<(usize, usize)>);
+    impl EventHandler for RecordingHandler {
+        fn handle_event(
+            &mut self,
+            chain: &[usize],
+            event: &mut DomEvent,
+            _mutr: &mut DocumentMutator<'_>,
+            _event_state: &mut EventState,
+        ) {
+            self.0
+                .extend(chain.iter().map(|&node_id| (node_id, event.target)));
+        }
+    }
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    let host = mutr.create_element(name("div"), Vec::new());
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[host]);
+    let shadow_root = mutr.attach_shadow(host, ShadowRootMode::Open).unwrap();
+    let button = mutr.create_element(name("span"), Vec::new());
+    mutr.append_children(shadow_root, &[button]);
+    drop(mutr);
+    doc.resolve();
+
+    let mut driver = EventDriver::new(doc.mutate(), RecordingHandler(Vec::new()));
+    let click = DomEventData::Click(BlitzMouseButtonEvent {
+        x: 0.0,
+        y: 0.0,
+        button: MouseEventButton::Main,
+        buttons: MouseEventButtons::Primary,
+        mods: Modifiers::empty(),
+    });
+    driver.handle_dom_event(DomEvent::new(button, click));
+
+    // Listeners outside the shadow tree see the host as the target
+    assert_eq!(
+        driver.handler.0,
+        [(button, button), (host, host), (body, host), (html, host)]
+    );
+}
@@ -27,6 +27,16 @@ pub(crate) fn handle_keypress<F: FnMut(DomEvent)>(
         return;
     }
 
+    let is_text_input_focussed = doc.focus_node_id.is_some_and(|node_id| {
+        doc.nodes[node_id]
+            .element_data()
+            .is_some_and(|el| el.text_input_data().is_some())
+    });
+    if !is_text_input_focussed {
+        handle_selection_keypress(doc, &event);
+        return;
+    }
+
     if let Some(node_id) = doc.focus_node_id {
         if target != node_id {
             return;
@@ -70,6 +80,25 @@ const ACTION_MOD: Modifiers = Modifiers::SUPER;
 #[cfg(not(target_os = "macos"))]
 const ACTION_MOD: Modifiers = Modifiers::CONTROL;
 
+/// Copy or extend the document's text selection
+fn handle_selection_keypress(doc: &mut BaseDocument, event: &BlitzKeyEvent) {
+    if !event.state.is_pressed() {
+        return;
+    }
+
+    let mods = event.modifiers;
+    let action_mod = mods.contains(ACTION_MOD);
+    match &event.key {
+        Key::Character(c) if action_mod && c.to_lowercase() == "c" => {
+            doc.copy_selected_text();
+        }
+        key if mods.contains(Modifiers::SHIFT) && doc.extend_text_selection(key, action_mod) => {
+            doc.shell_provider.request_redraw();
+        }
+        _ => {}
+    }
+}
+
 fn apply_keypress_event(
     input_data: &mut TextInputData,
     font_ctx: &mut FontContext,
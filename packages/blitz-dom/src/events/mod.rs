@@ -19,6 +19,10 @@ pub(crate) fn handle_dom_event<F: FnMut(DomEvent)>(
 ) {
     let target_node_id = event.target;
 
+    if doc.nodes[target_node_id].flags.is_custom_element() {
+        doc.handle_custom_element_event(event);
+    }
+
     match &event.data {
         DomEventData::MouseMove(mouse_event) => {
             let changed = handle_mousemove(
@@ -34,7 +38,7 @@ pub(crate) fn handle_dom_event<F: FnMut(DomEvent)>(
             }
         }
         DomEventData::MouseDown(event) => {
-            handle_mousedown(doc, target_node_id, event.x, event.y);
+            handle_mousedown(doc, target_node_id, event);
         }
         DomEventData::MouseUp(event) => {
             handle_mouseup(doc, target_node_id, event, dispatch_event);
@@ -57,5 +61,8 @@ pub(crate) fn handle_dom_event<F: FnMut(DomEvent)>(
         DomEventData::Input(_) => {
             // Do nothing (no default action)
         }
+        DomEventData::ContentLoaded | DomEventData::Load => {
+            // Do nothing (no default action)
+        }
     }
 }
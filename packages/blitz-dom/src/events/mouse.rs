@@ -4,7 +4,9 @@ use blitz_traits::{
         MouseEventButtons,
     },
     navigation::NavigationOptions,
+    net::RequestDestination,
 };
+use keyboard_types::Modifiers;
 use markup5ever::local_name;
 
 use crate::{BaseDocument, node::SpecialElementData};
@@ -18,6 +20,11 @@ pub(crate) fn handle_mousemove(
 ) -> bool {
     let mut changed = doc.set_hover_to(x, y);
 
+    if buttons.contains(MouseEventButtons::Primary) && doc.drag_text_selection(x, y) {
+        doc.shell_provider.request_redraw();
+        changed = true;
+    }
+
     let Some(hit) = doc.hit(x, y) else {
         return changed;
     };
@@ -60,7 +67,12 @@ pub(crate) fn handle_mousemove(
     changed
 }
 
-pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y: f32) {
+pub(crate) fn handle_mousedown(
+    doc: &mut BaseDocument,
+    target: usize,
+    event: &BlitzMouseButtonEvent,
+) {
+    let (x, y) = (event.x, event.y);
     let Some(hit) = doc.hit(x, y) else {
         return;
     };
@@ -68,6 +80,22 @@ pub(crate) fn handle_mousedown(doc: &mut BaseDocument, target: usize, x: f32, y:
         return;
     }
 
+    // Select document text (unless the mouse is over a text input, which has it's own selection)
+    let is_text_input = doc.nodes[target]
+        .element_data()
+        .is_some_and(|el| el.text_input_data().is_some());
+    if !is_text_input && event.button == MouseEventButton::Main {
+        let extend = event.mods.contains(Modifiers::SHIFT);
+        if doc.start_text_selection(x, y, extend) {
+            doc.shell_provider.request_redraw();
+        }
+        return;
+    }
+    if is_text_input && doc.text_selection().is_some() {
+        doc.clear_text_selection();
+        doc.shell_provider.request_redraw();
+    }
+
     let node = &mut doc.nodes[target];
     let Some(el) = node.data.downcast_element_mut() else {
         return;
@@ -101,6 +129,8 @@ pub(crate) fn handle_mouseup<F: FnMut(DomEvent)>(
     event: &BlitzMouseButtonEvent,
     mut dispatch_event: F,
 ) {
+    doc.end_text_selection();
+
     if doc.devtools().highlight_hover {
         let mut node = doc.get_node(target).unwrap();
         if event.button == MouseEventButton::Secondary {
@@ -193,6 +223,14 @@ pub(crate) fn handle_click<F: FnMut(DomEvent)>(
         } else if el.name.local == local_name!("a") {
             if let Some(href) = el.attr(local_name!("href")) {
                 if let Some(url) = doc.url.resolve_relative(href) {
+                    if let Some(fragment) = doc.url.same_document_fragment(&url) {
+                        let fragment = fragment.to_string();
+                        doc.scroll_to_fragment(&fragment);
+                        return;
+                    }
+                    if !doc.is_allowed_by_policy(&url, RequestDestination::Document) {
+                        return;
+                    }
                     doc.navigation_provider.navigate_to(NavigationOptions::new(
                         url,
                         String::from("text/plain"),
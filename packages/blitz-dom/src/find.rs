@@ -0,0 +1,235 @@
+//! Find-in-page: searching the document's rendered text
+
+use std::ops::Range;
+
+use peniko::kurbo;
+
+use crate::BaseDocument;
+use crate::scroll::ScrollIntoViewOptions;
+
+/// Options for [`BaseDocument::find`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    /// Only match text with the same case as the query
+    pub case_sensitive: bool,
+    /// Only match whole words (matches may not be preceded or followed by a letter, digit or underscore)
+    pub whole_word: bool,
+}
+
+/// A match found by [`BaseDocument::find`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FindMatch {
+    /// The ID of the root node of the inline formatting context that the match is in
+    pub node_id: usize,
+    /// The byte range of the match in the text of the inline layout
+    pub range: Range<usize>,
+    /// The boxes (one per line) of the match in document coordinates (CSS pixels) at the time of the search
+    pub boxes: Vec<kurbo::Rect>,
+}
+
+/// The results of the last search
+#[derive(Debug, Clone, Default)]
+pub(crate) struct FindState {
+    pub(crate) matches: Vec<FindMatch>,
+    pub(crate) active: Option<usize>,
+}
+
+impl BaseDocument {
+    /// Search the text of the document for `query`. Matches may not span multiple inline formatting contexts.
+    ///
+    /// The matches replace those of the previous search and are highlighted when the document is painted. The first
+    /// match becomes the active match and is scrolled into view. Matches are not updated when the document changes,
+    /// so the search should be repeated after mutating the document.
+    pub fn find(&mut self, query: &str, options: FindOptions) -> &[FindMatch] {
+        let mut matches = Vec::new();
+        for node_id in self.inline_roots() {
+            let text = &self.inline_layout(node_id).unwrap().text;
+            for range in find_in_text(text, query, options) {
+                let boxes = self.text_range_boxes(node_id, range.clone());
+                matches.push(FindMatch {
+                    node_id,
+                    range,
+                    boxes,
+                });
+            }
+        }
+
+        let active = (!matches.is_empty()).then_some(0);
+        self.find_state = FindState { matches, active };
+        self.scroll_to_active_find_match();
+        self.shell_provider.request_redraw();
+        &self.find_state.matches
+    }
+
+    /// The matches from the last search
+    pub fn find_matches(&self) -> &[FindMatch] {
+        &self.find_state.matches
+    }
+
+    /// The index of the active match (if there are any matches)
+    pub fn active_find_match(&self) -> Option<usize> {
+        self.find_state.active
+    }
+
+    /// Make the next match active (wrapping around to the first match) and scroll it into view.
+    /// Returns the index of the new active match.
+    pub fn find_next(&mut self) -> Option<usize> {
+        let count = self.find_state.matches.len();
+        self.find_state.active = self.find_state.active.map(|index| (index + 1) % count);
+        self.scroll_to_active_find_match();
+        self.shell_provider.request_redraw();
+        self.find_state.active
+    }
+
+    /// Make the previous match active (wrapping around to the last match) and scroll it into view.
+    /// Returns the index of the new active match.
+    pub fn find_previous(&mut self) -> Option<usize> {
+        let count = self.find_state.matches.len();
+        self.find_state.active = self
+            .find_state
+            .active
+            .map(|index| (index + count - 1) % count);
+        self.scroll_to_active_find_match();
+        self.shell_provider.request_redraw();
+        self.find_state.active
+    }
+
+    /// Clear the matches of the last search (removing their highlights)
+    pub fn clear_find(&mut self) {
+        self.find_state = FindState::default();
+        self.shell_provider.request_redraw();
+    }
+
+    /// The ranges of an inline layout's text which match the last search, and whether each is the active match
+    pub fn find_highlights(&self, node_id: usize) -> Vec<(Range<usize>, bool)> {
+        let Some(text_layout) = self.inline_layout(node_id) else {
+            return Vec::new();
+        };
+        let text = &text_layout.text;
+        self.find_state
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.node_id == node_id)
+            .filter(|(_, m)| text.get(m.range.clone()).is_some())
+            .map(|(index, m)| (m.range.clone(), Some(index) == self.find_state.active))
+            .collect()
+    }
+
+    fn scroll_to_active_find_match(&mut self) {
+        let Some(index) = self.find_state.active else {
+            return;
+        };
+        let FindMatch { node_id, range, .. } = self.find_state.matches[index].clone();
+        let boxes = self.text_range_boxes(node_id, range);
+        if let Some(rect) = boxes.into_iter().reduce(|a, b| a.union(b)) {
+            self.scroll_rect_into_view(node_id, rect, ScrollIntoViewOptions::nearest());
+        }
+    }
+}
+
+/// The byte ranges of the (non-overlapping) matches of `query` in `text`
+fn find_in_text(text: &str, query: &str, options: FindOptions) -> Vec<Range<usize>> {
+    let mut matches = Vec::new();
+    if query.is_empty() {
+        return matches;
+    }
+
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut start = 0;
+    while let Some(c) = text[start..].chars().next() {
+        if let Some(len) = match_len(&text[start..], query, options.case_sensitive) {
+            let end = start + len;
+            let is_whole_word = !text[..start].chars().next_back().is_some_and(is_word_char)
+                && !text[end..].chars().next().is_some_and(is_word_char);
+            if !options.whole_word || is_whole_word {
+                matches.push(start..end);
+                start = end;
+                continue;
+            }
+        }
+        start += c.len_utf8();
+    }
+    matches
+}
+
+/// The length (in bytes) of the match if `haystack` starts with `query`
+fn match_len(haystack: &str, query: &str, case_sensitive: bool) -> Option<usize> {
+    let mut chars = haystack.char_indices();
+    for q in query.chars() {
+        let (_, c) = chars.next()?;
+        let is_match = c == q || (!case_sensitive && c.to_lowercase().eq(q.to_lowercase()));
+        if !is_match {
+            return None;
+        }
+    }
+    Some(chars.next().map_or(haystack.len(), |(index, _)| index))
+}
+
+#[test]
+fn finds_matches_across_inline_layouts() {
+    use crate::DocumentConfig;
+    use blitz_traits::shell::{ColorScheme, Viewport};
+    use markup5ever::{LocalName, QualName, ns};
+
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(800, 100, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    let mut paragraphs = Vec::new();
+    for text in [
+        "Blitz renders HTML",
+        "blitzing along",
+        "Lorem",
+        "ipsum",
+        "dolor",
+        "sit",
+        "The BLITZ end",
+    ] {
+        let p = mutr.create_element(name("p"), Vec::new());
+        let text = mutr.create_text_node(text);
+        mutr.append_children(body, &[p]);
+        mutr.append_children(p, &[text]);
+        paragraphs.push(p);
+    }
+    drop(mutr);
+    doc.resolve();
+
+    let matches = doc.find("blitz", FindOptions::default());
+    let found: Vec<_> = matches
+        .iter()
+        .map(|m| (m.node_id, m.range.clone()))
+        .collect();
+    assert_eq!(
+        found,
+        [
+            (paragraphs[0], 0..5),
+            (paragraphs[1], 0..5),
+            (paragraphs[6], 4..9)
+        ]
+    );
+    assert!(matches.iter().all(|m| m.boxes.len() == 1));
+
+    let options = FindOptions {
+        case_sensitive: true,
+        whole_word: true,
+    };
+    assert_eq!(doc.find("blitz", options).len(), 0);
+    assert_eq!(doc.find("BLITZ", options).len(), 1);
+
+    // Moving to the last match scrolls it into view
+    doc.find("blitz", FindOptions::default());
+    let scroll_y = doc.viewport_scroll().y;
+    assert!(doc.find_matches()[0].boxes[0].y0 >= scroll_y);
+    assert_eq!(doc.find_previous(), Some(2));
+    let rect = doc.find_matches()[2].boxes[0];
+    assert!(doc.viewport_scroll().y > scroll_y);
+    assert!(rect.y1 <= doc.viewport_scroll().y + 100.0);
+    assert_eq!(doc.find_highlights(paragraphs[6]), [(4..9, true)]);
+}
@@ -5,6 +5,7 @@ use crate::{
     traversal::{AncestorTraverser, TreeTraverser},
 };
 use blitz_traits::navigation::NavigationOptions;
+use blitz_traits::net::RequestDestination;
 use core::str::FromStr;
 use std::fmt::Display;
 
@@ -180,6 +181,10 @@ impl BaseDocument {
             }
         }
 
+        if !self.is_allowed_by_policy(&parsed_action, RequestDestination::Document) {
+            return;
+        }
+
         let navigation_options =
             NavigationOptions::new(parsed_action, enctype.to_string(), self.id())
                 .set_document_resource(post_resource);
@@ -0,0 +1,405 @@
+//! Element geometry queries: client rects and box metrics
+//! (see <https://drafts.csswg.org/cssom-view/#extension-to-the-element-interface>)
+
+use markup5ever::local_name;
+use parley::PositionedLayoutItem;
+use peniko::kurbo;
+use style::properties::generated::longhands::position::computed_value::T as Position;
+
+use crate::BaseDocument;
+
+/// The box metrics of an element (the equivalents of the DOM's `offset*`, `client*` and `scroll*` properties).
+/// All values are in CSS pixels.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ElementMetrics {
+    /// The nearest positioned ancestor (or the nearest `<td>`, `<th>`, `<table>` or `<body>`) of the element.
+    /// `None` for the root element, `<body>` and fixed position elements.
+    pub offset_parent: Option<usize>,
+    /// The horizontal offset of the border box from the padding box of the offset parent (ignoring transforms and scrolling)
+    pub offset_left: f64,
+    /// The vertical offset of the border box from the padding box of the offset parent (ignoring transforms and scrolling)
+    pub offset_top: f64,
+    /// The width of the border box (ignoring transforms)
+    pub offset_width: f64,
+    /// The height of the border box (ignoring transforms)
+    pub offset_height: f64,
+    /// The width of the left border
+    pub client_left: f64,
+    /// The width of the top border
+    pub client_top: f64,
+    /// The width of the padding box excluding any scrollbar (the width of the viewport for the root element)
+    pub client_width: f64,
+    /// The height of the padding box excluding any scrollbar (the height of the viewport for the root element)
+    pub client_height: f64,
+    /// The horizontal scroll offset (the viewport's scroll offset for the root element)
+    pub scroll_left: f64,
+    /// The vertical scroll offset (the viewport's scroll offset for the root element)
+    pub scroll_top: f64,
+    /// The width of the element's scrollable content (at least `client_width`)
+    pub scroll_width: f64,
+    /// The height of the element's scrollable content (at least `client_height`)
+    pub scroll_height: f64,
+}
+
+impl BaseDocument {
+    /// The transform from a node's local coordinate space (CSS pixels relative to the top-left corner of its border box)
+    /// to viewport coordinates. This accounts for the CSS transforms of the node and its ancestors, the scroll offsets
+    /// of its ancestors and the scroll offset of the viewport.
+    pub fn node_to_viewport_transform(&self, node_id: usize) -> kurbo::Affine {
+        let mut transform = kurbo::Affine::IDENTITY;
+        let mut node = &self.nodes[node_id];
+        loop {
+            if let Some(css_transform) = node.css_transform() {
+                transform = css_transform * transform;
+            }
+            let location = node.final_layout.location;
+            transform =
+                transform.then_translate(kurbo::Vec2::new(location.x as f64, location.y as f64));
+
+            let Some(parent_id) = node.layout_parent.get() else {
+                break;
+            };
+            node = &self.nodes[parent_id];
+            transform = transform.then_translate(-node.scroll_offset.to_vec2());
+        }
+        transform.then_translate(-self.viewport_scroll.to_vec2())
+    }
+
+    /// The smallest rectangle (in viewport coordinates) which contains the border boxes of the node, or (for inline
+    /// elements) the union of the node's line box fragments. Transforms are applied, so the rectangle is the bounding box
+    /// of the transformed border box.
+    ///
+    /// Returns `None` if the node is not a rendered element (e.g. it has `display: none` or is not in the document).
+    /// This is the equivalent of the DOM's `Element.getBoundingClientRect()`.
+    pub fn get_bounding_client_rect(&self, node_id: usize) -> Option<kurbo::Rect> {
+        self.get_client_rects(node_id)
+            .into_iter()
+            .reduce(|a, b| a.union(b))
+    }
+
+    /// The boxes (in viewport coordinates) of the node: a single border box for elements which generate a box, or one
+    /// box per line for inline elements which are laid out as part of an inline formatting context.
+    ///
+    /// Returns an empty list if the node is not a rendered element. This is the equivalent of the DOM's `Element.getClientRects()`.
+    pub fn get_client_rects(&self, node_id: usize) -> Vec<kurbo::Rect> {
+        if !self.is_rendered(node_id) {
+            return Vec::new();
+        }
+
+        match self.containing_inline_root(node_id) {
+            Some(root_id) => {
+                let transform = self.node_to_viewport_transform(root_id);
+                self.inline_fragments(root_id, node_id)
+                    .into_iter()
+                    .map(|rect| transform.transform_rect_bbox(rect))
+                    .collect()
+            }
+            None => {
+                let size = self.nodes[node_id].final_layout.size;
+                let border_box = kurbo::Rect::new(0.0, 0.0, size.width as f64, size.height as f64);
+                let transform = self.node_to_viewport_transform(node_id);
+                vec![transform.transform_rect_bbox(border_box)]
+            }
+        }
+    }
+
+    /// The box metrics of an element. Returns `None` if the node is not a rendered element.
+    ///
+    /// Inline elements (which do not generate their own box) have zero `client*` and `scroll*` metrics, and their
+    /// `offset*` metrics describe the bounding box of their line box fragments.
+    pub fn element_metrics(&self, node_id: usize) -> Option<ElementMetrics> {
+        if !self.is_rendered(node_id) {
+            return None;
+        }
+
+        let node = &self.nodes[node_id];
+        let offset_parent = self.offset_parent(node_id);
+        let parent_origin = offset_parent
+            .map(|parent_id| {
+                let border = self.nodes[parent_id].final_layout.border;
+                self.layout_position(parent_id)
+                    + kurbo::Vec2::new(border.left as f64, border.top as f64)
+            })
+            .unwrap_or(kurbo::Point::ZERO);
+
+        if let Some(root_id) = self.containing_inline_root(node_id) {
+            let origin = self.layout_position(root_id).to_vec2();
+            let rect = self
+                .inline_fragments(root_id, node_id)
+                .into_iter()
+                .reduce(|a, b| a.union(b))
+                .unwrap_or_default()
+                + origin;
+            return Some(ElementMetrics {
+                offset_parent,
+                offset_left: rect.x0 - parent_origin.x,
+                offset_top: rect.y0 - parent_origin.y,
+                offset_width: rect.width(),
+                offset_height: rect.height(),
+                ..Default::default()
+            });
+        }
+
+        let layout = &node.final_layout;
+        let position = self.layout_position(node_id);
+        let (client_width, client_height, scroll_offset, scroll_width, scroll_height);
+        if node_id == self.root_element().id {
+            (client_width, client_height) = self.viewport_size();
+            scroll_offset = self.viewport_scroll;
+            scroll_width = client_width.max(layout.size.width as f64);
+            scroll_height = client_height.max(layout.size.height as f64);
+        } else {
+            client_width = (layout.size.width
+                - layout.border.left
+                - layout.border.right
+                - layout.scrollbar_size.width)
+                .max(0.0) as f64;
+            client_height = (layout.size.height
+                - layout.border.top
+                - layout.border.bottom
+                - layout.scrollbar_size.height)
+                .max(0.0) as f64;
+            scroll_offset = node.scroll_offset;
+            scroll_width = client_width + layout.scroll_width() as f64;
+            scroll_height = client_height + layout.scroll_height() as f64;
+        }
+
+        Some(ElementMetrics {
+            offset_parent,
+            offset_left: position.x - parent_origin.x,
+            offset_top: position.y - parent_origin.y,
+            offset_width: layout.size.width as f64,
+            offset_height: layout.size.height as f64,
+            client_left: layout.border.left as f64,
+            client_top: layout.border.top as f64,
+            client_width,
+            client_height,
+            scroll_left: scroll_offset.x,
+            scroll_top: scroll_offset.y,
+            scroll_width,
+            scroll_height,
+        })
+    }
+
+    /// The offset parent of an element (see <https://drafts.csswg.org/cssom-view/#dom-htmlelement-offsetparent>)
+    pub fn offset_parent(&self, node_id: usize) -> Option<usize> {
+        let node = &self.nodes[node_id];
+        let is_positioned = |node_id: usize| {
+            self.nodes[node_id]
+                .primary_styles()
+                .is_some_and(|styles| styles.clone_position() != Position::Static)
+        };
+        if node_id == self.root_element().id
+            || node.data.is_element_with_tag_name(&local_name!("body"))
+            || node
+                .primary_styles()
+                .is_some_and(|styles| styles.clone_position() == Position::Fixed)
+        {
+            return None;
+        }
+
+        let mut maybe_ancestor = node.parent;
+        while let Some(ancestor_id) = maybe_ancestor {
+            let ancestor = &self.nodes[ancestor_id];
+            if !ancestor.is_element() {
+                return None;
+            }
+            let is_table_element = [local_name!("td"), local_name!("th"), local_name!("table")]
+                .iter()
+                .any(|tag| ancestor.data.is_element_with_tag_name(tag));
+            if is_positioned(ancestor_id)
+                || ancestor.data.is_element_with_tag_name(&local_name!("body"))
+                || (is_table_element && !is_positioned(node_id))
+            {
+                return Some(ancestor_id);
+            }
+            maybe_ancestor = ancestor.parent;
+        }
+        None
+    }
+
+    /// Whether a node is an element in the document which generates boxes (i.e. it and its ancestors are not
+    /// `display: none`). Stylo does not compute styles for the descendants of `display: none` elements.
+    fn is_rendered(&self, node_id: usize) -> bool {
+        self.nodes.get(node_id).is_some_and(|node| {
+            node.flags.is_in_document()
+                && node.is_element()
+                && node
+                    .primary_styles()
+                    .is_some_and(|styles| !styles.get_box().display.is_none())
+        })
+    }
+
+    /// The root of the inline formatting context that a node is laid out in, if the node does not generate its own
+    /// box (i.e. it is an inline element)
+    fn containing_inline_root(&self, node_id: usize) -> Option<usize> {
+        let node = &self.nodes[node_id];
+        if node.flags.is_inline_root() {
+            return None;
+        }
+        let parent_id = node.layout_parent.get()?;
+        let parent = &self.nodes[parent_id];
+        let is_layout_child = parent
+            .layout_children
+            .borrow()
+            .as_ref()
+            .is_some_and(|children| children.contains(&node_id));
+        if is_layout_child {
+            return None;
+        }
+
+        match parent.flags.is_inline_root() {
+            true => Some(parent_id),
+            false => self.containing_inline_root(parent_id),
+        }
+    }
+
+    /// The line box fragments (one per line) of a node which is laid out as part of the inline formatting context
+    /// rooted at `root_id`, relative to the root's border box
+    fn inline_fragments(&self, root_id: usize, node_id: usize) -> Vec<kurbo::Rect> {
+        let Some(text_layout) = self.inline_layout(root_id) else {
+            return Vec::new();
+        };
+        let layout = &text_layout.layout;
+        let scale = layout.scale() as f64;
+
+        // Text is attributed to the innermost element containing it, so fragments are made of the glyph runs and
+        // inline boxes of the node and any of its descendants
+        let mut node_ids = vec![node_id];
+        let mut index = 0;
+        while let Some(&id) = node_ids.get(index) {
+            node_ids.extend_from_slice(&self.nodes[id].children);
+            index += 1;
+        }
+
+        let root_layout = self.nodes[root_id].final_layout;
+        let content_box_offset = kurbo::Vec2::new(
+            (root_layout.border.left + root_layout.padding.left) as f64,
+            (root_layout.border.top + root_layout.padding.top) as f64,
+        );
+
+        let mut fragments = Vec::new();
+        for line in layout.lines() {
+            let metrics = line.metrics();
+            let line_top = (metrics.baseline - metrics.ascent) as f64;
+            let line_bottom = (metrics.baseline + metrics.descent) as f64;
+            let fragment = line
+                .items()
+                .filter_map(|item| match item {
+                    PositionedLayoutItem::GlyphRun(glyph_run) => {
+                        node_ids.contains(&glyph_run.style().brush.id).then(|| {
+                            let x = glyph_run.offset() as f64;
+                            let width = glyph_run.advance() as f64;
+                            kurbo::Rect::new(x, line_top, x + width, line_bottom)
+                        })
+                    }
+                    PositionedLayoutItem::InlineBox(inline_box) => {
+                        node_ids.contains(&(inline_box.id as usize)).then(|| {
+                            kurbo::Rect::from_origin_size(
+                                (inline_box.x as f64, inline_box.y as f64),
+                                (inline_box.width as f64, inline_box.height as f64),
+                            )
+                        })
+                    }
+                })
+                .reduce(|a, b| a.union(b));
+            if let Some(fragment) = fragment {
+                fragments.push(fragment.scale_from_origin(1.0 / scale) + content_box_offset);
+            }
+        }
+        fragments
+    }
+
+    /// The position of a node's border box in document coordinates, ignoring transforms and scrolling
+    fn layout_position(&self, node_id: usize) -> kurbo::Point {
+        let mut position = kurbo::Point::ZERO;
+        let mut maybe_node_id = Some(node_id);
+        while let Some(node_id) = maybe_node_id {
+            let node = &self.nodes[node_id];
+            position.x += node.final_layout.location.x as f64;
+            position.y += node.final_layout.location.y as f64;
+            maybe_node_id = node.layout_parent.get();
+        }
+        position
+    }
+}
+
+#[test]
+fn computes_client_rects_through_transforms_and_scroll() {
+    use crate::{Attribute, DocumentConfig, ScrollBehavior};
+    use blitz_traits::shell::{ColorScheme, Viewport};
+    use markup5ever::{LocalName, QualName, ns};
+
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(400, 300, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let style = |value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("style")),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), style("margin: 0"));
+    let scaled = mutr.create_element(
+        name("div"),
+        style("margin-left: 10px; width: 100px; height: 50px; transform: scale(2)"),
+    );
+    let scroller = mutr.create_element(
+        name("div"),
+        style("height: 100px; overflow: auto; border: 5px solid black"),
+    );
+    let inner_spacer = mutr.create_element(name("div"), style("height: 300px"));
+    let target = mutr.create_element(name("div"), style("height: 20px"));
+    let p = mutr.create_element(name("p"), style("margin: 0"));
+    let hello = mutr.create_text_node("Hello ");
+    let span = mutr.create_element(name("span"), Vec::new());
+    let world = mutr.create_text_node("world");
+    let spacer = mutr.create_element(name("div"), style("height: 1000px"));
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[scaled, scroller, p, spacer]);
+    mutr.append_children(scroller, &[inner_spacer, target]);
+    mutr.append_children(p, &[hello, span]);
+    mutr.append_children(span, &[world]);
+    drop(mutr);
+    doc.resolve();
+
+    // Transforms scale around the transform origin (the center of the border box by default)
+    assert_eq!(
+        doc.get_bounding_client_rect(scaled),
+        Some(kurbo::Rect::new(-40.0, -25.0, 160.0, 75.0))
+    );
+    let metrics = doc.element_metrics(scaled).unwrap();
+    assert_eq!((metrics.offset_left, metrics.offset_top), (10.0, 0.0));
+    assert_eq!((metrics.offset_width, metrics.offset_height), (100.0, 50.0));
+    assert_eq!(metrics.offset_parent, Some(body));
+
+    // Client rects account for the scroll offsets of ancestors and the viewport, offsets do not
+    doc.scroll_to(
+        scroller,
+        kurbo::Point::new(0.0, 100.0),
+        ScrollBehavior::Instant,
+    );
+    doc.scroll_viewport_to(kurbo::Point::new(0.0, 10.0), ScrollBehavior::Instant);
+    let rect = doc.get_bounding_client_rect(target).unwrap();
+    assert_eq!((rect.y0, rect.y1), (245.0, 265.0));
+    assert_eq!(doc.element_metrics(target).unwrap().offset_top, 355.0);
+
+    let metrics = doc.element_metrics(scroller).unwrap();
+    assert_eq!((metrics.client_left, metrics.client_top), (5.0, 5.0));
+    assert_eq!(metrics.client_height, 100.0);
+    assert_eq!(metrics.scroll_height, 320.0);
+    assert_eq!(metrics.scroll_top, 100.0);
+
+    // Inline elements have one rect per line fragment, within the rect of their inline formatting context
+    let span_rects = doc.get_client_rects(span);
+    let p_rect = doc.get_bounding_client_rect(p).unwrap();
+    assert_eq!(span_rects.len(), 1);
+    assert!(span_rects[0].x0 > p_rect.x0 && span_rects[0].width() > 0.0);
+    assert!(p_rect.contains_rect(span_rects[0]));
+    assert!(doc.get_client_rects(world).is_empty());
+}
@@ -0,0 +1,244 @@
+//! Hit testing: finding the elements at a point
+//!
+//! Nodes are hit tested in the reverse of the order that they are painted in (so the topmost node is found first).
+//! Children are painted in `paint_children` order (sorted by z-index and positioning) after their parent's background
+//! and text, so a node's children are tested before its text, which is tested before the node's own box.
+
+use blitz_traits::events::HitResult;
+use parley::Cluster;
+use peniko::kurbo;
+use selectors::Element;
+use style::dom::TDocument;
+use style::properties::generated::longhands::visibility::computed_value::T as Visibility;
+use style::values::computed::{Overflow, PointerEvents};
+
+use crate::BaseDocument;
+use crate::node::Node;
+
+impl BaseDocument {
+    /// The topmost node at a point (in document coordinates). Nodes with `pointer-events: none` are skipped.
+    ///
+    /// The coordinates of the returned [`HitResult`] are relative to the node's border box (after transforms are
+    /// inverted). Text is attributed to the innermost element containing it, in which case the coordinates are
+    /// relative to the border box of the root of the inline formatting context.
+    pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
+        if TDocument::as_node(&&self.nodes[0])
+            .first_element_child()
+            .is_none()
+        {
+            println!("No DOM - not resolving");
+            return None;
+        }
+
+        let mut hits = Vec::new();
+        let point = kurbo::Point::new(x as f64, y as f64);
+        self.hit_test_node(self.root_element().id, point, &mut hits, false);
+        hits.into_iter().next()
+    }
+
+    /// All of the elements at a point (in viewport coordinates), topmost first. Nodes with `pointer-events: none`
+    /// are skipped. This is the equivalent of the DOM's `Document.elementsFromPoint()`.
+    pub fn elements_from_point(&self, x: f32, y: f32) -> Vec<usize> {
+        if TDocument::as_node(&&self.nodes[0])
+            .first_element_child()
+            .is_none()
+        {
+            return Vec::new();
+        }
+
+        let mut hits = Vec::new();
+        let point = kurbo::Point::new(x as f64, y as f64) + self.viewport_scroll.to_vec2();
+        self.hit_test_node(self.root_element().id, point, &mut hits, true);
+
+        let mut node_ids: Vec<usize> = Vec::with_capacity(hits.len());
+        for hit in hits {
+            if !node_ids.contains(&hit.node_id) {
+                node_ids.push(hit.node_id);
+            }
+        }
+        node_ids
+    }
+
+    /// Hit test the layout subtree rooted at a node. The point is in the coordinate space that the node is positioned
+    /// in (the border box of its layout parent, offset by the parent's scroll position).
+    ///
+    /// Hits are pushed topmost first. Returns `true` if hit testing is complete (because only the topmost hit is wanted
+    /// and it has been found).
+    fn hit_test_node(
+        &self,
+        node_id: usize,
+        point: kurbo::Point,
+        hits: &mut Vec<HitResult>,
+        find_all: bool,
+    ) -> bool {
+        let node = &self.nodes[node_id];
+        if node.style.display == taffy::Display::None {
+            return false;
+        }
+        let Some(styles) = node.primary_styles() else {
+            return false;
+        };
+        // Invisible elements are not painted (and neither are their descendants)
+        if styles.get_inherited_box().visibility != Visibility::Visible {
+            return false;
+        }
+        let box_styles = styles.get_box();
+        let clips_content = box_styles.overflow_x != Overflow::Visible
+            || box_styles.overflow_y != Overflow::Visible;
+        let accepts_pointer_events =
+            styles.get_inherited_ui().pointer_events != PointerEvents::None;
+        drop(styles);
+
+        // Map the point into the node's local coordinate space (relative to its border box)
+        let layout = node.final_layout;
+        let mut transform =
+            kurbo::Affine::translate((layout.location.x as f64, layout.location.y as f64));
+        if let Some(css_transform) = node.css_transform() {
+            transform *= css_transform;
+        }
+        if transform.determinant() == 0.0 {
+            return false;
+        }
+        let local = transform.inverse() * point;
+
+        let (width, height) = (layout.size.width as f64, layout.size.height as f64);
+        let in_border_box =
+            local.x >= 0.0 && local.y >= 0.0 && local.x <= width && local.y <= height;
+        let in_padding_box = local.x >= layout.border.left as f64
+            && local.y >= layout.border.top as f64
+            && local.x <= width - layout.border.right as f64
+            && local.y <= height - layout.border.bottom as f64;
+
+        // Content is clipped to the padding box if overflow is not visible
+        if !clips_content || in_padding_box {
+            let content_point = local + node.scroll_offset.to_vec2();
+            if let Some(children) = &*node.paint_children.borrow() {
+                for &child_id in children.iter().rev() {
+                    if self.hit_test_node(child_id, content_point, hits, find_all) {
+                        return true;
+                    }
+                }
+            }
+
+            if let Some(hit) = self.hit_test_text(node, content_point, local) {
+                hits.push(hit);
+                if !find_all {
+                    return true;
+                }
+            }
+        }
+
+        if in_border_box && accepts_pointer_events {
+            hits.push(HitResult {
+                node_id,
+                x: local.x as f32,
+                y: local.y as f32,
+            });
+            return !find_all;
+        }
+        false
+    }
+
+    /// Hit test the text of an inline formatting context. `content_point` is relative to the root's border box offset
+    /// by its scroll position, and `local` is the same point without the scroll offset.
+    fn hit_test_text(
+        &self,
+        node: &Node,
+        content_point: kurbo::Point,
+        local: kurbo::Point,
+    ) -> Option<HitResult> {
+        if !node.flags.is_inline_root() {
+            return None;
+        }
+        let layout = &self.inline_layout(node.id)?.layout;
+        let scale = layout.scale() as f64;
+        let node_layout = node.final_layout;
+        let x = content_point.x - (node_layout.border.left + node_layout.padding.left) as f64;
+        let y = content_point.y - (node_layout.border.top + node_layout.padding.top) as f64;
+        let in_layout = x >= 0.0
+            && y >= 0.0
+            && x <= layout.width() as f64 / scale
+            && y <= layout.height() as f64 / scale;
+        if !in_layout {
+            return None;
+        }
+
+        let (cluster, _) = Cluster::from_point(layout, (x * scale) as f32, (y * scale) as f32)?;
+        let style_index = cluster.glyphs().next()?.style_index();
+        let node_id = layout.styles()[style_index].brush.id;
+        let accepts_pointer_events = self.nodes[node_id]
+            .primary_styles()
+            .is_some_and(|styles| styles.get_inherited_ui().pointer_events != PointerEvents::None);
+        accepts_pointer_events.then_some(HitResult {
+            node_id,
+            x: local.x as f32,
+            y: local.y as f32,
+        })
+    }
+}
+
+#[test]
+fn hit_tests_in_paint_order_through_transforms() {
+    use crate::{Attribute, DocumentConfig};
+    use blitz_traits::shell::{ColorScheme, Viewport};
+    use markup5ever::{LocalName, QualName, local_name, ns};
+
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(400, 300, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let style = |value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("style")),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), style("margin: 0"));
+    let over = mutr.create_element(
+        name("div"),
+        style("position: absolute; top: 0; left: 0; width: 50px; height: 50px; z-index: 1"),
+    );
+    let ghost = mutr.create_element(
+        name("div"),
+        style("position: absolute; top: 0; left: 60px; width: 50px; height: 50px; pointer-events: none"),
+    );
+    let under = mutr.create_element(name("div"), style("height: 100px"));
+    let rotated = mutr.create_element(
+        name("div"),
+        style("width: 100px; height: 20px; transform: rotate(90deg)"),
+    );
+    let clipper = mutr.create_element(name("div"), style("height: 20px; overflow: hidden"));
+    let clipped = mutr.create_element(name("div"), style("height: 100px"));
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[over, ghost, under, rotated, clipper]);
+    mutr.append_children(clipper, &[clipped]);
+    drop(mutr);
+    doc.resolve();
+
+    let hit = |x: f32, y: f32| doc.hit(x, y).map(|hit| hit.node_id);
+
+    // Elements with a higher z-index are above later siblings
+    assert_eq!(hit(10.0, 10.0), Some(over));
+    assert_eq!(
+        doc.elements_from_point(10.0, 10.0),
+        [over, under, body, html]
+    );
+
+    // Elements with `pointer-events: none` are transparent to hit testing
+    assert_eq!(hit(70.0, 10.0), Some(under));
+
+    // Transforms are inverted: the rotated box covers x: 40..60 and y: 60..160
+    assert_eq!(hit(50.0, 150.0), Some(rotated));
+    assert_eq!(hit(50.0, 70.0), Some(rotated));
+    assert_eq!(hit(90.0, 110.0), Some(body));
+    let hit_result = doc.hit(55.0, 70.0).unwrap();
+    assert!((hit_result.x - 10.0).abs() < 0.01 && (hit_result.y - 5.0).abs() < 0.01);
+
+    // Content is clipped by `overflow: hidden`
+    assert_eq!(hit(200.0, 130.0), Some(clipped));
+    assert!(!doc.elements_from_point(200.0, 150.0).contains(&clipped));
+}
@@ -0,0 +1,34 @@
+//! Parsing of HTML fragments into an existing document (for `innerHTML` and similar APIs)
+
+use crate::DocumentMutator;
+
+/// A type which can parse HTML fragments. Blitz DOM does not contain an HTML parser, so one must be
+/// provided (e.g. `blitz_html::HtmlProvider`) in order to use [`DocumentMutator::set_inner_html`],
+/// [`DocumentMutator::set_outer_html`] and [`DocumentMutator::insert_adjacent_html`]. Without one,
+/// those methods log a warning and leave the document unchanged.
+pub trait HtmlParserProvider: Send + Sync + 'static {
+    /// Parse `html` using the HTML fragment parsing algorithm, with the element `context_id` as the
+    /// context element, and append the resulting nodes to the (unparented) node `container_id`.
+    fn parse_fragment(
+        &self,
+        mutr: &mut DocumentMutator<'_>,
+        container_id: usize,
+        context_id: usize,
+        html: &str,
+    );
+}
+
+#[test]
+fn setting_html_without_a_parser_keeps_content() {
+    use markup5ever::{QualName, local_name, ns};
+
+    let mut doc = crate::BaseDocument::new(crate::DocumentConfig::default());
+    let mut mutr = doc.mutate();
+    let div_id = mutr.create_element(QualName::new(None, ns!(html), local_name!("div")), vec![]);
+    let text_id = mutr.create_text_node("Hello");
+    mutr.append_children(div_id, &[text_id]);
+
+    mutr.set_inner_html(div_id, "<p>Replaced</p>");
+    drop(mutr);
+    assert_eq!(doc.get_node(div_id).unwrap().children, [text_id]);
+}
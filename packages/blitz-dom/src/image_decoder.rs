@@ -0,0 +1,89 @@
+//! A pool of worker threads on which images are decoded
+
+use std::panic::AssertUnwindSafe;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// The default value of [`ImageDecoder::max_decoded_size`]: 256MiB (e.g. an 8192x8192 image)
+pub const DEFAULT_MAX_DECODED_SIZE: u64 = 256 * 1024 * 1024;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A handle to a pool of worker threads which decode images off of the thread driving the document.
+///
+/// Images whose decoded (RGBA8) pixel data would exceed [`max_decoded_size`](Self::max_decoded_size) bytes
+/// are rejected without being decoded, which guards against "decompression bombs" (small files which decode
+/// to enormous images). For animated images the limit applies to the total size of all frames.
+///
+/// Handles are cheap to clone and may be shared between documents using [`DocumentConfig::image_decoder`](crate::DocumentConfig::image_decoder).
+/// The worker threads of a pool created with [`ImageDecoder::new`] exit once all of its handles have been dropped.
+#[derive(Clone)]
+pub struct ImageDecoder {
+    sender: Sender<Job>,
+    max_decoded_size: u64,
+}
+
+impl ImageDecoder {
+    /// Create a new pool with `threads` worker threads (at least one thread is always created)
+    pub fn new(threads: usize, max_decoded_size: u64) -> Self {
+        let (sender, receiver) = channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for idx in 0..threads.max(1) {
+            let receiver = Arc::clone(&receiver);
+            std::thread::Builder::new()
+                .name(format!("blitz-image-decoder-{idx}"))
+                .spawn(move || run_worker(&receiver))
+                .expect("Failed to spawn image decoder thread");
+        }
+
+        Self {
+            sender,
+            max_decoded_size,
+        }
+    }
+
+    /// The maximum number of bytes that an image may decode to
+    pub fn max_decoded_size(&self) -> u64 {
+        self.max_decoded_size
+    }
+
+    /// Create a handle to the same pool of worker threads with a different maximum decoded size
+    pub fn with_max_decoded_size(mut self, max_decoded_size: u64) -> Self {
+        self.max_decoded_size = max_decoded_size;
+        self
+    }
+
+    /// Run `job` on one of the pool's worker threads
+    pub(crate) fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        // Sending only fails if all of the workers have panicked, in which case the image is never loaded
+        let _ = self.sender.send(Box::new(job));
+    }
+}
+
+impl Default for ImageDecoder {
+    /// A handle to a pool (shared by all documents which use the default) with one thread per CPU
+    /// (up to a maximum of four threads) and a maximum decoded size of [`DEFAULT_MAX_DECODED_SIZE`].
+    fn default() -> Self {
+        static SHARED: OnceLock<ImageDecoder> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get().min(4));
+                ImageDecoder::new(threads, DEFAULT_MAX_DECODED_SIZE)
+            })
+            .clone()
+    }
+}
+
+fn run_worker(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        // The lock is released before running the job so that other workers can receive jobs
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => {
+                // Don't let a decoder which panics on a malformed image take down the worker
+                let _ = std::panic::catch_unwind(AssertUnwindSafe(job));
+            }
+            Err(_) => return,
+        }
+    }
+}
@@ -38,7 +38,7 @@ fn push_children_and_pseudos(layout_children: &mut Vec<usize>, node: &Node) {
     if let Some(before) = node.before {
         layout_children.push(before);
     }
-    layout_children.extend_from_slice(&node.children);
+    layout_children.extend_from_slice(node.flat_tree_children());
     if let Some(after) = node.after {
         layout_children.push(after);
     }
@@ -93,6 +93,11 @@ pub(crate) fn collect_layout_children(
             }
         }
 
+        // Custom elements are replaced elements (their children are not rendered)
+        if doc.nodes[container_node_id].flags.is_custom_element() {
+            return;
+        }
+
         #[cfg(feature = "svg")]
         if matches!(tag_name, "svg") {
             let mut outer_html = doc.get_node(container_node_id).unwrap().outer_html();
@@ -138,7 +143,7 @@ pub(crate) fn collect_layout_children(
     // Skip further construction if the node has no children or psuedo-children
     {
         let node = &doc.nodes[container_node_id];
-        if node.children.is_empty() && node.before.is_none() && node.after.is_none() {
+        if node.flat_tree_children().is_empty() && node.before.is_none() && node.after.is_none() {
             return;
         }
     }
@@ -153,15 +158,12 @@ pub(crate) fn collect_layout_children(
     match container_display.inside() {
         DisplayInside::None => {}
         DisplayInside::Contents => {
-            // Take children array from node to avoid borrow checker issues.
-            let children = std::mem::take(&mut doc.nodes[container_node_id].children);
+            // Copy children array from node to avoid borrow checker issues.
+            let children = doc.nodes[container_node_id].flat_tree_children().to_vec();
 
-            for child_id in children.iter().copied() {
+            for child_id in children {
                 collect_layout_children(doc, child_id, layout_children, anonymous_block_id)
             }
-
-            // Put children array back
-            doc.nodes[container_node_id].children = children;
         }
         DisplayInside::Flow | DisplayInside::FlowRoot | DisplayInside::TableCell => {
             // TODO: make "all_inline" detection work in the presence of display:contents nodes
@@ -169,7 +171,7 @@ pub(crate) fn collect_layout_children(
             let mut all_inline = true;
             let mut has_contents = false;
             for child in doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
@@ -240,7 +242,7 @@ pub(crate) fn collect_layout_children(
         }
         DisplayInside::Flex | DisplayInside::Grid => {
             let has_text_node_or_contents = doc.nodes[container_node_id]
-                .children
+                .flat_tree_children()
                 .iter()
                 .copied()
                 .map(|child_id| &doc.nodes[child_id])
@@ -380,7 +382,7 @@ fn collect_list_item_children(
     reversed: bool,
     node_id: usize,
 ) {
-    let mut children = doc.nodes[node_id].children.clone();
+    let mut children = doc.nodes[node_id].flat_tree_children().to_vec();
     if reversed {
         children.reverse();
     }
@@ -588,7 +590,7 @@ fn collect_complex_layout_children(
         true
     }
 
-    doc.iter_children_and_pseudos_mut(container_node_id, |child_id, doc| {
+    doc.iter_flat_tree_children_and_pseudos_mut(container_node_id, |child_id, doc| {
         // Get node kind (text, element, comment, etc)
         let child_node_kind = doc.nodes[child_id].data.kind();
 
@@ -779,7 +781,7 @@ pub(crate) fn build_inline_layout(
             root_line_height,
         );
     }
-    for child_id in root_node.children.iter().copied() {
+    for child_id in root_node.flat_tree_children().iter().copied() {
         build_inline_layout_recursive(
             &mut builder,
             &doc.nodes,
@@ -812,7 +814,7 @@ pub(crate) fn build_inline_layout(
     return (TextLayout { text, layout }, layout_children);
 
     fn flush_inline_pseudos_recursive(doc: &mut BaseDocument, node_id: usize) {
-        doc.iter_children_mut(node_id, |child_id, doc| {
+        doc.iter_flat_tree_children_mut(node_id, |child_id, doc| {
             flush_pseudo_elements(doc, child_id);
             let display = doc.nodes[node_id]
                 .display_style()
@@ -863,7 +865,7 @@ pub(crate) fn build_inline_layout(
                 match (display.outside(), display.inside()) {
                     (DisplayOutside::None, DisplayInside::None) => {}
                     (DisplayOutside::None, DisplayInside::Contents) => {
-                        for child_id in node.children.iter().copied() {
+                        for child_id in node.flat_tree_children().iter().copied() {
                             build_inline_layout_recursive(
                                 builder,
                                 nodes,
@@ -878,6 +880,7 @@ pub(crate) fn build_inline_layout(
                         let tag_name = &element_data.name.local;
 
                         if *tag_name == local_name!("img")
+                            || node.flags.is_custom_element()
                             || *tag_name == local_name!("svg")
                             || *tag_name == local_name!("input")
                             || *tag_name == local_name!("textarea")
@@ -933,7 +936,7 @@ pub(crate) fn build_inline_layout(
                                 );
                             }
 
-                            for child_id in node.children.iter().copied() {
+                            for child_id in node.flat_tree_children().iter().copied() {
                                 build_inline_layout_recursive(
                                     builder,
                                     nodes,
@@ -975,7 +978,7 @@ pub(crate) fn build_inline_layout(
                 builder.push_text(&data.content);
             }
             NodeData::Comment => {}
-            NodeData::Document => unreachable!(),
+            NodeData::Document | NodeData::ShadowRoot(_) => unreachable!(),
         }
     }
 }
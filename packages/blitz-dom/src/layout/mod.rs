@@ -210,8 +210,10 @@ impl LayoutPartialTree for BaseDocument {
                         }
                     }
 
+                    let custom_element = tree.custom_elements.get(&node_id.into());
                     if *element_data.name.local == *"img"
                         || *element_data.name.local == *"canvas"
+                        || custom_element.is_some()
                         || (cfg!(feature = "svg") && *element_data.name.local == *"svg")
                     {
                         // Get width and height attributes on image element
@@ -228,25 +230,36 @@ impl LayoutPartialTree for BaseDocument {
                         };
 
                         // Get image's native sizespecial_data
-                        let inherent_size = match &element_data.special_data {
-                            SpecialElementData::Image(image_data) => match &**image_data {
-                                ImageData::Raster(image) => taffy::Size {
-                                    width: image.width as f32,
-                                    height: image.height as f32,
-                                },
-                                #[cfg(feature = "svg")]
-                                ImageData::Svg(svg) => {
-                                    let size = svg.size();
-                                    taffy::Size {
-                                        width: size.width(),
-                                        height: size.height(),
+                        let density = element_data
+                            .image_source
+                            .as_ref()
+                            .map_or(1.0, |source| source.density);
+                        let inherent_size = match custom_element {
+                            Some(custom_element) => custom_element.intrinsic_size(),
+                            None => match &element_data.special_data {
+                                SpecialElementData::Image(image_data) => match &**image_data {
+                                    ImageData::Raster(image) => taffy::Size {
+                                        width: image.width as f32 / density,
+                                        height: image.height as f32 / density,
+                                    },
+                                    ImageData::Animated(image) => taffy::Size {
+                                        width: image.current_image().width as f32 / density,
+                                        height: image.current_image().height as f32 / density,
+                                    },
+                                    #[cfg(feature = "svg")]
+                                    ImageData::Svg(svg) => {
+                                        let size = svg.size();
+                                        taffy::Size {
+                                            width: size.width() / density,
+                                            height: size.height() / density,
+                                        }
                                     }
-                                }
-                                ImageData::None => taffy::Size::ZERO,
+                                    ImageData::None => taffy::Size::ZERO,
+                                },
+                                SpecialElementData::Canvas(_) => taffy::Size::ZERO,
+                                SpecialElementData::None => taffy::Size::ZERO,
+                                _ => unreachable!(),
                             },
-                            SpecialElementData::Canvas(_) => taffy::Size::ZERO,
-                            SpecialElementData::None => taffy::Size::ZERO,
-                            _ => unreachable!(),
                         };
 
                         let replaced_context = ReplacedContext {
@@ -427,6 +440,7 @@ impl PrintTree for BaseDocument {
             // NodeData::Doctype { .. } => return "DOCTYPE",
             NodeData::Text { .. } => node.node_debug_str().leak(),
             NodeData::Comment => "COMMENT",
+            NodeData::ShadowRoot(_) => "SHADOW ROOT",
             NodeData::AnonymousBlock(_) => "ANONYMOUS BLOCK",
             NodeData::Element(_) => {
                 let display = match style.display {
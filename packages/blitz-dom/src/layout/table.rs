@@ -46,7 +46,7 @@ pub(crate) fn build_table_context(
 
     let root_node = &mut doc.nodes[table_root_node_id];
 
-    let children = std::mem::take(&mut root_node.children);
+    let children = root_node.flat_tree_children().to_vec();
 
     let Some(stylo_styles) = root_node.primary_styles() else {
         panic!("Ignoring table because it has no styles");
@@ -88,9 +88,6 @@ pub(crate) fn build_table_context(
         .filter(|item| item.kind == TableItemKind::Cell)
         .map(|cell| cell.node_id)
         .collect();
-    let root_node = &mut doc.nodes[table_root_node_id];
-    root_node.children = children;
-
     (TableContext { style, items }, layout_children)
 }
 
@@ -123,11 +120,10 @@ pub(crate) fn collect_table_cells(
         | DisplayInside::TableHeaderGroup
         | DisplayInside::TableFooterGroup
         | DisplayInside::Contents => {
-            let children = std::mem::take(&mut doc.nodes[node_id].children);
-            for child_id in children.iter().copied() {
+            let children = doc.nodes[node_id].flat_tree_children().to_vec();
+            for child_id in children {
                 collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
             }
-            doc.nodes[node_id].children = children;
         }
         DisplayInside::TableRow => {
             *row += 1;
@@ -151,11 +147,10 @@ pub(crate) fn collect_table_cells(
                 });
             }
 
-            let children = std::mem::take(&mut doc.nodes[node_id].children);
-            for child_id in children.iter().copied() {
+            let children = doc.nodes[node_id].flat_tree_children().to_vec();
+            for child_id in children {
                 collect_table_cells(doc, child_id, is_fixed, row, col, cells, columns);
             }
-            doc.nodes[node_id].children = children;
         }
         DisplayInside::TableCell => {
             let stylo_style = &node.primary_styles().unwrap();
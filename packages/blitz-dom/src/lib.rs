@@ -37,14 +37,30 @@ mod document;
 /// The nodes themsleves, and their data.
 pub mod node;
 
+mod cache;
 mod config;
+mod custom_element;
 mod debug;
 mod events;
+mod find;
 mod form;
+mod geometry;
+mod hit_test;
+mod html;
+mod image_decoder;
 /// Integration of taffy and the DOM.
 mod layout;
+mod metadata;
+mod mutation_observer;
 mod mutator;
+mod network_log;
+mod policy;
+mod preload;
 mod query_selector;
+mod scroll;
+mod selection;
+mod shadow;
+mod srcset;
 /// Implementations that interact with servo's style engine
 mod stylo;
 mod stylo_to_cursor_icon;
@@ -52,21 +68,41 @@ mod stylo_to_parley;
 mod traversal;
 mod url;
 
+pub mod encoding;
 pub mod net;
 pub mod util;
 
 #[cfg(feature = "accessibility")]
 mod accessibility;
 
+pub use cache::{DEFAULT_MAX_CACHE_SIZE, ResourceCache};
 pub use config::DocumentConfig;
-pub use document::{BaseDocument, Document};
+pub use custom_element::{
+    CustomElement, CustomElementConstructor, CustomElementEventCtx, CustomElementPaintCtx,
+    DefineCustomElementErr,
+};
+pub use document::{BaseDocument, Document, DocumentReadyState};
+pub use find::{FindMatch, FindOptions};
+pub use geometry::ElementMetrics;
+pub use html::HtmlParserProvider;
+pub use image_decoder::{DEFAULT_MAX_DECODED_SIZE, ImageDecoder};
 pub use markup5ever::{
     LocalName, Namespace, NamespaceStaticSet, Prefix, PrefixStaticSet, QualName, local_name,
     namespace_prefix, namespace_url, ns,
 };
-pub use mutator::DocumentMutator;
-pub use node::{Attribute, ElementData, Node, NodeData, TextNodeData};
+pub use mutation_observer::{
+    MutationKind, MutationObserverId, MutationObserverOptions, MutationRecord,
+};
+pub use mutator::{AdjacentPosition, AttachShadowErr, DocumentMutator};
+pub use network_log::{NetworkLog, NetworkRequest, NetworkRequestStatus};
+pub use node::{
+    Attribute, ElementData, Node, NodeData, ShadowRootData, ShadowRootMode, TextNodeData,
+};
 pub use parley::FontContext;
+pub use policy::{PolicyViolation, ResourcePolicy, ResourceRule, ViolationReason};
+pub use scroll::{ScrollAlignment, ScrollBehavior, ScrollIntoViewOptions};
+pub use selection::{TextPosition, TextSelection};
+pub use shadow::can_attach_shadow;
 pub use style::Atom;
 pub use style::invalidation::element::restyle_hints::RestyleHint;
 pub type SelectorList = selectors::SelectorList<style::selector_parser::SelectorImpl>;
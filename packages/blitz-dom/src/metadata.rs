@@ -0,0 +1,263 @@
+//! Metadata from `<link rel="icon">` and `<meta name="theme-color">` elements which is forwarded to the
+//! [`ShellProvider`](blitz_traits::shell::ShellProvider) as the window icon and theme color.
+
+use std::io::Cursor;
+
+use blitz_traits::net::{Bytes, NetHandler, Request, RequestDestination, SharedCallback};
+use blitz_traits::shell::WindowIcon;
+use markup5ever::local_name;
+use style::color::AbsoluteColor;
+use style::values::specified::Color as SpecifiedColor;
+use url::Url;
+
+use crate::BaseDocument;
+use crate::image_decoder::ImageDecoder;
+use crate::net::Resource;
+use crate::srcset::is_supported_image_type;
+use crate::traversal::TreeTraverser;
+use crate::util::ToColorColor;
+
+/// The size (in pixels) of icon that is preferred when selecting between icons. SVG icons are rasterized at this size.
+const PREFERRED_ICON_SIZE: u32 = 128;
+
+/// The size of an icon as declared by the `sizes` attribute of it's `<link>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IconSize {
+    /// No (valid) `sizes` attribute
+    Unknown,
+    /// The largest size listed in the `sizes` attribute
+    Pixels(u32),
+    /// A scalable (SVG) icon
+    Any,
+}
+
+impl IconSize {
+    /// Parse the `sizes` attribute of a `<link rel="icon">` (e.g. `16x16 32x32` or `any`)
+    fn parse(sizes: &str) -> Self {
+        let mut largest = None;
+        for size in sizes.split_ascii_whitespace() {
+            if size.eq_ignore_ascii_case("any") {
+                return IconSize::Any;
+            }
+            let Some((width, height)) = size.split_once(['x', 'X']) else {
+                continue;
+            };
+            if let (Ok(width), Ok(height)) = (width.parse::<u32>(), height.parse::<u32>()) {
+                largest = largest.max(Some(width.max(height)));
+            }
+        }
+        largest.map_or(IconSize::Unknown, IconSize::Pixels)
+    }
+
+    /// Icons with a higher rank are preferred: scalable icons, then the smallest icon which is at least
+    /// [`PREFERRED_ICON_SIZE`], then the largest icon which is smaller than that, then icons of unknown size.
+    fn rank(self) -> (u8, i64) {
+        match self {
+            IconSize::Any => (3, 0),
+            IconSize::Pixels(size) if size >= PREFERRED_ICON_SIZE => (2, -(size as i64)),
+            IconSize::Pixels(size) => (1, size as i64),
+            IconSize::Unknown => (0, 0),
+        }
+    }
+}
+
+impl BaseDocument {
+    /// Select the best icon from the `<link rel="icon">` elements in the document.
+    ///
+    /// Icons whose `type` isn't supported or whose `media` doesn't match are ignored. If several icons
+    /// are equally good then the last one in the document is used (as in browsers).
+    pub(crate) fn select_window_icon(&self) -> Option<Url> {
+        TreeTraverser::new(self)
+            .filter_map(|node_id| {
+                let element = self.nodes[node_id].element_data()?;
+                if element.name.local != local_name!("link") {
+                    return None;
+                }
+                let is_icon = element.attr(local_name!("rel")).is_some_and(|rel| {
+                    rel.split_ascii_whitespace()
+                        .any(|rel| rel.eq_ignore_ascii_case("icon"))
+                });
+                if !is_icon {
+                    return None;
+                }
+                let href = element.attr(local_name!("href"))?.trim();
+                if href.is_empty() {
+                    return None;
+                }
+                if element
+                    .attr(local_name!("media"))
+                    .is_some_and(|media| !self.evaluate_media_query(media))
+                {
+                    return None;
+                }
+                let mime_type = element.attr(local_name!("type"));
+                if mime_type.is_some_and(|mime_type| !is_supported_image_type(mime_type)) {
+                    return None;
+                }
+
+                let url = self.url.resolve_relative(href)?;
+                let is_svg = match mime_type {
+                    Some(mime_type) => mime_type.trim().eq_ignore_ascii_case("image/svg+xml"),
+                    None => url.path().to_ascii_lowercase().ends_with(".svg"),
+                };
+                let size = if is_svg {
+                    if !cfg!(feature = "svg") {
+                        return None;
+                    }
+                    IconSize::Any
+                } else {
+                    element
+                        .attr(local_name!("sizes"))
+                        .map_or(IconSize::Unknown, IconSize::parse)
+                };
+
+                Some((size.rank(), url))
+            })
+            // Note: `max_by_key` returns the last of several equally good icons
+            .max_by_key(|(rank, _)| *rank)
+            .map(|(_, url)| url)
+    }
+
+    /// Get the color of the first `<meta name="theme-color">` element (whose `media` matches) in the document
+    pub(crate) fn select_theme_color(&self) -> Option<[u8; 4]> {
+        TreeTraverser::new(self).find_map(|node_id| {
+            let element = self.nodes[node_id].element_data()?;
+            if element.name.local != local_name!("meta") {
+                return None;
+            }
+            let is_theme_color = element
+                .attr(local_name!("name"))
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case("theme-color"));
+            if !is_theme_color {
+                return None;
+            }
+            if element
+                .attr(local_name!("media"))
+                .is_some_and(|media| !self.evaluate_media_query(media))
+            {
+                return None;
+            }
+
+            let content = element.attr(local_name!("content"))?.trim();
+            let color = self.parse_with_context(content, |context, input| {
+                SpecifiedColor::parse_and_compute(context, input, None)
+            })?;
+            let color = color
+                .resolve_to_absolute(&AbsoluteColor::BLACK)
+                .as_color_color()
+                .to_rgba8();
+            Some([color.r, color.g, color.b, color.a])
+        })
+    }
+
+    /// Re-select the window icon, fetching it if it has changed
+    pub(crate) fn update_window_icon(&mut self) {
+        let url = self.select_window_icon();
+        if url == self.window_icon_url {
+            return;
+        }
+        self.window_icon_url = url.clone();
+
+        match url {
+            Some(url) => self.net_provider.fetch(
+                self.id(),
+                Request::get(url.clone()).with_destination(RequestDestination::Image),
+                Box::new(IconHandler {
+                    url,
+                    decoder: self.image_decoder.clone(),
+                }),
+            ),
+            None => self.shell_provider.set_window_icon(None),
+        }
+    }
+
+    /// Re-select the theme color, passing it to the `ShellProvider` if it has changed
+    pub(crate) fn update_theme_color(&mut self) {
+        let color = self.select_theme_color();
+        if color != self.theme_color {
+            self.theme_color = color;
+            self.shell_provider.set_theme_color(color);
+        }
+    }
+}
+
+/// Decodes (or rasterizes) a fetched icon into a [`WindowIcon`]
+struct IconHandler {
+    url: Url,
+    decoder: ImageDecoder,
+}
+
+impl NetHandler<Resource> for IconHandler {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        let decoder = self.decoder.clone();
+        decoder.spawn(move || {
+            let result = decode_icon(&bytes, self.decoder.max_decoded_size())
+                .map(|icon| Resource::WindowIcon(self.url, icon))
+                .ok_or_else(|| Some(String::from("Could not decode icon")));
+            callback.call(doc_id, result);
+        });
+    }
+}
+
+fn decode_icon(bytes: &[u8], max_decoded_size: u64) -> Option<WindowIcon> {
+    let mut reader = image::ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .expect("IO errors impossible with Cursor");
+    let mut limits = image::Limits::default();
+    limits.max_alloc = Some(max_decoded_size);
+    reader.limits(limits);
+    if let Ok(image) = reader.decode() {
+        let image = image.into_rgba8();
+        return Some(WindowIcon {
+            width: image.width(),
+            height: image.height(),
+            rgba: image.into_raw(),
+        });
+    }
+
+    #[cfg(feature = "svg")]
+    if let Ok(tree) = crate::util::parse_svg(bytes) {
+        use resvg::tiny_skia::{Pixmap, Transform};
+
+        let size = tree.size();
+        let scale = PREFERRED_ICON_SIZE as f32 / size.width().max(size.height());
+        let width = (size.width() * scale).round().max(1.0) as u32;
+        let height = (size.height() * scale).round().max(1.0) as u32;
+        let mut pixmap = Pixmap::new(width, height)?;
+        resvg::render(
+            &tree,
+            Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        let rgba = pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let pixel = pixel.demultiply();
+                [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+            })
+            .collect();
+        return Some(WindowIcon {
+            width,
+            height,
+            rgba,
+        });
+    }
+
+    None
+}
+
+#[test]
+fn parses_icon_sizes() {
+    assert_eq!(IconSize::parse("16x16 48X48 32x32"), IconSize::Pixels(48));
+    assert_eq!(IconSize::parse("any"), IconSize::Any);
+    assert_eq!(IconSize::parse("16"), IconSize::Unknown);
+
+    let best = ["16x16", "256x256", "any", "", "128x128", "64x64"]
+        .into_iter()
+        .map(IconSize::parse)
+        .filter(|size| *size != IconSize::Any)
+        .max_by_key(|size| size.rank());
+    assert_eq!(best, Some(IconSize::Pixels(128)));
+}
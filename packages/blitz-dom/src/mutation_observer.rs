@@ -0,0 +1,356 @@
+//! Observation of changes to the DOM (similar to the web's `MutationObserver` API)
+
+use markup5ever::{LocalName, QualName};
+
+use crate::BaseDocument;
+
+/// The kind of change described by a [`MutationRecord`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MutationKind {
+    /// Children were added to or removed from the target
+    ChildList,
+    /// An attribute of the target was set or removed
+    Attributes,
+    /// The text of the target (a text node) changed
+    CharacterData,
+}
+
+/// A change to the DOM, delivered to the observers of the changed node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MutationRecord {
+    pub kind: MutationKind,
+    /// The node which changed (the parent of the added and removed nodes for [`MutationKind::ChildList`] records)
+    pub target: usize,
+    pub added_nodes: Vec<usize>,
+    pub removed_nodes: Vec<usize>,
+    /// The sibling before the added or removed nodes
+    pub previous_sibling: Option<usize>,
+    /// The sibling after the added or removed nodes
+    pub next_sibling: Option<usize>,
+    /// The name of the changed attribute (for [`MutationKind::Attributes`] records)
+    pub attribute_name: Option<QualName>,
+    /// The previous value of the attribute or text (if requested by the observer's options). `None` for
+    /// attributes which were previously unset.
+    pub old_value: Option<String>,
+}
+
+impl MutationRecord {
+    pub(crate) fn child_list(
+        target: usize,
+        added_nodes: Vec<usize>,
+        removed_nodes: Vec<usize>,
+        previous_sibling: Option<usize>,
+        next_sibling: Option<usize>,
+    ) -> Self {
+        Self {
+            kind: MutationKind::ChildList,
+            target,
+            added_nodes,
+            removed_nodes,
+            previous_sibling,
+            next_sibling,
+            attribute_name: None,
+            old_value: None,
+        }
+    }
+
+    pub(crate) fn attribute(target: usize, name: QualName, old_value: Option<String>) -> Self {
+        Self {
+            kind: MutationKind::Attributes,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: Some(name),
+            old_value,
+        }
+    }
+
+    pub(crate) fn character_data(target: usize, old_value: String) -> Self {
+        Self {
+            kind: MutationKind::CharacterData,
+            target,
+            added_nodes: Vec::new(),
+            removed_nodes: Vec::new(),
+            previous_sibling: None,
+            next_sibling: None,
+            attribute_name: None,
+            old_value: Some(old_value),
+        }
+    }
+}
+
+/// Which changes a mutation observer is notified of
+#[derive(Debug, Clone, Default)]
+pub struct MutationObserverOptions {
+    /// Observe children being added to or removed from the target
+    pub child_list: bool,
+    /// Observe changes to the target's attributes
+    pub attributes: bool,
+    /// Observe changes to the text of the target
+    pub character_data: bool,
+    /// Also observe the target's descendants
+    pub subtree: bool,
+    /// Include the previous value of changed attributes in records. Implies `attributes`.
+    pub attribute_old_value: bool,
+    /// Include the previous text of changed text nodes in records. Implies `character_data`.
+    pub character_data_old_value: bool,
+    /// Only observe changes to attributes with these (local) names. Implies `attributes`.
+    pub attribute_filter: Option<Vec<LocalName>>,
+}
+
+impl MutationObserverOptions {
+    fn observes(&self, record: &MutationRecord) -> bool {
+        match record.kind {
+            MutationKind::ChildList => self.child_list,
+            MutationKind::CharacterData => self.character_data || self.character_data_old_value,
+            MutationKind::Attributes => {
+                let name = &record.attribute_name.as_ref().unwrap().local;
+                (self.attributes || self.attribute_old_value || self.attribute_filter.is_some())
+                    && self
+                        .attribute_filter
+                        .as_ref()
+                        .is_none_or(|filter| filter.contains(name))
+            }
+        }
+    }
+
+    fn includes_old_value(&self, kind: MutationKind) -> bool {
+        match kind {
+            MutationKind::ChildList => false,
+            MutationKind::Attributes => self.attribute_old_value,
+            MutationKind::CharacterData => self.character_data_old_value,
+        }
+    }
+}
+
+/// Identifies an observer registered with [`BaseDocument::observe_mutations`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MutationObserverId(u64);
+
+type MutationCallback = Box<dyn FnMut(Vec<MutationRecord>) + Send>;
+
+struct MutationObserver {
+    id: MutationObserverId,
+    target: usize,
+    options: MutationObserverOptions,
+    callback: MutationCallback,
+    records: Vec<MutationRecord>,
+    /// Nodes which have been removed from the observed subtree since records were last delivered. These
+    /// continue to be observed until the next delivery (like the web's "transient registered observers").
+    transient_targets: Vec<usize>,
+}
+
+#[derive(Default)]
+pub(crate) struct MutationObservers {
+    observers: Vec<MutationObserver>,
+    next_id: u64,
+    /// The number of [`DocumentMutator`](crate::DocumentMutator)s which currently exist for the document.
+    /// Mutators may be nested (e.g. when parsing a fragment), in which case records are only delivered once
+    /// the outermost mutator is flushed.
+    pub(crate) mutator_depth: usize,
+}
+
+impl MutationObservers {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+}
+
+impl BaseDocument {
+    /// Register a callback which is called with records of the changes to the node `target` (and it's descendants
+    /// if [`subtree`](MutationObserverOptions::subtree) is set) which match `options`.
+    ///
+    /// Records are collected as a [`DocumentMutator`](crate::DocumentMutator) applies changes, and are delivered
+    /// (in the order in which the changes were made) when it is flushed. Nodes which are removed from an observed
+    /// subtree remain observed until the records are delivered, so changes made to them in the same batch are reported.
+    pub fn observe_mutations(
+        &mut self,
+        target: usize,
+        options: MutationObserverOptions,
+        callback: impl FnMut(Vec<MutationRecord>) + Send + 'static,
+    ) -> MutationObserverId {
+        let observers = &mut self.mutation_observers;
+        let id = MutationObserverId(observers.next_id);
+        observers.next_id += 1;
+        observers.observers.push(MutationObserver {
+            id,
+            target,
+            options,
+            callback: Box::new(callback),
+            records: Vec::new(),
+            transient_targets: Vec::new(),
+        });
+        id
+    }
+
+    /// Unregister a mutation observer. Records which have not yet been delivered to it are discarded.
+    pub fn disconnect_mutation_observer(&mut self, id: MutationObserverId) {
+        self.mutation_observers
+            .observers
+            .retain(|observer| observer.id != id);
+    }
+
+    /// Take the records which have been collected for an observer but not yet delivered to it
+    pub fn take_mutation_records(&mut self, id: MutationObserverId) -> Vec<MutationRecord> {
+        self.mutation_observers
+            .observers
+            .iter_mut()
+            .find(|observer| observer.id == id)
+            .map(|observer| std::mem::take(&mut observer.records))
+            .unwrap_or_default()
+    }
+
+    /// Queue a record for the observers of the record's target and (for subtree observers) it's ancestors
+    pub(crate) fn queue_mutation_record(&mut self, record: MutationRecord) {
+        let observers = &mut self.mutation_observers.observers;
+        for observer in observers.iter_mut() {
+            let mut node_id = Some(record.target);
+            let mut is_target = true;
+            let is_observing = loop {
+                let Some(id) = node_id else {
+                    break false;
+                };
+                if id == observer.target {
+                    break is_target || observer.options.subtree;
+                }
+                if observer.transient_targets.contains(&id) {
+                    break true;
+                }
+                node_id = self.nodes[id].parent;
+                is_target = false;
+            };
+            if !is_observing {
+                continue;
+            }
+            // Removed nodes remain observed by subtree observers until the records are delivered
+            if observer.options.subtree {
+                observer
+                    .transient_targets
+                    .extend_from_slice(&record.removed_nodes);
+            }
+            if !observer.options.observes(&record) {
+                continue;
+            }
+
+            let mut record = record.clone();
+            if !observer.options.includes_old_value(record.kind) {
+                record.old_value = None;
+            }
+            observer.records.push(record);
+        }
+    }
+
+    /// Pass the records which have been collected to their observers
+    pub(crate) fn deliver_mutation_records(&mut self) {
+        for observer in &mut self.mutation_observers.observers {
+            observer.transient_targets.clear();
+            if !observer.records.is_empty() {
+                (observer.callback)(std::mem::take(&mut observer.records));
+            }
+        }
+    }
+}
+
+#[test]
+fn delivers_mutation_records_on_flush() {
+    use crate::{DocumentConfig, local_name, ns};
+    use std::sync::{Arc, Mutex};
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let name = |local| QualName::new(None, ns!(html), local);
+    let mut mutr = doc.mutate();
+    let root_id = mutr.create_element(name(local_name!("div")), Vec::new());
+    let child_id = mutr.create_element(name(local_name!("span")), Vec::new());
+    let text_id = mutr.create_text_node("Hello");
+    mutr.append_children(0, &[root_id]);
+    drop(mutr);
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let subtree_records = records.clone();
+    let options = MutationObserverOptions {
+        child_list: true,
+        subtree: true,
+        character_data_old_value: true,
+        attribute_old_value: true,
+        attribute_filter: Some(vec![local_name!("id")]),
+        ..Default::default()
+    };
+    doc.observe_mutations(root_id, options, move |records| {
+        subtree_records.lock().unwrap().extend(records)
+    });
+
+    let mut mutr = doc.mutate();
+    mutr.append_children(root_id, &[child_id]);
+    mutr.append_children(child_id, &[text_id]);
+    mutr.set_node_text(text_id, "World");
+    mutr.set_attribute(child_id, name(local_name!("id")), "a");
+    mutr.set_attribute(child_id, name(local_name!("id")), "b");
+    mutr.set_attribute(child_id, name(local_name!("class")), "ignored");
+    mutr.remove_node(child_id);
+    mutr.set_node_text(text_id, "Detached");
+    assert!(records.lock().unwrap().is_empty());
+    drop(mutr);
+
+    // Removed nodes are no longer observed once the records have been delivered
+    doc.mutate().set_node_text(text_id, "Forgotten");
+
+    let records = records.lock().unwrap();
+    let summary: Vec<_> = records
+        .iter()
+        .map(|record| (record.kind, record.target, record.old_value.as_deref()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            (MutationKind::ChildList, root_id, None),
+            (MutationKind::ChildList, child_id, None),
+            (MutationKind::CharacterData, text_id, Some("Hello")),
+            (MutationKind::Attributes, child_id, None),
+            (MutationKind::Attributes, child_id, Some("a")),
+            (MutationKind::ChildList, root_id, None),
+            (MutationKind::CharacterData, text_id, Some("World")),
+        ]
+    );
+    assert_eq!(records[0].added_nodes, [child_id]);
+    assert_eq!(records[5].removed_nodes, [child_id]);
+}
+
+#[test]
+fn delivers_mutation_records_from_outermost_mutator() {
+    use crate::{DocumentConfig, DocumentMutator, local_name, ns};
+    use std::sync::{Arc, Mutex};
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let name = QualName::new(None, ns!(html), local_name!("div"));
+    let mut mutr = doc.mutate();
+    let root_id = mutr.create_element(name, Vec::new());
+    mutr.append_children(0, &[root_id]);
+    drop(mutr);
+
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let observed_records = records.clone();
+    let options = MutationObserverOptions {
+        child_list: true,
+        ..Default::default()
+    };
+    doc.observe_mutations(root_id, options, move |records| {
+        observed_records.lock().unwrap().extend(records)
+    });
+
+    let mut mutr = doc.mutate();
+    let first_id = mutr.create_text_node("first");
+    mutr.append_children(root_id, &[first_id]);
+    {
+        // e.g. the mutator used by a fragment parser
+        let mut nested = DocumentMutator::new(mutr.doc);
+        let second_id = nested.create_text_node("second");
+        nested.append_children(root_id, &[second_id]);
+    }
+    assert!(records.lock().unwrap().is_empty());
+    drop(mutr);
+
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+}
@@ -3,11 +3,14 @@ use std::mem;
 use std::ops::{Deref, DerefMut};
 
 use crate::document::make_device;
-use crate::net::{CssHandler, ImageHandler};
-use crate::node::{CanvasData, NodeFlags, SpecialElementData};
-use crate::util::ImageType;
-use crate::{Attribute, BaseDocument, ElementData, Node, NodeData, QualName, local_name, ns};
-use blitz_traits::net::Request;
+use crate::mutation_observer::MutationRecord;
+use crate::net::CssHandler;
+use crate::node::{CanvasData, NodeFlags, ShadowRootData, ShadowRootMode, SpecialElementData};
+use crate::{
+    Attribute, BaseDocument, ElementData, LocalName, Node, NodeData, QualName, can_attach_shadow,
+    local_name, ns,
+};
+use blitz_traits::net::{Request, RequestDestination};
 use blitz_traits::shell::Viewport;
 use style::invalidation::element::restyle_hints::RestyleHint;
 use style::stylesheets::OriginSet;
@@ -24,11 +27,33 @@ pub enum AppendTextErr {
     NotTextNode,
 }
 
+#[derive(Debug, Clone)]
+pub enum AttachShadowErr {
+    /// The node is not an element that shadow roots can be attached to
+    InvalidHost,
+    /// The element already has a shadow root
+    AlreadyHost,
+}
+
+/// Where [`DocumentMutator::insert_adjacent_html`] inserts nodes relative to an element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdjacentPosition {
+    /// Before the element itself
+    BeforeBegin,
+    /// Inside the element, before it's first child
+    AfterBegin,
+    /// Inside the element, after it's last child
+    BeforeEnd,
+    /// After the element itself
+    AfterEnd,
+}
+
 /// Operations that happen almost immediately, but are deferred within a
 /// function for borrow-checker reasons.
 enum SpecialOp {
     LoadImage(usize),
     LoadStylesheet(usize),
+    PreloadLink(usize),
     UnloadStylesheet(usize),
     LoadCustomPaintSource(usize),
     ProcessButtonInput(usize),
@@ -49,6 +74,9 @@ pub struct DocumentMutator<'doc> {
     /// Whether an element/attribute that affect animation status has been seen
     recompute_is_animating: bool,
 
+    /// Whether a `<link>` or `<meta>` element which may affect the window icon or theme color has changed
+    metadata_changed: bool,
+
     /// The (latest) node which has been mounted in and had autofocus=true, if any
     #[cfg(feature = "autofocus")]
     node_to_autofocus: Option<usize>,
@@ -57,11 +85,13 @@ pub struct DocumentMutator<'doc> {
 impl Drop for DocumentMutator<'_> {
     fn drop(&mut self) {
         self.flush(); // Defined at bottom of file
+        self.doc.mutation_observers.mutator_depth -= 1;
     }
 }
 
 impl DocumentMutator<'_> {
     pub fn new<'doc>(doc: &'doc mut BaseDocument) -> DocumentMutator<'doc> {
+        doc.mutation_observers.mutator_depth += 1;
         DocumentMutator {
             doc,
             eager_op_queue: Vec::new(),
@@ -69,6 +99,7 @@ impl DocumentMutator<'_> {
             style_nodes: HashSet::new(),
             form_nodes: HashSet::new(),
             recompute_is_animating: false,
+            metadata_changed: false,
             #[cfg(feature = "autofocus")]
             node_to_autofocus: None,
         }
@@ -119,6 +150,37 @@ impl DocumentMutator<'_> {
         self.doc.create_text_node(text)
     }
 
+    /// Attach a new (empty) shadow root to an element, returning the shadow root's node id
+    pub fn attach_shadow(
+        &mut self,
+        host_id: usize,
+        mode: ShadowRootMode,
+    ) -> Result<usize, AttachShadowErr> {
+        let Some(element) = self.doc.nodes[host_id].element_data() else {
+            return Err(AttachShadowErr::InvalidHost);
+        };
+        if !can_attach_shadow(&element.name) {
+            return Err(AttachShadowErr::InvalidHost);
+        }
+        if element.shadow_root.is_some() {
+            return Err(AttachShadowErr::AlreadyHost);
+        }
+
+        let shadow_root_id = self
+            .doc
+            .create_node(NodeData::ShadowRoot(ShadowRootData::new(host_id, mode)));
+        self.doc.shadow_roots.insert(shadow_root_id);
+
+        let host = &mut self.doc.nodes[host_id];
+        host.element_data_mut().unwrap().shadow_root = Some(shadow_root_id);
+        host.set_restyle_hint(RestyleHint::restyle_subtree());
+        if host.flags.is_in_document() {
+            self.process_added_subtree(shadow_root_id);
+        }
+
+        Ok(shadow_root_id)
+    }
+
     pub fn create_element(&mut self, name: QualName, attrs: Vec<Attribute>) -> usize {
         let mut data = ElementData::new(name, attrs);
         data.flush_style_attribute(self.doc.guard(), &self.doc.url.url_extra_data());
@@ -149,21 +211,35 @@ impl DocumentMutator<'_> {
 
         let changed = text.content != value;
         if changed {
-            text.content.clear();
-            text.content.push_str(value);
+            let old_value = mem::replace(&mut text.content, value.to_string());
             let parent = node.parent;
             self.maybe_record_node(parent);
+            if self.is_observing_mutations() {
+                self.doc
+                    .queue_mutation_record(MutationRecord::character_data(node_id, old_value));
+            }
         }
     }
 
     pub fn append_text_to_node(&mut self, node_id: usize, text: &str) -> Result<(), AppendTextErr> {
-        match self.doc.nodes[node_id].text_data_mut() {
-            Some(data) => {
-                data.content += text;
-                Ok(())
-            }
-            None => Err(AppendTextErr::NotTextNode),
+        let is_observing_mutations = self.is_observing_mutations();
+        let node = &mut self.doc.nodes[node_id];
+        let Some(data) = node.text_data_mut() else {
+            return Err(AppendTextErr::NotTextNode);
+        };
+        let old_value = is_observing_mutations.then(|| data.content.clone());
+        data.content += text;
+
+        if let Some(old_value) = old_value {
+            self.doc
+                .queue_mutation_record(MutationRecord::character_data(node_id, old_value));
         }
+
+        // Text may be appended to a `<style>` or `<title>` across several calls (e.g. when parsing
+        // HTML incrementally) so make sure the parent is reprocessed.
+        let parent = self.doc.nodes[node_id].parent;
+        self.maybe_record_node(parent);
+        Ok(())
     }
 
     pub fn add_attrs_if_missing(&mut self, node_id: usize, attrs: Vec<Attribute>) {
@@ -186,6 +262,7 @@ impl DocumentMutator<'_> {
 
     pub fn set_attribute(&mut self, node_id: usize, name: QualName, value: &str) {
         self.doc.snapshot_node(node_id);
+        self.queue_attribute_mutation(node_id, &name, false);
 
         let node = &mut self.doc.nodes[node_id];
         if let Some(data) = &mut *node.stylo_element_data.borrow_mut() {
@@ -197,6 +274,9 @@ impl DocumentMutator<'_> {
         };
 
         element.attrs.set(name.clone(), value);
+        if let Some(custom_element) = self.doc.custom_elements.get_mut(&node_id) {
+            custom_element.attribute_changed(&name, Some(value));
+        }
 
         let tag = &element.name.local;
         let attr = &name.local;
@@ -215,19 +295,27 @@ impl DocumentMutator<'_> {
             return;
         }
 
+        if is_metadata_attr(tag, attr) {
+            self.metadata_changed = true;
+        }
         if *attr == local_name!("style") {
             element.flush_style_attribute(&self.doc.guard, &self.doc.url.url_extra_data());
         } else if (tag, attr) == tag_and_attr!("input", "checked") {
             set_input_checked_state(element, value.to_string());
-        } else if (tag, attr) == tag_and_attr!("img", "src") {
+        } else if *tag == local_name!("img")
+            && matches!(attr.as_ref(), "src" | "srcset" | "sizes" | "loading")
+        {
             self.load_image(node_id);
         } else if (tag, attr) == tag_and_attr!("canvas", "src") {
             self.load_custom_paint_src(node_id);
+        } else if *tag == local_name!("link") && matches!(attr.as_ref(), "rel" | "href" | "as") {
+            self.doc.preload_link(node_id);
         }
     }
 
     pub fn clear_attribute(&mut self, node_id: usize, name: QualName) {
         self.doc.snapshot_node(node_id);
+        self.queue_attribute_mutation(node_id, &name, true);
 
         let node = &mut self.doc.nodes[node_id];
 
@@ -246,6 +334,9 @@ impl DocumentMutator<'_> {
         if !had_attr {
             return;
         }
+        if let Some(custom_element) = self.doc.custom_elements.get_mut(&node_id) {
+            custom_element.attribute_changed(&name, None);
+        }
 
         // Update text input value
         if name.local == local_name!("value") {
@@ -256,17 +347,25 @@ impl DocumentMutator<'_> {
 
         let tag = &element.name.local;
         let attr = &name.local;
+        if is_metadata_attr(tag, attr) {
+            self.metadata_changed = true;
+        }
         if *attr == local_name!("style") {
             element.flush_style_attribute(&self.doc.guard, &self.doc.url.url_extra_data());
         } else if (tag, attr) == tag_and_attr!("canvas", "src") {
             self.recompute_is_animating = true;
         } else if (tag, attr) == tag_and_attr!("link", "href") {
             self.unload_stylesheet(node_id);
+        } else if *tag == local_name!("img")
+            && matches!(attr.as_ref(), "src" | "srcset" | "sizes" | "loading")
+        {
+            self.load_image(node_id);
         }
     }
 
     /// Remove the node from it's parent but don't drop it
     pub fn remove_node(&mut self, node_id: usize) {
+        self.queue_removal_mutation(node_id);
         let node = &mut self.doc.nodes[node_id];
 
         // Update child_idx values
@@ -280,11 +379,17 @@ impl DocumentMutator<'_> {
     }
 
     pub fn remove_and_drop_node(&mut self, node_id: usize) -> Option<Node> {
+        self.queue_removal_mutation(node_id);
         self.process_removed_subtree(node_id);
 
         fn remove_node_ignoring_parent(mutr: &mut DocumentMutator, node_id: usize) -> Option<Node> {
             let mut node = mutr.doc.nodes.try_remove(node_id);
+            mutr.doc.custom_elements.remove(&node_id);
             if let Some(node) = &mut node {
+                if let Some(shadow_root_id) = node.element_data().and_then(|e| e.shadow_root) {
+                    mutr.doc.shadow_roots.remove(&shadow_root_id);
+                    remove_node_ignoring_parent(mutr, shadow_root_id);
+                }
                 for &child in &node.children {
                     remove_node_ignoring_parent(mutr, child);
                 }
@@ -336,6 +441,10 @@ impl DocumentMutator<'_> {
         child_ids: &[usize],
         insert_children_fn: &dyn Fn(&mut Node, &[usize]),
     ) {
+        for &child_id in child_ids {
+            self.queue_removal_mutation(child_id);
+        }
+
         let new_parent = &mut self.doc.nodes[parent_id];
         let new_parent_is_in_doc = new_parent.flags.is_in_document();
 
@@ -359,6 +468,7 @@ impl DocumentMutator<'_> {
         }
 
         self.maybe_record_node(parent_id);
+        self.queue_insertion_mutation(parent_id, child_ids);
     }
 
     // Tree mutation methods (that defer to other methods)
@@ -374,6 +484,16 @@ impl DocumentMutator<'_> {
 
     pub fn reparent_children(&mut self, old_parent_id: usize, new_parent_id: usize) {
         let child_ids = std::mem::take(&mut self.doc.nodes[old_parent_id].children);
+        if self.is_observing_mutations() && !child_ids.is_empty() {
+            let record = MutationRecord::child_list(
+                old_parent_id,
+                Vec::new(),
+                child_ids.clone(),
+                None,
+                None,
+            );
+            self.doc.queue_mutation_record(record);
+        }
         self.maybe_record_node(old_parent_id);
         self.append_children(new_parent_id, &child_ids);
     }
@@ -382,6 +502,174 @@ impl DocumentMutator<'_> {
         self.insert_nodes_before(anchor_node_id, new_node_ids);
         self.remove_node(anchor_node_id);
     }
+
+    // HTML fragment methods (these require an HTML parser provider, and have no effect without one)
+
+    /// Replace the children of a node with the nodes parsed from `html` (like setting `Element.innerHTML`).
+    /// The previous children are dropped.
+    pub fn set_inner_html(&mut self, node_id: usize, html: &str) {
+        let Some(new_node_ids) = self.parse_html_fragment(node_id, html) else {
+            return;
+        };
+        for child_id in self.doc.nodes[node_id].children.clone() {
+            self.remove_and_drop_node(child_id);
+        }
+        self.append_children(node_id, &new_node_ids);
+    }
+
+    /// Replace a node with the nodes parsed from `html` (like setting `Element.outerHTML`), and drop it.
+    /// Has no effect if the node has no parent, or if it's parent is the document.
+    pub fn set_outer_html(&mut self, node_id: usize, html: &str) {
+        let Some(parent_id) = self.parent_id(node_id) else {
+            return;
+        };
+        if matches!(self.doc.nodes[parent_id].data, NodeData::Document) {
+            return;
+        }
+        let Some(new_node_ids) = self.parse_html_fragment(parent_id, html) else {
+            return;
+        };
+        self.insert_nodes_before(node_id, &new_node_ids);
+        self.remove_and_drop_node(node_id);
+    }
+
+    /// Insert the nodes parsed from `html` relative to a node (like `Element.insertAdjacentHTML`). Has no effect for
+    /// [`AdjacentPosition::BeforeBegin`] and [`AdjacentPosition::AfterEnd`] if the node has no parent, or if it's
+    /// parent is the document.
+    pub fn insert_adjacent_html(&mut self, node_id: usize, position: AdjacentPosition, html: &str) {
+        let context_id = match position {
+            AdjacentPosition::BeforeBegin | AdjacentPosition::AfterEnd => {
+                match self.parent_id(node_id) {
+                    Some(parent_id)
+                        if !matches!(self.doc.nodes[parent_id].data, NodeData::Document) =>
+                    {
+                        parent_id
+                    }
+                    _ => return,
+                }
+            }
+            AdjacentPosition::AfterBegin | AdjacentPosition::BeforeEnd => node_id,
+        };
+
+        let Some(new_node_ids) = self.parse_html_fragment(context_id, html) else {
+            return;
+        };
+        match position {
+            AdjacentPosition::BeforeBegin => self.insert_nodes_before(node_id, &new_node_ids),
+            AdjacentPosition::AfterBegin => match self.doc.nodes[node_id].children.first() {
+                Some(&first_child_id) => self.insert_nodes_before(first_child_id, &new_node_ids),
+                None => self.append_children(node_id, &new_node_ids),
+            },
+            AdjacentPosition::BeforeEnd => self.append_children(node_id, &new_node_ids),
+            AdjacentPosition::AfterEnd => self.insert_nodes_after(node_id, &new_node_ids),
+        }
+    }
+
+    /// Parse `html` as a fragment in the context of the node `context_id`, returning the (unparented) parsed nodes
+    fn parse_html_fragment(&mut self, context_id: usize, html: &str) -> Option<Vec<usize>> {
+        let Some(provider) = self.doc.html_parser_provider.clone() else {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("Cannot parse HTML fragment: the document has no HtmlParserProvider");
+            return None;
+        };
+
+        // Fragments are parsed in the context of a `<body>` if the context isn't an element (or is the `<html>` element)
+        let context_name = self.element_name(context_id);
+        let needs_body_context = context_name
+            .is_none_or(|name| name.ns == ns!(html) && name.local == local_name!("html"));
+        let body_id = needs_body_context.then(|| {
+            self.create_element(
+                QualName::new(None, ns!(html), local_name!("body")),
+                Vec::new(),
+            )
+        });
+
+        // Note: the container is never inserted into the tree, so the node type doesn't matter
+        let container_id = self.create_comment_node();
+        provider.parse_fragment(self, container_id, body_id.unwrap_or(context_id), html);
+
+        let node_ids = mem::take(&mut self.doc.nodes[container_id].children);
+        for &node_id in &node_ids {
+            self.doc.nodes[node_id].parent = None;
+        }
+        self.remove_and_drop_node(container_id);
+        if let Some(body_id) = body_id {
+            self.remove_and_drop_node(body_id);
+        }
+        Some(node_ids)
+    }
+
+    // Mutation observer methods
+
+    fn is_observing_mutations(&self) -> bool {
+        !self.doc.mutation_observers.is_empty()
+    }
+
+    /// Queue a record of an attribute being set (or removed, in which case nothing is queued if it wasn't set)
+    fn queue_attribute_mutation(&mut self, node_id: usize, name: &QualName, is_removal: bool) {
+        if !self.is_observing_mutations() {
+            return;
+        }
+        let Some(element) = self.doc.nodes[node_id].element_data() else {
+            return;
+        };
+        let old_value = element
+            .attrs
+            .iter()
+            .find(|attr| attr.name == *name)
+            .map(|attr| attr.value.clone());
+        if is_removal && old_value.is_none() {
+            return;
+        }
+        let record = MutationRecord::attribute(node_id, name.clone(), old_value);
+        self.doc.queue_mutation_record(record);
+    }
+
+    /// Queue a record of a node being removed from it's parent (if it has one)
+    fn queue_removal_mutation(&mut self, node_id: usize) {
+        if !self.is_observing_mutations() {
+            return;
+        }
+        let Some(parent_id) = self.doc.nodes[node_id].parent else {
+            return;
+        };
+        let siblings = &self.doc.nodes[parent_id].children;
+        let Some(index) = siblings.iter().position(|id| *id == node_id) else {
+            return;
+        };
+        let previous_sibling = index.checked_sub(1).map(|index| siblings[index]);
+        let next_sibling = siblings.get(index + 1).copied();
+        let record = MutationRecord::child_list(
+            parent_id,
+            Vec::new(),
+            vec![node_id],
+            previous_sibling,
+            next_sibling,
+        );
+        self.doc.queue_mutation_record(record);
+    }
+
+    /// Queue a record of (consecutive) nodes being inserted into a parent
+    fn queue_insertion_mutation(&mut self, parent_id: usize, child_ids: &[usize]) {
+        if !self.is_observing_mutations() || child_ids.is_empty() {
+            return;
+        }
+        let siblings = &self.doc.nodes[parent_id].children;
+        let first_index = siblings.iter().position(|id| *id == child_ids[0]);
+        let previous_sibling = first_index
+            .and_then(|index| index.checked_sub(1))
+            .map(|index| siblings[index]);
+        let next_sibling =
+            first_index.and_then(|index| siblings.get(index + child_ids.len()).copied());
+        let record = MutationRecord::child_list(
+            parent_id,
+            child_ids.to_vec(),
+            Vec::new(),
+            previous_sibling,
+            next_sibling,
+        );
+        self.doc.queue_mutation_record(record);
+    }
 }
 
 impl<'doc> DocumentMutator<'doc> {
@@ -395,6 +683,11 @@ impl<'doc> DocumentMutator<'doc> {
             self.doc.shell_provider.set_window_title(title);
         }
 
+        if mem::take(&mut self.metadata_changed) {
+            self.doc.update_window_icon();
+            self.doc.update_theme_color();
+        }
+
         // Add/Update inline stylesheets (<style> elements)
         for id in self.style_nodes.drain() {
             self.doc.process_style_element(id);
@@ -410,6 +703,11 @@ impl<'doc> DocumentMutator<'doc> {
                 self.doc.set_focus_to(node_id);
             }
         }
+
+        // Records are delivered by the outermost mutator so that they are not delivered part-way through a batch
+        if self.doc.mutation_observers.mutator_depth == 1 {
+            self.doc.deliver_mutation_records();
+        }
     }
 
     fn flush_eager_ops(&mut self) {
@@ -418,6 +716,7 @@ impl<'doc> DocumentMutator<'doc> {
             match op {
                 SpecialOp::LoadImage(node_id) => self.load_image(node_id),
                 SpecialOp::LoadStylesheet(node_id) => self.load_linked_stylesheet(node_id),
+                SpecialOp::PreloadLink(node_id) => self.doc.preload_link(node_id),
                 SpecialOp::UnloadStylesheet(node_id) => self.unload_stylesheet(node_id),
                 SpecialOp::LoadCustomPaintSource(node_id) => self.load_custom_paint_src(node_id),
                 SpecialOp::ProcessButtonInput(node_id) => self.process_button_input(node_id),
@@ -430,6 +729,8 @@ impl<'doc> DocumentMutator<'doc> {
 
     fn process_added_subtree(&mut self, node_id: usize) {
         self.doc.iter_subtree_mut(node_id, |node_id, doc| {
+            doc.upgrade_custom_element(node_id);
+
             let node = &mut doc.nodes[node_id];
             node.flags.set(NodeFlags::IS_IN_DOCUMENT, true);
 
@@ -446,7 +747,12 @@ impl<'doc> DocumentMutator<'doc> {
             let tag = element.name.local.as_ref();
             match tag {
                 "title" => self.title_node = Some(node_id),
-                "link" => self.eager_op_queue.push(SpecialOp::LoadStylesheet(node_id)),
+                "link" => {
+                    self.eager_op_queue.push(SpecialOp::PreloadLink(node_id));
+                    self.eager_op_queue.push(SpecialOp::LoadStylesheet(node_id));
+                    self.metadata_changed = true;
+                }
+                "meta" => self.metadata_changed = true,
                 "img" => self.eager_op_queue.push(SpecialOp::LoadImage(node_id)),
                 "canvas" => self
                     .eager_op_queue
@@ -491,6 +797,10 @@ impl<'doc> DocumentMutator<'doc> {
                 return;
             };
 
+            if matches!(element.name.local.as_ref(), "link" | "meta") {
+                self.metadata_changed = true;
+            }
+
             match &element.special_data {
                 SpecialElementData::Stylesheet(_) => self
                     .eager_op_queue
@@ -514,11 +824,13 @@ impl<'doc> DocumentMutator<'doc> {
             return;
         };
 
-        let Some(tag_name) = self.doc.nodes[node_id]
-            .data
-            .downcast_element()
-            .map(|elem| &elem.name.local)
-        else {
+        // Nodes which aren't in the document are processed once they are added to it
+        let node = &self.doc.nodes[node_id];
+        if !node.flags.is_in_document() {
+            return;
+        }
+
+        let Some(tag_name) = node.data.downcast_element().map(|elem| &elem.name.local) else {
             return;
         };
 
@@ -547,12 +859,16 @@ impl<'doc> DocumentMutator<'doc> {
         let url = self.doc.resolve_url(href);
         self.doc.net_provider.fetch(
             self.doc.id(),
-            Request::get(url.clone()),
+            Request::get(url.clone())
+                .with_destination(RequestDestination::Stylesheet)
+                .with_initiator(target_id),
             Box::new(CssHandler {
                 node: target_id,
                 source_url: url,
                 guard: self.doc.guard.clone(),
                 provider: self.doc.net_provider.clone(),
+                document_encoding: self.doc.encoding,
+                resource_cache: self.doc.resource_cache.clone(),
             }),
         );
     }
@@ -566,27 +882,47 @@ impl<'doc> DocumentMutator<'doc> {
             unreachable!();
         };
 
-        let guard = self.doc.guard.read();
-        self.doc.stylist.remove_stylesheet(stylesheet, &guard);
-        self.doc
-            .stylist
-            .force_stylesheet_origins_dirty(OriginSet::all());
-
-        self.doc.nodes_to_stylesheet.remove(&node_id);
+        // Stylesheets in shadow trees were never added to the stylist
+        if self.doc.nodes_to_stylesheet.remove(&node_id).is_some() {
+            let guard = self.doc.guard.read();
+            self.doc.stylist.remove_stylesheet(stylesheet, &guard);
+            self.doc
+                .stylist
+                .force_stylesheet_origins_dirty(OriginSet::all());
+        } else {
+            self.doc.shadow_stylesheets_dirty = true;
+        }
     }
 
-    fn load_image(&mut self, target_id: usize) {
-        let node = &self.doc.nodes[target_id];
-        if let Some(raw_src) = node.attr(local_name!("src")) {
-            if !raw_src.is_empty() {
-                let src = self.doc.resolve_url(raw_src);
-                self.doc.net_provider.fetch(
-                    self.doc.id(),
-                    Request::get(src),
-                    Box::new(ImageHandler::new(target_id, ImageType::Image)),
-                );
-            }
+    pub(crate) fn load_image(&mut self, target_id: usize) {
+        let source = self.doc.select_image_source(target_id);
+        let node = &mut self.doc.nodes[target_id];
+        let Some(element) = node.element_data_mut() else {
+            return;
+        };
+        let previous = std::mem::replace(&mut element.image_source, source.clone());
+
+        let Some(source) = source else {
+            self.doc.lazy_images.remove(&target_id);
+            return;
+        };
+        let is_lazy = element
+            .attr(local_name!("loading"))
+            .is_some_and(|loading| loading.eq_ignore_ascii_case("lazy"));
+        let is_pending = self.doc.lazy_images.contains(&target_id);
+        if !is_pending && previous.is_some_and(|previous| previous.url == source.url) {
+            // Same image at a different density (e.g. a width descriptor with a new `sizes` value)
+            node.cache.clear();
+            return;
+        }
+
+        // Lazy images are fetched once they are near the viewport (see `BaseDocument::load_lazy_images`)
+        if is_lazy {
+            self.doc.lazy_images.insert(target_id);
+            return;
         }
+        self.doc.lazy_images.remove(&target_id);
+        self.doc.fetch_image(target_id, source.url);
     }
 
     fn load_custom_paint_src(&mut self, target_id: usize) {
@@ -647,6 +983,15 @@ fn set_input_checked_state(element: &mut ElementData, value: String) {
     }
 }
 
+/// Whether changing the attribute may change the window icon or theme color
+fn is_metadata_attr(tag: &LocalName, attr: &LocalName) -> bool {
+    match tag.as_ref() {
+        "link" => matches!(attr.as_ref(), "rel" | "href" | "sizes" | "type" | "media"),
+        "meta" => matches!(attr.as_ref(), "name" | "content" | "media"),
+        _ => false,
+    }
+}
+
 /// Type that allows mutable access to the viewport
 /// And syncs it back to stylist on drop.
 pub struct ViewportMut<'doc> {
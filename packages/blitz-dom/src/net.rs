@@ -1,5 +1,13 @@
 use selectors::context::QuirksMode;
-use std::{io::Cursor, sync::Arc, sync::atomic::AtomicBool};
+use std::{
+    future::Future,
+    io::Cursor,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    sync::{Arc, Mutex},
+    task::{Context as TaskContext, Poll, Waker},
+    time::Duration,
+};
 use style::{
     font_face::{FontFaceSourceFormat, FontFaceSourceFormatKeyword, Source},
     media_queries::MediaList,
@@ -16,19 +24,29 @@ use style::{
     values::{CssUrl, SourceLocation},
 };
 
-use blitz_traits::net::{Bytes, NetHandler, Request, SharedCallback, SharedProvider};
+use blitz_traits::net::{
+    BoxedHandler, Bytes, DummyNetCallback, NetCallback, NetHandler, NetProvider, Request,
+    RequestDestination, ResponseHead, SharedCallback, SharedProvider,
+};
+use blitz_traits::shell::WindowIcon;
 
 use url::Url;
 
+use crate::cache::{CacheKey, DecodedImage, ResourceCache};
+use crate::encoding::{Encoding, decode_css};
+use crate::image_decoder::ImageDecoder;
+use crate::node::{ImageFrame, RasterImageData};
 use crate::util::ImageType;
 
 #[derive(Clone, Debug)]
 pub enum Resource {
     Image(usize, ImageType, u32, u32, Arc<Vec<u8>>),
+    AnimatedImage(usize, ImageType, Vec<ImageFrame>),
     #[cfg(feature = "svg")]
-    Svg(usize, ImageType, Box<usvg::Tree>),
+    Svg(usize, ImageType, Arc<usvg::Tree>),
     Css(usize, DocumentStyleSheet),
     Font(Bytes),
+    WindowIcon(Url, WindowIcon),
     Navigation {
         url: String,
         document: Bytes,
@@ -40,10 +58,20 @@ pub struct CssHandler {
     pub source_url: Url,
     pub guard: SharedRwLock,
     pub provider: SharedProvider<Resource>,
+    /// The encoding of the document which referenced the stylesheet. Used as a fallback
+    /// if the stylesheet doesn't specify it's own encoding.
+    pub document_encoding: &'static Encoding,
+    /// Cache in which web fonts loaded by the stylesheet are stored
+    pub resource_cache: Option<ResourceCache>,
 }
 
 #[derive(Clone)]
-pub(crate) struct StylesheetLoader(pub(crate) usize, pub(crate) SharedProvider<Resource>);
+pub(crate) struct StylesheetLoader(
+    pub(crate) usize,
+    pub(crate) SharedProvider<Resource>,
+    pub(crate) &'static Encoding,
+    pub(crate) Option<ResourceCache>,
+);
 impl ServoStylesheetLoader for StylesheetLoader {
     fn request_stylesheet(
         &self,
@@ -100,29 +128,42 @@ impl ServoStylesheetLoader for StylesheetLoader {
                 bytes: Bytes,
                 callback: SharedCallback<Resource>,
             ) {
-                let Ok(css) = std::str::from_utf8(&bytes) else {
-                    callback.call(doc_id, Err(Some(String::from("Invalid UTF8"))));
-                    return;
-                };
+                self.bytes_with_content_type(doc_id, bytes, None, callback);
+            }
+
+            fn bytes_with_content_type(
+                self: Box<Self>,
+                doc_id: usize,
+                bytes: Bytes,
+                content_type: Option<&str>,
+                callback: SharedCallback<Resource>,
+            ) {
+                let css = decode_css(&bytes, content_type, self.loader.2);
 
                 // NOTE(Nico): I don't *think* external stylesheets should have HTML entities escaped
                 // let escaped_css = html_escape::decode_html_entities(css);
                 Stylesheet::update_from_str(
                     &self.sheet,
-                    css,
+                    &css,
                     UrlExtraData(self.url),
                     Some(&self.loader),
                     None,
                     AllowImportRules::Yes,
                 );
-                fetch_font_face(doc_id, &self.sheet, &self.provider, &self.read_lock.read());
+                fetch_font_face(
+                    doc_id,
+                    &self.sheet,
+                    &self.provider,
+                    self.loader.3.as_ref(),
+                    &self.read_lock.read(),
+                );
                 callback.call(doc_id, Ok(Resource::None))
             }
         }
         let url = import.url.url().unwrap();
         self.1.fetch(
             self.0,
-            Request::get(url.as_ref().clone()),
+            Request::get(url.as_ref().clone()).with_destination(RequestDestination::Stylesheet),
             Box::new(StylesheetLoaderInner {
                 url: url.clone(),
                 loader: self.clone(),
@@ -137,27 +178,45 @@ impl ServoStylesheetLoader for StylesheetLoader {
 }
 impl NetHandler<Resource> for CssHandler {
     fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
-        let Ok(css) = std::str::from_utf8(&bytes) else {
-            callback.call(doc_id, Err(Some(String::from("Invalid UTF8"))));
-            return;
-        };
+        self.bytes_with_content_type(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_content_type(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<Resource>,
+    ) {
+        let css = decode_css(&bytes, content_type, self.document_encoding);
 
         // NOTE(Nico): I don't *think* external stylesheets should have HTML entities escaped
         // let escaped_css = html_escape::decode_html_entities(css);
 
         let sheet = Stylesheet::from_str(
-            css,
+            &css,
             self.source_url.into(),
             Origin::Author,
             ServoArc::new(self.guard.wrap(MediaList::empty())),
             self.guard.clone(),
-            Some(&StylesheetLoader(doc_id, self.provider.clone())),
+            Some(&StylesheetLoader(
+                doc_id,
+                self.provider.clone(),
+                self.document_encoding,
+                self.resource_cache.clone(),
+            )),
             None,
             QuirksMode::NoQuirks,
             AllowImportRules::Yes,
         );
         let read_guard = self.guard.read();
-        fetch_font_face(doc_id, &sheet, &self.provider, &read_guard);
+        fetch_font_face(
+            doc_id,
+            &sheet,
+            &self.provider,
+            self.resource_cache.as_ref(),
+            &read_guard,
+        );
 
         callback.call(
             doc_id,
@@ -168,11 +227,61 @@ impl NetHandler<Resource> for CssHandler {
         )
     }
 }
-struct FontFaceHandler(FontFaceSourceFormatKeyword);
+struct FontFaceHandler {
+    format: FontFaceSourceFormatKeyword,
+    /// The cache to store the decompressed font in, and the url of the font
+    cache: Option<(ResourceCache, Url)>,
+}
 impl NetHandler<Resource> for FontFaceHandler {
-    fn bytes(mut self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
-        if self.0 == FontFaceSourceFormatKeyword::None {
-            self.0 = match bytes.as_ref() {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.load(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.load(doc_id, bytes, Some(head), callback);
+    }
+}
+impl FontFaceHandler {
+    /// Use the cached font if the response is for the cached version of it, or otherwise decompress the font
+    /// (into the cache)
+    fn load(
+        self,
+        doc_id: usize,
+        bytes: Bytes,
+        head: Option<&ResponseHead>,
+        callback: SharedCallback<Resource>,
+    ) {
+        let Some((cache, url)) = &self.cache else {
+            return self.decompress(doc_id, bytes, callback);
+        };
+
+        let key = CacheKey::new(url, head, &bytes);
+        if let Some(font) = cache.get_font(&key) {
+            return callback.call(doc_id, Ok(Resource::Font(font)));
+        }
+        if key.is_not_modified() {
+            let error = format!("Cached font {url} was evicted before it was revalidated");
+            return callback.call(doc_id, Err(Some(error)));
+        }
+        let cache = cache.clone();
+        let callback = Arc::new(move |doc_id, result: Result<Resource, Option<String>>| {
+            if let Ok(Resource::Font(font)) = &result {
+                cache.insert_font(key.clone(), font.clone());
+            }
+            callback.call(doc_id, result);
+        });
+        self.decompress(doc_id, bytes, callback)
+    }
+
+    fn decompress(mut self, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        if self.format == FontFaceSourceFormatKeyword::None {
+            self.format = match bytes.as_ref() {
                 // WOFF (v1) files begin with 0x774F4646 ('wOFF' in ascii)
                 // See: <https://w3c.github.io/woff/woff1/spec/Overview.html#WOFFHeader>
                 // #[cfg(any(feature = "woff-c"))]
@@ -198,7 +307,7 @@ impl NetHandler<Resource> for FontFaceHandler {
         #[cfg(any(feature = "woff-c", feature = "woff-rust"))]
         let mut bytes = bytes;
 
-        match self.0 {
+        match self.format {
             // #[cfg(feature = "woff-c")]
             // FontFaceSourceFormatKeyword::Woff => {
             //     #[cfg(feature = "tracing")]
@@ -248,6 +357,7 @@ fn fetch_font_face(
     doc_id: usize,
     sheet: &Stylesheet,
     network_provider: &SharedProvider<Resource>,
+    cache: Option<&ResourceCache>,
     read_guard: &SharedRwLockReadGuard,
 ) {
     sheet
@@ -296,47 +406,607 @@ fn fetch_font_face(
                 return;
             }
             let url = url_source.url.url().unwrap().as_ref().clone();
-            network_provider.fetch(doc_id, Request::get(url), Box::new(FontFaceHandler(format)))
+            let mut request = Request::get(url.clone()).with_destination(RequestDestination::Font);
+            if let Some(cache) = cache {
+                request = cache.revalidate(request);
+            }
+            let cache = cache.map(|cache| (cache.clone(), url));
+            network_provider.fetch(doc_id, request, Box::new(FontFaceHandler { format, cache }))
         });
 }
 
-pub struct ImageHandler(usize, ImageType);
+/// Decodes fetched images (on the [`ImageDecoder`]'s worker threads)
+pub struct ImageHandler {
+    node_id: usize,
+    kind: ImageType,
+    decoder: ImageDecoder,
+    cache: Option<(ResourceCache, Url)>,
+}
 impl ImageHandler {
-    pub fn new(node_id: usize, kind: ImageType) -> Self {
-        Self(node_id, kind)
+    pub fn new(node_id: usize, kind: ImageType, decoder: ImageDecoder) -> Self {
+        Self {
+            node_id,
+            kind,
+            decoder,
+            cache: None,
+        }
     }
-}
-impl NetHandler<Resource> for ImageHandler {
-    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+
+    /// Reuse the decoded image from `cache` if the image at `url` has already been decoded
+    pub fn with_cache(mut self, cache: ResourceCache, url: Url) -> Self {
+        self.cache = Some((cache, url));
+        self
+    }
+
+    /// Use the cached image if the response is for the cached version of it, or otherwise decode the image
+    /// (into the cache)
+    fn load(&self, bytes: &[u8], head: Option<&ResponseHead>) -> Result<DecodedImage, String> {
+        let Some((cache, url)) = &self.cache else {
+            return self.decode(bytes);
+        };
+
+        let key = CacheKey::new(url, head, bytes);
+        if let Some(image) = cache.get_image(&key) {
+            return Ok(image);
+        }
+        if key.is_not_modified() {
+            return Err(format!(
+                "Cached image {url} was evicted before it was revalidated"
+            ));
+        }
+        let image = self.decode(bytes)?;
+        cache.insert_image(key, image.clone(), bytes.len());
+        Ok(image)
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<DecodedImage, String> {
+        use image::ImageDecoder as _;
+
+        let max_decoded_size = self.decoder.max_decoded_size();
+        if let Some(frames) = decode_animated_image(bytes, max_decoded_size) {
+            return Ok(DecodedImage::Animated(frames));
+        }
+
         // Try parse image
-        if let Ok(image) = image::ImageReader::new(Cursor::new(&bytes))
+        let mut reader = image::ImageReader::new(Cursor::new(bytes))
             .with_guessed_format()
-            .expect("IO errors impossible with Cursor")
-            .decode()
-        {
-            let raw_rgba8_data = image.clone().into_rgba8().into_raw();
-            callback.call(
-                doc_id,
-                Ok(Resource::Image(
-                    self.0,
-                    self.1,
-                    image.width(),
-                    image.height(),
+            .expect("IO errors impossible with Cursor");
+        let mut limits = image::Limits::default();
+        limits.max_alloc = Some(max_decoded_size);
+        reader.limits(limits);
+        if let Ok(decoder) = reader.into_decoder() {
+            let (width, height) = decoder.dimensions();
+            if rgba8_size(width, height) > max_decoded_size {
+                return Err(format!(
+                    "{width}x{height} image exceeds the maximum decoded size of {max_decoded_size} bytes"
+                ));
+            }
+            if let Ok(image) = image::DynamicImage::from_decoder(decoder) {
+                let raw_rgba8_data = image.into_rgba8().into_raw();
+                return Ok(DecodedImage::Raster(RasterImageData::new(
+                    width,
+                    height,
                     Arc::new(raw_rgba8_data),
-                )),
-            );
-            return;
-        };
+                )));
+            }
+        }
 
         #[cfg(feature = "svg")]
         {
             use crate::util::parse_svg;
-            if let Ok(tree) = parse_svg(&bytes) {
-                callback.call(doc_id, Ok(Resource::Svg(self.0, self.1, Box::new(tree))));
-                return;
+            if let Ok(tree) = parse_svg(bytes) {
+                return Ok(DecodedImage::Svg(Arc::new(tree)));
             }
         }
 
-        callback.call(doc_id, Err(Some(String::from("Could not parse image"))))
+        Err(String::from("Could not parse image"))
+    }
+
+    fn into_resource(self, image: DecodedImage) -> Resource {
+        match image {
+            DecodedImage::Raster(image) => Resource::Image(
+                self.node_id,
+                self.kind,
+                image.width,
+                image.height,
+                image.data,
+            ),
+            DecodedImage::Animated(frames) => {
+                Resource::AnimatedImage(self.node_id, self.kind, frames)
+            }
+            #[cfg(feature = "svg")]
+            DecodedImage::Svg(tree) => Resource::Svg(self.node_id, self.kind, tree),
+        }
+    }
+
+    fn spawn_load(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: Option<ResponseHead>,
+        callback: SharedCallback<Resource>,
+    ) {
+        let decoder = self.decoder.clone();
+        decoder.spawn(move || {
+            let result = match self.load(&bytes, head.as_ref()) {
+                Ok(image) => Ok(self.into_resource(image)),
+                Err(err) => Err(Some(err)),
+            };
+            callback.call(doc_id, result);
+        });
+    }
+}
+impl NetHandler<Resource> for ImageHandler {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.spawn_load(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.spawn_load(doc_id, bytes, Some(head.clone()), callback);
     }
 }
+
+/// Decode a preloaded font or image into `cache`, so that it doesn't need to be decoded once it is used
+pub(crate) fn warm_resource_cache(
+    doc_id: usize,
+    destination: RequestDestination,
+    url: &Url,
+    bytes: Bytes,
+    head: Option<&ResponseHead>,
+    cache: &ResourceCache,
+    decoder: &ImageDecoder,
+) {
+    let handler: BoxedHandler<Resource> = match destination {
+        RequestDestination::Font => Box::new(FontFaceHandler {
+            format: FontFaceSourceFormatKeyword::None,
+            cache: Some((cache.clone(), url.clone())),
+        }),
+        RequestDestination::Image => Box::new(
+            ImageHandler::new(0, ImageType::Image, decoder.clone())
+                .with_cache(cache.clone(), url.clone()),
+        ),
+        _ => return,
+    };
+    // The decoded resource is only inserted into the cache (not loaded into the document)
+    let callback = Arc::new(DummyNetCallback);
+    match head {
+        Some(head) => handler.bytes_with_head(doc_id, bytes, head, callback),
+        None => handler.bytes(doc_id, bytes, callback),
+    }
+}
+
+/// The number of bytes that an image decodes to in RGBA8 format
+fn rgba8_size(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * 4
+}
+
+/// Browsers display frames with a delay of 10ms or less for 100ms, as many GIFs
+/// specify a delay of zero but were authored expecting this behaviour.
+const MIN_FRAME_DELAY: Duration = Duration::from_millis(10);
+const DEFAULT_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// Decode all of the frames of an animated GIF, APNG or WebP image.
+///
+/// Returns `None` if the image is not animated (or isn't in one of these formats) so that
+/// it can be decoded as a still image instead.
+fn decode_animated_image(bytes: &[u8], max_decoded_size: u64) -> Option<Vec<ImageFrame>> {
+    use image::codecs::{gif::GifDecoder, png::PngDecoder, webp::WebPDecoder};
+    use image::{AnimationDecoder, ImageDecoder as _, ImageFormat};
+
+    let ((width, height), frames) = match image::guess_format(bytes).ok()? {
+        ImageFormat::Gif => {
+            let decoder = GifDecoder::new(Cursor::new(bytes)).ok()?;
+            (decoder.dimensions(), decoder.into_frames())
+        }
+        ImageFormat::Png => {
+            let decoder = PngDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.is_apng().ok()? {
+                return None;
+            }
+            (decoder.dimensions(), decoder.apng().ok()?.into_frames())
+        }
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes)).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            (decoder.dimensions(), decoder.into_frames())
+        }
+        _ => return None,
+    };
+
+    // Every frame is composited onto a buffer the size of the whole image. Animations that exceed
+    // the maximum decoded size fall back to being decoded as a still image (of their first frame).
+    let mut decoded_size = 0;
+    let mut decoded_frames = Vec::new();
+    for frame in frames {
+        decoded_size += rgba8_size(width, height);
+        if decoded_size > max_decoded_size {
+            return None;
+        }
+
+        let frame = frame.ok()?;
+        let delay = Duration::from(frame.delay());
+        let buffer = frame.into_buffer();
+        decoded_frames.push(ImageFrame {
+            image: RasterImageData::new(
+                buffer.width(),
+                buffer.height(),
+                Arc::new(buffer.into_raw()),
+            ),
+            delay: if delay <= MIN_FRAME_DELAY {
+                DEFAULT_FRAME_DELAY
+            } else {
+                delay
+            },
+        });
+    }
+
+    (decoded_frames.len() >= 2).then_some(decoded_frames)
+}
+
+/// Tracks the number of resources that a document is waiting on.
+///
+/// A resource is pending from the moment it is requested until it has been passed to
+/// [`BaseDocument::load_resource`](crate::BaseDocument::load_resource) (or until the request fails).
+#[derive(Clone, Default)]
+pub struct ResourceTracker {
+    inner: Arc<ResourceTrackerInner>,
+}
+
+#[derive(Default)]
+struct ResourceTrackerInner {
+    pending: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl ResourceTracker {
+    /// The number of resources that have been requested but not yet loaded into the document
+    pub fn pending_count(&self) -> usize {
+        self.inner.pending.load(Ordering::SeqCst)
+    }
+
+    /// A future which resolves once there are no pending resources.
+    ///
+    /// Note that the document must continue to be driven (by passing fetched resources to
+    /// [`BaseDocument::load_resource`](crate::BaseDocument::load_resource)) for this future to resolve.
+    pub fn idle(&self) -> ResourcesIdle {
+        ResourcesIdle(self.clone())
+    }
+
+    /// Register a waker to be woken once there are no pending resources
+    pub(crate) fn register_waker(&self, waker: &Waker) {
+        let mut wakers = self.inner.wakers.lock().unwrap();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+        }
+        drop(wakers);
+
+        // The last resource may have finished loading while we were registering
+        if self.pending_count() == 0 {
+            self.wake_all();
+        }
+    }
+
+    pub(crate) fn increment(&self) {
+        self.inner.pending.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub(crate) fn decrement(&self) {
+        let prev = self
+            .inner
+            .pending
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        if prev == Ok(1) {
+            self.wake_all();
+        }
+    }
+
+    fn wake_all(&self) {
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+}
+
+/// Future returned by [`ResourceTracker::idle`]
+pub struct ResourcesIdle(ResourceTracker);
+
+impl Future for ResourcesIdle {
+    type Output = ();
+    fn poll(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<()> {
+        if self.0.pending_count() == 0 {
+            return Poll::Ready(());
+        }
+        self.0.register_waker(cx.waker());
+        match self.0.pending_count() {
+            0 => Poll::Ready(()),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// A [`NetProvider`] which records requests with a [`ResourceTracker`] before forwarding
+/// them to the underlying provider.
+pub(crate) struct TrackingNetProvider {
+    pub(crate) inner: SharedProvider<Resource>,
+    pub(crate) tracker: ResourceTracker,
+}
+
+impl NetProvider<Resource> for TrackingNetProvider {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        self.tracker.increment();
+        let handler = Box::new(TrackingHandler {
+            inner: Some(handler),
+            tracker: self.tracker.clone(),
+        });
+        self.inner.fetch(doc_id, request, handler);
+    }
+}
+
+/// Wraps a [`NetHandler`], marking the request as complete if it is dropped without being passed a response.
+///
+/// Otherwise the request remains pending until the inner handler calls the callback, which may happen
+/// asynchronously (images are decoded on worker threads). Successfully parsed resources remain pending
+/// until they are loaded into the document.
+struct TrackingHandler {
+    inner: Option<BoxedHandler<Resource>>,
+    tracker: ResourceTracker,
+}
+
+impl TrackingHandler {
+    /// Wrap the callback so that it takes over the pending request
+    fn tracking_callback(&self, callback: SharedCallback<Resource>) -> SharedCallback<Resource> {
+        Arc::new(TrackingCallback {
+            inner: callback,
+            tracker: self.tracker.clone(),
+            pending: AtomicBool::new(true),
+        })
+    }
+}
+
+impl NetHandler<Resource> for TrackingHandler {
+    fn bytes(mut self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        let callback = self.tracking_callback(callback);
+        if let Some(inner) = self.inner.take() {
+            inner.bytes(doc_id, bytes, callback);
+        }
+    }
+
+    fn bytes_with_content_type(
+        mut self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<Resource>,
+    ) {
+        let callback = self.tracking_callback(callback);
+        if let Some(inner) = self.inner.take() {
+            inner.bytes_with_content_type(doc_id, bytes, content_type, callback);
+        }
+    }
+
+    fn bytes_with_head(
+        mut self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Resource>,
+    ) {
+        let callback = self.tracking_callback(callback);
+        if let Some(inner) = self.inner.take() {
+            inner.bytes_with_head(doc_id, bytes, head, callback);
+        }
+    }
+
+    fn failed(
+        mut self: Box<Self>,
+        doc_id: usize,
+        error: String,
+        callback: SharedCallback<Resource>,
+    ) {
+        let callback = self.tracking_callback(callback);
+        if let Some(inner) = self.inner.take() {
+            inner.failed(doc_id, error, callback);
+        }
+    }
+}
+
+impl Drop for TrackingHandler {
+    fn drop(&mut self) {
+        // The request failed (or was cancelled) before a response was received
+        if self.inner.is_some() {
+            self.tracker.decrement();
+        }
+    }
+}
+
+/// Wraps the callback passed to a tracked handler. The request remains pending until the callback is
+/// first called, or until it is dropped without being called (e.g. because the handler ignored the response).
+struct TrackingCallback {
+    inner: SharedCallback<Resource>,
+    tracker: ResourceTracker,
+    pending: AtomicBool,
+}
+
+impl NetCallback<Resource> for TrackingCallback {
+    fn call(&self, doc_id: usize, result: Result<Resource, Option<String>>) {
+        let was_pending = self.pending.swap(false, Ordering::SeqCst);
+        match (&result, was_pending) {
+            // The pending request becomes the pending load of the resource
+            (Ok(_), true) => {}
+            // Each additional resource is pending until it has been loaded
+            (Ok(_), false) => self.tracker.increment(),
+            (Err(_), true) => self.tracker.decrement(),
+            (Err(_), false) => {}
+        }
+        self.inner.call(doc_id, result);
+    }
+}
+
+impl Drop for TrackingCallback {
+    fn drop(&mut self) {
+        if *self.pending.get_mut() {
+            self.tracker.decrement();
+        }
+    }
+}
+
+#[test]
+fn tracks_pending_resources_until_loaded() {
+    use std::sync::mpsc;
+
+    // A provider which immediately serves an empty response for every request
+    struct EmptyProvider(SharedCallback<Resource>);
+    impl NetProvider<Resource> for EmptyProvider {
+        fn fetch(&self, doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            handler.bytes(doc_id, Bytes::new(), self.0.clone());
+        }
+    }
+    struct NoopHandler;
+    impl NetHandler<Resource> for NoopHandler {
+        fn bytes(
+            self: Box<Self>,
+            doc_id: usize,
+            _bytes: Bytes,
+            callback: SharedCallback<Resource>,
+        ) {
+            callback.call(doc_id, Ok(Resource::None));
+        }
+    }
+
+    let (send, recv) = mpsc::channel();
+    let callback = move |_doc_id, result: Result<Resource, Option<String>>| {
+        send.send(result.unwrap()).unwrap();
+    };
+    let provider = Arc::new(EmptyProvider(Arc::new(callback)));
+
+    let mut doc = crate::BaseDocument::new(crate::DocumentConfig {
+        net_provider: Some(provider),
+        ..Default::default()
+    });
+    let url = Url::parse("https://example.com/image.png").unwrap();
+    doc.net_provider
+        .fetch(doc.id(), Request::get(url), Box::new(NoopHandler));
+    assert_eq!(doc.pending_resource_count(), 1);
+
+    doc.mark_content_loaded();
+    assert_eq!(doc.ready_state(), crate::DocumentReadyState::Interactive);
+
+    doc.load_resource(recv.try_recv().unwrap());
+    assert_eq!(doc.pending_resource_count(), 0);
+    assert_eq!(doc.ready_state(), crate::DocumentReadyState::Complete);
+}
+
+#[test]
+fn tracks_images_until_decoded() {
+    use markup5ever::{QualName, local_name, ns};
+    use std::sync::mpsc;
+
+    // A provider which immediately serves the same image for every request
+    struct ImageProvider {
+        image: Bytes,
+        callback: SharedCallback<Resource>,
+    }
+    impl NetProvider<Resource> for ImageProvider {
+        fn fetch(&self, doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            handler.bytes(doc_id, self.image.clone(), self.callback.clone());
+        }
+    }
+
+    let mut png = Vec::new();
+    image::RgbaImage::new(2, 2)
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let (send, recv) = mpsc::channel();
+    let callback = move |_doc_id, result: Result<Resource, Option<String>>| {
+        send.send(result.unwrap()).unwrap();
+    };
+    let provider = Arc::new(ImageProvider {
+        image: Bytes::from(png),
+        callback: Arc::new(callback),
+    });
+
+    // Keep the decoder's only thread busy until the image has been requested
+    let decoder = ImageDecoder::new(1, u64::MAX);
+    let (unblock, blocked) = mpsc::channel::<()>();
+    decoder.spawn(move || {
+        let _ = blocked.recv();
+    });
+
+    let mut doc = crate::BaseDocument::new(crate::DocumentConfig {
+        net_provider: Some(provider),
+        ..Default::default()
+    });
+    let mut mutr = doc.mutate();
+    let img = mutr.create_element(
+        QualName::new(None, ns!(html), local_name!("img")),
+        Vec::new(),
+    );
+    mutr.append_children(0, &[img]);
+    drop(mutr);
+
+    let url = Url::parse("https://example.com/image.png").unwrap();
+    let handler = ImageHandler::new(img, ImageType::Image, decoder);
+    doc.net_provider
+        .fetch(doc.id(), Request::get(url), Box::new(handler));
+    // The response has been received, but the image has not been decoded yet
+    assert_eq!(doc.pending_resource_count(), 1);
+
+    unblock.send(()).unwrap();
+    let resource = recv.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert!(matches!(resource, Resource::Image(node_id, ..) if node_id == img));
+    assert_eq!(doc.pending_resource_count(), 1);
+
+    doc.load_resource(resource);
+    assert_eq!(doc.pending_resource_count(), 0);
+}
+
+#[test]
+fn decodes_animated_gif_frames() {
+    use crate::node::AnimatedImageData;
+    use image::codecs::gif::GifEncoder;
+    use image::{Delay, Frame, Rgba, RgbaImage};
+
+    let mut gif = Vec::new();
+    {
+        let mut encoder = GifEncoder::new(&mut gif);
+        for (color, delay_ms) in [([255, 0, 0, 255], 0), ([0, 0, 255, 255], 250)] {
+            let buffer = RgbaImage::from_pixel(2, 2, Rgba(color));
+            let delay = Delay::from_numer_denom_ms(delay_ms, 1);
+            encoder
+                .encode_frame(Frame::from_parts(buffer, 0, 0, delay))
+                .unwrap();
+        }
+    }
+
+    let frames = decode_animated_image(&gif, u64::MAX).unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].delay, DEFAULT_FRAME_DELAY);
+    assert_eq!(frames[1].delay, Duration::from_millis(250));
+    assert_eq!(&frames[1].image.data[..4], &[0, 0, 255, 255]);
+
+    let mut image = AnimatedImageData::new(frames, Duration::from_secs(1));
+    assert!(!image.advance_to(Duration::from_millis(1050)));
+    assert!(image.advance_to(Duration::from_millis(1100)));
+    assert_eq!(image.current_frame, 1);
+    // The animation loops after 350ms
+    assert!(image.advance_to(Duration::from_millis(1360)));
+    assert_eq!(image.current_frame, 0);
+
+    // Still images are not decoded as animations
+    let mut png = Vec::new();
+    RgbaImage::new(2, 2)
+        .write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    assert!(decode_animated_image(&png, u64::MAX).is_none());
+    // Animations which are too large to decode in full are displayed as still images
+    assert!(decode_animated_image(&gif, 2 * 2 * 4).is_none());
+}
@@ -0,0 +1,291 @@
+//! A log of the network requests made by a document
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use blitz_traits::net::{
+    BoxedHandler, Bytes, Method, NetHandler, NetProvider, Request, RequestDestination,
+    ResponseHead, SharedCallback, SharedProvider, StatusCode,
+};
+use url::Url;
+
+use crate::net::Resource;
+
+/// The state of a request in a [`NetworkLog`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NetworkRequestStatus {
+    /// The request has been made, but no response has been received yet
+    Pending,
+    /// The response has been received (which may have an error status, see [`NetworkRequest::status_code`])
+    Complete,
+    /// The request failed, or was blocked by the document's [`ResourcePolicy`](crate::ResourcePolicy)
+    Failed,
+}
+
+/// A request in a [`NetworkLog`]
+#[derive(Debug, Clone)]
+pub struct NetworkRequest {
+    /// Identifies the request within the log
+    pub id: u64,
+    pub url: Url,
+    pub method: Method,
+    /// The kind of resource that was requested
+    pub destination: RequestDestination,
+    /// The id of the node which caused the request to be made (if any)
+    pub initiator: Option<usize>,
+    pub status: NetworkRequestStatus,
+    /// The HTTP status of the response (if known). Only set if the `NetProvider` passes it to the request's
+    /// handler (as `blitz_net::Provider` does).
+    pub status_code: Option<StatusCode>,
+    /// The `Content-Type` of the response (if known)
+    pub content_type: Option<String>,
+    /// The size of the response body in bytes (once it has been received)
+    pub size: Option<usize>,
+    /// When the request was made, relative to the document's time origin
+    pub start_time: Duration,
+    /// How long the request took to complete or fail
+    pub duration: Option<Duration>,
+}
+
+/// A log of the network requests made by a document, from which embedders can build a network
+/// inspector (or make assertions in tests).
+///
+/// Every request that is passed to the document's `NetProvider` is recorded, including preloads and
+/// requests which are blocked by the document's resource policy. Requests which are served from a
+/// preloaded response are not recorded a second time. Handles are cheap to clone.
+#[derive(Clone)]
+pub struct NetworkLog {
+    inner: Arc<Mutex<NetworkLogInner>>,
+    time_origin: Instant,
+}
+
+struct NetworkLogInner {
+    requests: Vec<NetworkRequest>,
+    next_id: u64,
+}
+
+impl NetworkLog {
+    pub(crate) fn new(time_origin: Instant) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(NetworkLogInner {
+                requests: Vec::new(),
+                next_id: 0,
+            })),
+            time_origin,
+        }
+    }
+
+    /// The requests in the log, in the order in which they were made
+    pub fn requests(&self) -> Vec<NetworkRequest> {
+        self.inner.lock().unwrap().requests.clone()
+    }
+
+    /// The number of requests in the log
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all requests from the log. Requests which are still pending are not added back once they complete.
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().requests.clear();
+    }
+
+    fn record(&self, request: &Request) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        let id = inner.next_id;
+        inner.next_id += 1;
+        inner.requests.push(NetworkRequest {
+            id,
+            url: request.url.clone(),
+            method: request.method.clone(),
+            destination: request.destination,
+            initiator: request.initiator,
+            status: NetworkRequestStatus::Pending,
+            status_code: None,
+            content_type: None,
+            size: None,
+            start_time: self.time_origin.elapsed(),
+            duration: None,
+        });
+        id
+    }
+
+    fn update(&self, id: u64, update: impl FnOnce(&mut NetworkRequest)) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(request) = inner.requests.iter_mut().rev().find(|req| req.id == id) {
+            request.duration = Some(self.time_origin.elapsed() - request.start_time);
+            update(request);
+        }
+    }
+}
+
+/// A [`NetProvider`] which records requests in a [`NetworkLog`] before forwarding them to the underlying provider
+pub(crate) struct LoggingNetProvider {
+    pub(crate) inner: SharedProvider<Resource>,
+    pub(crate) log: NetworkLog,
+}
+
+impl NetProvider<Resource> for LoggingNetProvider {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        let id = self.log.record(&request);
+        let handler = Box::new(LoggingHandler {
+            inner: Some(handler),
+            log: self.log.clone(),
+            id,
+        });
+        self.inner.fetch(doc_id, request, handler);
+    }
+}
+
+/// Wraps a [`NetHandler`], recording the response in the log. If the handler is dropped without
+/// receiving a response then the request is recorded as failed.
+struct LoggingHandler {
+    inner: Option<BoxedHandler<Resource>>,
+    log: NetworkLog,
+    id: u64,
+}
+
+impl LoggingHandler {
+    fn record_response(
+        &self,
+        status_code: Option<StatusCode>,
+        content_type: Option<&str>,
+        size: usize,
+    ) {
+        self.log.update(self.id, |request| {
+            request.status = NetworkRequestStatus::Complete;
+            request.status_code = status_code;
+            request.content_type = content_type.map(String::from);
+            request.size = Some(size);
+        });
+    }
+}
+
+impl NetHandler<Resource> for LoggingHandler {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.bytes_with_content_type(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_content_type(
+        mut self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.record_response(None, content_type, bytes.len());
+        if let Some(inner) = self.inner.take() {
+            inner.bytes_with_content_type(doc_id, bytes, content_type, callback);
+        }
+    }
+
+    fn bytes_with_head(
+        mut self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.record_response(Some(head.status), head.content_type(), bytes.len());
+        if let Some(inner) = self.inner.take() {
+            inner.bytes_with_head(doc_id, bytes, head, callback);
+        }
+    }
+
+    fn failed(
+        mut self: Box<Self>,
+        doc_id: usize,
+        error: String,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.log.update(self.id, |request| {
+            request.status = NetworkRequestStatus::Failed;
+        });
+        if let Some(inner) = self.inner.take() {
+            inner.failed(doc_id, error, callback);
+        }
+    }
+}
+
+impl Drop for LoggingHandler {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            self.log.update(self.id, |request| {
+                request.status = NetworkRequestStatus::Failed;
+            });
+        }
+    }
+}
+
+#[test]
+fn logs_completed_and_failed_requests() {
+    use blitz_traits::net::DummyNetCallback;
+
+    // A provider which serves `.css` files, responds to `.html` files with a 404 and fails all other requests
+    struct CssProvider;
+    impl NetProvider<Resource> for CssProvider {
+        fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+            if request.url.path().ends_with(".css") {
+                let body = Bytes::from_static(b"body { color: red }");
+                handler.bytes_with_content_type(
+                    doc_id,
+                    body,
+                    Some("text/css"),
+                    Arc::new(DummyNetCallback),
+                );
+            } else if request.url.path().ends_with(".html") {
+                let head = ResponseHead::new(StatusCode::NOT_FOUND, Default::default());
+                let body = Bytes::from_static(b"Not Found");
+                handler.bytes_with_head(doc_id, body, &head, Arc::new(DummyNetCallback));
+            }
+        }
+    }
+    struct NoopHandler;
+    impl NetHandler<Resource> for NoopHandler {
+        fn bytes(self: Box<Self>, _: usize, _: Bytes, _: SharedCallback<Resource>) {}
+    }
+
+    let log = NetworkLog::new(Instant::now());
+    let provider = LoggingNetProvider {
+        inner: Arc::new(CssProvider),
+        log: log.clone(),
+    };
+    let url = |path: &str| {
+        Url::parse("https://example.com/")
+            .unwrap()
+            .join(path)
+            .unwrap()
+    };
+    provider.fetch(
+        0,
+        Request::get(url("style.css"))
+            .with_destination(RequestDestination::Stylesheet)
+            .with_initiator(7),
+        Box::new(NoopHandler),
+    );
+    provider.fetch(0, Request::get(url("missing.png")), Box::new(NoopHandler));
+    provider.fetch(0, Request::get(url("missing.html")), Box::new(NoopHandler));
+
+    let requests = log.requests();
+    assert_eq!(requests.len(), 3);
+    assert_eq!(requests[0].url, url("style.css"));
+    assert_eq!(requests[0].destination, RequestDestination::Stylesheet);
+    assert_eq!(requests[0].initiator, Some(7));
+    assert_eq!(requests[0].status, NetworkRequestStatus::Complete);
+    assert_eq!(requests[0].status_code, None);
+    assert_eq!(requests[0].content_type.as_deref(), Some("text/css"));
+    assert_eq!(requests[0].size, Some(19));
+    assert!(requests[0].duration.is_some());
+    assert_eq!(requests[1].status, NetworkRequestStatus::Failed);
+    assert_eq!(requests[1].size, None);
+    assert_eq!(requests[2].status, NetworkRequestStatus::Complete);
+    assert_eq!(requests[2].status_code, Some(StatusCode::NOT_FOUND));
+    assert_eq!(requests[2].size, Some(9));
+
+    log.clear();
+    assert!(log.is_empty());
+}
@@ -4,6 +4,7 @@ use parley::{FontContext, LayoutContext};
 use selectors::matching::QuirksMode;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use style::Atom;
 use style::stylesheets::{DocumentStyleSheet, UrlExtraData};
 use style::{
@@ -43,6 +44,9 @@ pub struct ElementData {
 
     pub background_images: Vec<Option<BackgroundImageData>>,
 
+    /// The image source selected from the element's `src` and `srcset` attributes (\<img\> elements only)
+    pub image_source: Option<ImageSource>,
+
     /// Parley text layout (elements with inline inner display mode only)
     pub inline_layout_data: Option<Box<TextLayout>>,
 
@@ -52,6 +56,12 @@ pub struct ElementData {
 
     /// The element's template contents (\<template\> elements only)
     pub template_contents: Option<usize>,
+
+    /// The shadow root attached to the element (shadow hosts only)
+    pub shadow_root: Option<usize>,
+
+    /// The nodes assigned to the element (\<slot\> elements in shadow trees only)
+    pub assigned_nodes: Vec<usize>,
     // /// Whether the node is a [HTML integration point] (https://html.spec.whatwg.org/multipage/#html-integration-point)
     // pub mathml_annotation_xml_integration_point: bool,
 }
@@ -111,7 +121,10 @@ impl ElementData {
             list_item_data: None,
             special_data: SpecialElementData::None,
             template_contents: None,
+            shadow_root: None,
+            assigned_nodes: Vec::new(),
             background_images: Vec::new(),
+            image_source: None,
         };
         data.flush_is_focussable();
         data
@@ -151,15 +164,13 @@ impl ElementData {
     }
 
     pub fn raster_image_data(&self) -> Option<&RasterImageData> {
-        match self.image_data()? {
-            ImageData::Raster(data) => Some(data),
-            _ => None,
-        }
+        self.image_data()?.raster()
     }
 
     pub fn raster_image_data_mut(&mut self) -> Option<&mut RasterImageData> {
         match self.image_data_mut()? {
             ImageData::Raster(data) => Some(data),
+            ImageData::Animated(data) => Some(&mut data.frames[data.current_frame].image),
             _ => None,
         }
     }
@@ -182,7 +193,7 @@ impl ElementData {
     #[cfg(feature = "svg")]
     pub fn svg_data_mut(&mut self) -> Option<&mut usvg::Tree> {
         match self.image_data_mut()? {
-            ImageData::Svg(data) => Some(data),
+            ImageData::Svg(data) => Some(Arc::make_mut(data)),
             _ => None,
         }
     }
@@ -263,6 +274,16 @@ impl ElementData {
     }
 }
 
+/// The source of an \<img\> element's image, selected from its `src` and `srcset` attributes or
+/// from a `<source>` element in a parent \<picture\> element.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageSource {
+    /// The URL of the image
+    pub url: Url,
+    /// The pixel density of the image. The image's natural size is divided by this value.
+    pub density: f32,
+}
+
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct RasterImageData {
     /// The width of the image
@@ -282,17 +303,95 @@ impl RasterImageData {
     }
 }
 
+/// A single frame of an [`AnimatedImageData`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageFrame {
+    /// The (fully composited) image data of the frame
+    pub image: RasterImageData,
+    /// How long the frame is displayed for
+    pub delay: Duration,
+}
+
+/// An animated raster image (an animated GIF, APNG or WebP)
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedImageData {
+    /// The frames of the animation. Always contains at least one frame.
+    pub frames: Vec<ImageFrame>,
+    /// The document time at which the animation started
+    pub start_time: Duration,
+    /// The index of the frame which is currently displayed
+    pub current_frame: usize,
+}
+impl AnimatedImageData {
+    pub fn new(frames: Vec<ImageFrame>, start_time: Duration) -> Self {
+        assert!(
+            !frames.is_empty(),
+            "Animated images must have at least one frame"
+        );
+        Self {
+            frames,
+            start_time,
+            current_frame: 0,
+        }
+    }
+
+    /// The frame which is currently displayed
+    pub fn current_image(&self) -> &RasterImageData {
+        &self.frames[self.current_frame].image
+    }
+
+    /// The duration of a single loop of the animation
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|frame| frame.delay).sum()
+    }
+
+    /// Select the frame to display at the document time `time`. The animation loops forever.
+    ///
+    /// Returns `true` if the current frame changed.
+    pub fn advance_to(&mut self, time: Duration) -> bool {
+        let duration = self.duration().as_nanos();
+        if duration == 0 {
+            return false;
+        }
+
+        let mut offset = time.saturating_sub(self.start_time).as_nanos() % duration;
+        let mut frame_idx = 0;
+        for (idx, frame) in self.frames.iter().enumerate() {
+            frame_idx = idx;
+            if offset < frame.delay.as_nanos() {
+                break;
+            }
+            offset -= frame.delay.as_nanos();
+        }
+
+        let changed = frame_idx != self.current_frame;
+        self.current_frame = frame_idx;
+        changed
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum ImageData {
     Raster(RasterImageData),
+    Animated(AnimatedImageData),
     #[cfg(feature = "svg")]
-    Svg(Box<usvg::Tree>),
+    Svg(Arc<usvg::Tree>),
     None,
 }
+impl ImageData {
+    /// The raster image to display (the current frame for animated images)
+    pub fn raster(&self) -> Option<&RasterImageData> {
+        match self {
+            ImageData::Raster(data) => Some(data),
+            ImageData::Animated(data) => Some(data.current_image()),
+            _ => None,
+        }
+    }
+}
 #[cfg(feature = "svg")]
 impl From<usvg::Tree> for ImageData {
     fn from(value: usvg::Tree) -> Self {
-        Self::Svg(Box::new(value))
+        Self::Svg(Arc::new(value))
     }
 }
 
@@ -370,6 +469,7 @@ impl std::fmt::Debug for SpecialElementData {
             SpecialElementData::Stylesheet(_) => f.write_str("NodeSpecificData::Stylesheet"),
             SpecialElementData::Image(data) => match **data {
                 ImageData::Raster(_) => f.write_str("NodeSpecificData::Image(Raster)"),
+                ImageData::Animated(_) => f.write_str("NodeSpecificData::Image(Animated)"),
                 #[cfg(feature = "svg")]
                 ImageData::Svg(_) => f.write_str("NodeSpecificData::Image(Svg)"),
                 ImageData::None => f.write_str("NodeSpecificData::Image(None)"),
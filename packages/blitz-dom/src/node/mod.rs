@@ -6,8 +6,8 @@ mod node;
 
 pub use attributes::{Attribute, Attributes};
 pub use element::{
-    BackgroundImageData, CanvasData, ElementData, ImageData, ListItemLayout,
-    ListItemLayoutPosition, Marker, RasterImageData, SpecialElementData, SpecialElementType,
-    Status, TextBrush, TextInputData, TextLayout,
+    AnimatedImageData, BackgroundImageData, CanvasData, ElementData, ImageData, ImageFrame,
+    ImageSource, ListItemLayout, ListItemLayoutPosition, Marker, RasterImageData,
+    SpecialElementData, SpecialElementType, Status, TextBrush, TextInputData, TextLayout,
 };
 pub use node::*;
@@ -1,21 +1,22 @@
 use atomic_refcell::{AtomicRef, AtomicRefCell};
 use bitflags::bitflags;
-use blitz_traits::events::{BlitzMouseButtonEvent, DomEventData, HitResult};
+use blitz_traits::events::{BlitzMouseButtonEvent, DomEventData};
 use keyboard_types::Modifiers;
 use markup5ever::{LocalName, local_name};
-use parley::Cluster;
 use peniko::kurbo;
 use selectors::matching::ElementSelectorFlags;
 use slab::Slab;
 use std::cell::{Cell, RefCell};
 use std::fmt::Write;
 use std::sync::atomic::AtomicBool;
+use style::author_styles::AuthorStyles;
 use style::invalidation::element::restyle_hints::RestyleHint;
 use style::properties::ComputedValues;
 use style::properties::generated::longhands::position::computed_value::T as Position;
 use style::selector_parser::PseudoElement;
-use style::stylesheets::UrlExtraData;
-use style::values::computed::Display;
+use style::servo_arc::Arc as ServoArc;
+use style::stylesheets::{DocumentStyleSheet, UrlExtraData};
+use style::values::computed::{CSSPixelLength, Display};
 use style::values::specified::box_::{DisplayInside, DisplayOutside};
 use style::{data::ElementData as StyloElementData, shared_lock::SharedRwLock};
 use style_dom::ElementState;
@@ -43,6 +44,8 @@ bitflags! {
         const IS_TABLE_ROOT = 0b00000010;
         /// Whether the node is "in the document" (~= has a parent and isn't a template node)
         const IS_IN_DOCUMENT = 0b00000100;
+        /// Whether the node is a custom element with a Rust implementation (see [`BaseDocument::define_custom_element`](crate::BaseDocument::define_custom_element))
+        const IS_CUSTOM_ELEMENT = 0b00001000;
     }
 }
 
@@ -62,6 +65,11 @@ impl NodeFlags {
         self.contains(Self::IS_IN_DOCUMENT)
     }
 
+    #[inline(always)]
+    pub fn is_custom_element(&self) -> bool {
+        self.contains(Self::IS_CUSTOM_ELEMENT)
+    }
+
     #[inline(always)]
     pub fn reset_construction_flags(&mut self) {
         self.remove(Self::IS_INLINE_ROOT);
@@ -103,6 +111,9 @@ pub struct Node {
     pub before: Option<usize>,
     pub after: Option<usize>,
 
+    /// The `<slot>` the node is assigned to (children of shadow hosts only)
+    pub assigned_slot: Option<usize>,
+
     // Taffy layout data:
     pub style: Style,
     pub has_snapshot: bool,
@@ -142,6 +153,8 @@ impl Node {
             before: None,
             after: None,
 
+            assigned_slot: None,
+
             style: Default::default(),
             has_snapshot: false,
             snapshot_handled: AtomicBool::new(false),
@@ -196,7 +209,7 @@ impl Node {
             DisplayOutside::Block => true,
             _ => {
                 if display.inside() == DisplayInside::Flow {
-                    self.children
+                    self.flat_tree_children()
                         .iter()
                         .copied()
                         .any(|child_id| self.tree()[child_id].is_or_contains_block())
@@ -272,6 +285,7 @@ pub enum NodeKind {
     AnonymousBlock,
     Text,
     Comment,
+    ShadowRoot,
 }
 
 /// The different kinds of nodes in the DOM.
@@ -291,6 +305,9 @@ pub enum NodeData {
 
     /// A comment.
     Comment,
+
+    /// The root of a shadow tree attached to an element
+    ShadowRoot(ShadowRootData),
     // Comment { contents: String },
 
     // /// A `DOCTYPE` with name, public id, and system id. See
@@ -345,6 +362,7 @@ impl NodeData {
             NodeData::AnonymousBlock(_) => NodeKind::AnonymousBlock,
             NodeData::Text(_) => NodeKind::Text,
             NodeData::Comment => NodeKind::Comment,
+            NodeData::ShadowRoot(_) => NodeKind::ShadowRoot,
         }
     }
 }
@@ -361,6 +379,49 @@ impl TextNodeData {
     }
 }
 
+/// Whether a shadow root is open or closed. Blitz does not restrict access to closed shadow roots, but
+/// the mode is recorded for embedders which do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShadowRootMode {
+    Open,
+    Closed,
+}
+
+pub struct ShadowRootData {
+    /// The element the shadow root is attached to
+    pub host: usize,
+    pub mode: ShadowRootMode,
+    /// The stylesheets of the `<style>` and `<link>` elements in the shadow tree, and the cascade data built
+    /// from them (which only applies to the shadow tree)
+    pub(crate) author_styles: AuthorStyles<DocumentStyleSheet>,
+}
+
+impl ShadowRootData {
+    pub fn new(host: usize, mode: ShadowRootMode) -> Self {
+        Self {
+            host,
+            mode,
+            author_styles: AuthorStyles::new(),
+        }
+    }
+}
+
+/// Clones have no stylesheets (they are collected from the shadow tree when the document is next resolved)
+impl Clone for ShadowRootData {
+    fn clone(&self) -> Self {
+        Self::new(self.host, self.mode)
+    }
+}
+
+impl std::fmt::Debug for ShadowRootData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShadowRootData")
+            .field("host", &self.host)
+            .field("mode", &self.mode)
+            .finish()
+    }
+}
+
 /*
 -> Computed styles
 -> Layout
@@ -457,6 +518,10 @@ impl Node {
         matches!(self.data, NodeData::Text { .. })
     }
 
+    pub fn is_shadow_root(&self) -> bool {
+        matches!(self.data, NodeData::ShadowRoot { .. })
+    }
+
     pub fn element_data(&self) -> Option<&ElementData> {
         match self.data {
             NodeData::Element(ref data) => Some(data),
@@ -487,6 +552,29 @@ impl Node {
         }
     }
 
+    pub fn shadow_root_data(&self) -> Option<&ShadowRootData> {
+        match self.data {
+            NodeData::ShadowRoot(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    /// The node's children in the flat tree (the tree which is styled, laid out and painted). These are the
+    /// children of the node's shadow root for shadow hosts, the nodes assigned to the node for `<slot>` elements
+    /// with assigned nodes, and the node's own children otherwise.
+    pub fn flat_tree_children(&self) -> &[usize] {
+        let Some(element) = self.element_data() else {
+            return &self.children;
+        };
+        if let Some(shadow_root_id) = element.shadow_root {
+            return &self.with(shadow_root_id).children;
+        }
+        if !element.assigned_nodes.is_empty() {
+            return &element.assigned_nodes;
+        }
+        &self.children
+    }
+
     pub fn node_debug_str(&self) -> String {
         let mut s = String::new();
 
@@ -508,6 +596,7 @@ impl Node {
                 // &std::str::from_utf8(data.contents.as_bytes().split_at(10).0).unwrap_or("INVALID UTF8")
             ),
             NodeData::AnonymousBlock(_) => write!(s, "AnonymousBlock"),
+            NodeData::ShadowRoot(_) => write!(s, "SHADOW ROOT"),
             NodeData::Element(data) => {
                 let name = &data.name;
                 let class = self.attr(local_name!("class")).unwrap_or("");
@@ -543,6 +632,7 @@ impl Node {
             NodeData::Document => {}
             NodeData::Comment => {}
             NodeData::AnonymousBlock(_) => {}
+            NodeData::ShadowRoot(_) => {}
             // NodeData::Doctype { name, .. } => write!(s, "DOCTYPE {name}"),
             NodeData::Text(data) => {
                 writer.push_str(data.content.as_str());
@@ -612,6 +702,17 @@ impl Node {
         }
     }
 
+    /// The styles of the node's `::selection` pseudo-element (if any `::selection` rules match the node)
+    pub fn selection_styles(&self) -> Option<ServoArc<ComputedValues>> {
+        let stylo_element_data = self.stylo_element_data.borrow();
+        stylo_element_data
+            .as_ref()?
+            .styles
+            .pseudos
+            .get(&PseudoElement::Selection)
+            .cloned()
+    }
+
     pub fn text_content(&self) -> String {
         let mut out = String::new();
         self.write_text_content(&mut out);
@@ -654,73 +755,6 @@ impl Node {
             .unwrap_or(0)
     }
 
-    /// Takes an (x, y) position (relative to the *parent's* top-left corner) and returns:
-    ///    - None if the position is outside of this node's bounds
-    ///    - Some(HitResult) if the position is within the node but doesn't match any children
-    ///    - The result of recursively calling child.hit() on the the child element that is
-    ///      positioned at that position if there is one.
-    ///
-    /// TODO: z-index
-    /// (If multiple children are positioned at the position then a random one will be recursed into)
-    pub fn hit(&self, x: f32, y: f32) -> Option<HitResult> {
-        let mut x = x - self.final_layout.location.x + self.scroll_offset.x as f32;
-        let mut y = y - self.final_layout.location.y + self.scroll_offset.y as f32;
-
-        let size = self.final_layout.size;
-        let matches_self = !(x < 0.0
-            || x > size.width + self.scroll_offset.x as f32
-            || y < 0.0
-            || y > size.height + self.scroll_offset.y as f32);
-
-        let content_size = self.final_layout.content_size;
-        let matches_content = !(x < 0.0
-            || x > content_size.width + self.scroll_offset.x as f32
-            || y < 0.0
-            || y > content_size.height + self.scroll_offset.y as f32);
-
-        if !matches_self && !matches_content {
-            return None;
-        }
-
-        if self.flags.is_inline_root() {
-            let content_box_offset = taffy::Point {
-                x: self.final_layout.padding.left + self.final_layout.border.left,
-                y: self.final_layout.padding.top + self.final_layout.border.top,
-            };
-            x -= content_box_offset.x;
-            y -= content_box_offset.y;
-        }
-
-        // Call `.hit()` on each child in turn. If any return `Some` then return that value. Else return `Some(self.id).
-        self.paint_children
-            .borrow()
-            .iter()
-            .flatten()
-            .rev()
-            .find_map(|&i| self.with(i).hit(x, y))
-            .or_else(|| {
-                if self.flags.is_inline_root() {
-                    let element_data = &self.element_data().unwrap();
-                    let layout = &element_data.inline_layout_data.as_ref().unwrap().layout;
-                    let scale = layout.scale();
-
-                    Cluster::from_point(layout, x * scale, y * scale).and_then(|(cluster, _)| {
-                        let style_index = cluster.glyphs().next()?.style_index();
-                        let node_id = layout.styles()[style_index].brush.id;
-                        Some(HitResult { node_id, x, y })
-                    })
-                } else {
-                    None
-                }
-            })
-            .or(Some(HitResult {
-                node_id: self.id,
-                x,
-                y,
-            })
-            .filter(|_| matches_self))
-    }
-
     /// Computes the Document-relative coordinates of the Node
     pub fn absolute_position(&self, x: f32, y: f32) -> taffy::Point<f32> {
         let x = x + self.final_layout.location.x - self.scroll_offset.x as f32;
@@ -733,6 +767,41 @@ impl Node {
             .unwrap_or(taffy::Point { x, y })
     }
 
+    /// The node's 2D CSS transform (including its `transform-origin`) in CSS pixels relative to the top-left corner
+    /// of its border box. Returns `None` if the node has no transform or if the transform is 3D.
+    pub fn css_transform(&self) -> Option<kurbo::Affine> {
+        let styles = self.primary_styles()?;
+        let box_styles = styles.get_box();
+        if box_styles.transform.0.is_empty() {
+            return None;
+        }
+
+        let size = self.final_layout.size;
+        let width = CSSPixelLength::new(size.width);
+        let height = CSSPixelLength::new(size.height);
+        let zero = CSSPixelLength::new(0.0);
+        let reference_box = euclid::default::Rect::new(
+            euclid::default::Point2D::new(zero, zero),
+            euclid::default::Size2D::new(width, height),
+        );
+        let (t, has_3d) = box_styles
+            .transform
+            .to_transform_3d_matrix(Some(&reference_box))
+            .ok()?;
+        if has_3d {
+            return None;
+        }
+
+        // See: https://drafts.csswg.org/css-transforms-2/#two-dimensional-subset
+        let transform =
+            kurbo::Affine::new([t.m11, t.m12, t.m21, t.m22, t.m41, t.m42].map(f64::from));
+        let origin = kurbo::Affine::translate((
+            box_styles.transform_origin.horizontal.resolve(width).px() as f64,
+            box_styles.transform_origin.vertical.resolve(height).px() as f64,
+        ));
+        Some(origin * transform * origin.inverse())
+    }
+
     /// Creates a synthetic click event
     pub fn synthetic_click_event(&self, mods: Modifiers) -> DomEventData {
         DomEventData::Click(self.synthetic_click_event_data(mods))
@@ -0,0 +1,281 @@
+//! Restrictions on which resources a document may load
+
+use crate::net::Resource;
+use blitz_traits::net::{BoxedHandler, NetProvider, Request, RequestDestination, SharedProvider};
+use std::collections::HashMap;
+use std::sync::Arc;
+use url::{Origin, Url};
+
+/// Restrictions on the schemes and origins of resources that may be loaded for a particular [`RequestDestination`]
+#[derive(Debug, Clone, Default)]
+pub struct ResourceRule {
+    /// Schemes which may be loaded. `None` allows all schemes.
+    schemes: Option<Vec<String>>,
+    /// Origins which may be loaded. `None` allows all origins.
+    origins: Option<Vec<Origin>>,
+    /// Whether the document's own origin may be loaded (only relevant if `origins` is set)
+    allow_same_origin: bool,
+}
+
+impl ResourceRule {
+    /// A rule which allows all resources
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// A rule which blocks all resources
+    pub fn deny_all() -> Self {
+        Self {
+            schemes: Some(Vec::new()),
+            origins: None,
+            allow_same_origin: false,
+        }
+    }
+
+    /// A rule which only allows resources from the same origin as the document
+    pub fn same_origin() -> Self {
+        Self {
+            schemes: None,
+            origins: Some(Vec::new()),
+            allow_same_origin: true,
+        }
+    }
+
+    /// Only allow resources with the specified schemes (e.g. `["https", "data"]`)
+    pub fn with_schemes<S: Into<String>>(mut self, schemes: impl IntoIterator<Item = S>) -> Self {
+        let schemes = schemes
+            .into_iter()
+            .map(|scheme| scheme.into().to_ascii_lowercase());
+        self.schemes.get_or_insert_with(Vec::new).extend(schemes);
+        self
+    }
+
+    /// Only allow resources from the specified origins (e.g. `["https://fonts.gstatic.com"]`).
+    ///
+    /// Origins are only checked for URLs which have a host (such as `http` and `https` URLs). Other URLs (such
+    /// as `data:` and `file:` URLs) have opaque origins and are only restricted by [`with_schemes`](Self::with_schemes).
+    /// Values which cannot be parsed as URLs are ignored.
+    pub fn with_origins<'a>(mut self, origins: impl IntoIterator<Item = &'a str>) -> Self {
+        let origins = origins
+            .into_iter()
+            .filter_map(|origin| Url::parse(origin).ok())
+            .map(|url| url.origin());
+        self.origins.get_or_insert_with(Vec::new).extend(origins);
+        self
+    }
+
+    fn check(&self, document_url: &Url, url: &Url) -> Result<(), ViolationReason> {
+        let scheme_allowed = self
+            .schemes
+            .as_ref()
+            .is_none_or(|schemes| schemes.iter().any(|scheme| scheme == url.scheme()));
+        if !scheme_allowed {
+            return Err(ViolationReason::SchemeNotAllowed);
+        }
+
+        let origin = url.origin();
+        if let (Some(origins), true) = (&self.origins, origin.is_tuple()) {
+            let is_same_origin = self.allow_same_origin && origin == document_url.origin();
+            if !is_same_origin && !origins.contains(&origin) {
+                return Err(ViolationReason::OriginNotAllowed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The reason a resource was blocked by a [`ResourcePolicy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationReason {
+    /// The URL's scheme is not allowed for this kind of resource
+    SchemeNotAllowed,
+    /// The URL's origin is not allowed for this kind of resource
+    OriginNotAllowed,
+    /// An insecure (`http`) resource was requested by a secure (`https`) document
+    MixedContent,
+}
+
+/// A resource load that was blocked by a [`ResourcePolicy`]
+#[derive(Debug, Clone)]
+pub struct PolicyViolation {
+    /// The URL of the blocked resource
+    pub url: Url,
+    /// The URL of the document that requested the resource
+    pub document_url: Url,
+    /// The kind of resource that was blocked
+    pub destination: RequestDestination,
+    /// Why the resource was blocked
+    pub reason: ViolationReason,
+}
+
+type ViolationCallback = Arc<dyn Fn(&PolicyViolation) + Send + Sync>;
+
+/// A policy controlling which resources (stylesheets, images, fonts, navigations, etc) a document may load.
+///
+/// By default all resources are allowed. Restrictions can be added per [`RequestDestination`] using [`ResourceRule`]s:
+///
+/// ```rust
+/// # use blitz_dom::{ResourcePolicy, ResourceRule};
+/// # use blitz_traits::net::RequestDestination;
+/// let policy = ResourcePolicy::new()
+///     .with_default_rule(ResourceRule::allow_all().with_schemes(["http", "https", "data"]))
+///     .with_rule(RequestDestination::Font, ResourceRule::same_origin())
+///     .with_block_mixed_content(true);
+/// ```
+#[derive(Clone, Default)]
+pub struct ResourcePolicy {
+    default_rule: ResourceRule,
+    rules: HashMap<RequestDestination, ResourceRule>,
+    block_mixed_content: bool,
+    on_violation: Option<ViolationCallback>,
+}
+
+impl ResourcePolicy {
+    /// A policy which allows all resources
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the rule used for kinds of resource which do not have a specific rule
+    pub fn with_default_rule(mut self, rule: ResourceRule) -> Self {
+        self.default_rule = rule;
+        self
+    }
+
+    /// Set the rule for a specific kind of resource
+    pub fn with_rule(mut self, destination: RequestDestination, rule: ResourceRule) -> Self {
+        self.rules.insert(destination, rule);
+        self
+    }
+
+    /// Block insecure (`http`) subresources in secure (`https`) documents
+    pub fn with_block_mixed_content(mut self, block: bool) -> Self {
+        self.block_mixed_content = block;
+        self
+    }
+
+    /// Set a callback which is called whenever a resource is blocked
+    pub fn on_violation(
+        mut self,
+        callback: impl Fn(&PolicyViolation) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_violation = Some(Arc::new(callback));
+        self
+    }
+
+    /// Check whether a resource may be loaded by a document, reporting a violation if it may not.
+    pub fn check(&self, document_url: &Url, url: &Url, destination: RequestDestination) -> bool {
+        let Err(reason) = self.check_inner(document_url, url, destination) else {
+            return true;
+        };
+
+        let violation = PolicyViolation {
+            url: url.clone(),
+            document_url: document_url.clone(),
+            destination,
+            reason,
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            "Blocked {:?} load of {} ({:?})",
+            violation.destination,
+            violation.url,
+            violation.reason
+        );
+
+        if let Some(on_violation) = &self.on_violation {
+            on_violation(&violation);
+        }
+
+        false
+    }
+
+    fn check_inner(
+        &self,
+        document_url: &Url,
+        url: &Url,
+        destination: RequestDestination,
+    ) -> Result<(), ViolationReason> {
+        let is_subresource = destination != RequestDestination::Document;
+        if self.block_mixed_content
+            && is_subresource
+            && document_url.scheme() == "https"
+            && matches!(url.scheme(), "http" | "ws")
+        {
+            return Err(ViolationReason::MixedContent);
+        }
+
+        self.rules
+            .get(&destination)
+            .unwrap_or(&self.default_rule)
+            .check(document_url, url)
+    }
+}
+
+/// A [`NetProvider`] which checks requests against a document's [`ResourcePolicy`]
+/// before forwarding them to the underlying provider.
+pub(crate) struct PolicyNetProvider {
+    pub(crate) inner: SharedProvider<Resource>,
+    pub(crate) policy: Arc<ResourcePolicy>,
+    pub(crate) document_url: Url,
+}
+
+impl NetProvider<Resource> for PolicyNetProvider {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        if self
+            .policy
+            .check(&self.document_url, &request.url, request.destination)
+        {
+            self.inner.fetch(doc_id, request, handler);
+        }
+    }
+}
+
+#[test]
+fn blocks_local_files_and_mixed_content() {
+    let policy = ResourcePolicy::new()
+        .with_default_rule(ResourceRule::allow_all().with_schemes(["http", "https", "data"]))
+        .with_block_mixed_content(true);
+    let doc_url = Url::parse("https://example.com/index.html").unwrap();
+    let check =
+        |url: &str, destination| policy.check(&doc_url, &Url::parse(url).unwrap(), destination);
+
+    assert!(check(
+        "https://example.com/style.css",
+        RequestDestination::Stylesheet
+    ));
+    assert!(check(
+        "data:text/css,body{}",
+        RequestDestination::Stylesheet
+    ));
+    assert!(!check("file:///etc/passwd", RequestDestination::Image));
+    assert!(!check(
+        "http://example.com/img.png",
+        RequestDestination::Image
+    ));
+    assert!(check("http://example.com/", RequestDestination::Document));
+}
+
+#[test]
+fn restricts_origins() {
+    let policy = ResourcePolicy::new().with_rule(
+        RequestDestination::Font,
+        ResourceRule::same_origin().with_origins(["https://fonts.gstatic.com"]),
+    );
+    let doc_url = Url::parse("https://example.com/index.html").unwrap();
+    let check =
+        |url: &str, destination| policy.check(&doc_url, &Url::parse(url).unwrap(), destination);
+
+    assert!(check(
+        "https://example.com/a.woff2",
+        RequestDestination::Font
+    ));
+    assert!(check(
+        "https://fonts.gstatic.com/a.woff2",
+        RequestDestination::Font
+    ));
+    assert!(!check("https://evil.com/a.woff2", RequestDestination::Font));
+    assert!(check("https://evil.com/a.png", RequestDestination::Image));
+}
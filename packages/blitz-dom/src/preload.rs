@@ -0,0 +1,594 @@
+//! Fetching of resources ahead of time for `<link rel="preload">` and `<link rel="prefetch">`
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use blitz_traits::net::{
+    BoxedHandler, Bytes, Method, NetHandler, NetProvider, Request, RequestDestination,
+    ResponseHead, SharedCallback, SharedProvider,
+};
+use markup5ever::local_name;
+use url::Url;
+
+use crate::net::{Resource, ResourceTracker, warm_resource_cache};
+use crate::srcset::is_supported_image_type;
+use crate::{BaseDocument, DocumentReadyState, ImageDecoder, ResourceCache};
+
+enum Preload {
+    /// The resource is still being fetched. Requests for the resource wait for the preload to complete.
+    Pending(Vec<(usize, Request, BoxedHandler<Resource>)>),
+    /// The resource has been fetched, but has not been requested yet
+    Complete {
+        response: PreloadedResponse,
+        callback: SharedCallback<Resource>,
+    },
+}
+
+/// A preloaded response, with its status and headers if the `NetProvider` passed them to the handler
+#[derive(Clone)]
+struct PreloadedResponse {
+    bytes: Bytes,
+    content_type: Option<String>,
+    head: Option<ResponseHead>,
+}
+
+impl PreloadedResponse {
+    fn send_to(
+        self,
+        handler: BoxedHandler<Resource>,
+        doc_id: usize,
+        callback: SharedCallback<Resource>,
+    ) {
+        match &self.head {
+            Some(head) => handler.bytes_with_head(doc_id, self.bytes, head, callback),
+            None => handler.bytes_with_content_type(
+                doc_id,
+                self.bytes,
+                self.content_type.as_deref(),
+                callback,
+            ),
+        }
+    }
+}
+
+/// The maximum total size of the preloaded responses which are kept until they are requested. Once it is
+/// exceeded the oldest responses are discarded (and fetched again if they are requested later).
+const MAX_UNUSED_PRELOAD_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Default)]
+struct Preloads {
+    entries: HashMap<Url, Preload>,
+    /// The URLs of the `Complete` entries, oldest first
+    unused: VecDeque<Url>,
+    /// The total size of the responses of the `Complete` entries
+    unused_size: usize,
+}
+
+impl Preloads {
+    /// Store a preloaded response until it is requested, discarding the oldest unused responses if they
+    /// would exceed [`MAX_UNUSED_PRELOAD_SIZE`]
+    fn insert_complete(
+        &mut self,
+        url: Url,
+        response: PreloadedResponse,
+        callback: SharedCallback<Resource>,
+    ) {
+        self.unused_size += response.bytes.len();
+        self.unused.push_back(url.clone());
+        self.entries
+            .insert(url, Preload::Complete { response, callback });
+        while self.unused_size > MAX_UNUSED_PRELOAD_SIZE {
+            let Some(oldest) = self.unused.front().cloned() else {
+                break;
+            };
+            self.take_complete(&oldest);
+        }
+    }
+
+    /// Remove a preloaded response which has not been requested yet
+    fn take_complete(
+        &mut self,
+        url: &Url,
+    ) -> Option<(PreloadedResponse, SharedCallback<Resource>)> {
+        if !matches!(self.entries.get(url), Some(Preload::Complete { .. })) {
+            return None;
+        }
+        let Some(Preload::Complete { response, callback }) = self.entries.remove(url) else {
+            unreachable!();
+        };
+        self.unused.retain(|unused| unused != url);
+        self.unused_size -= response.bytes.len();
+        Some((response, callback))
+    }
+}
+
+/// A [`NetProvider`] which serves requests for preloaded resources from the preloaded response (waiting
+/// for it if the preload is still in flight) rather than fetching them a second time.
+///
+/// Preloaded responses are matched by URL, and each one is only used for a single request. Responses which
+/// are never requested are only kept up to a total of [`MAX_UNUSED_PRELOAD_SIZE`] bytes.
+#[derive(Clone)]
+pub(crate) struct PreloadNetProvider {
+    inner: SharedProvider<Resource>,
+    preloads: Arc<Mutex<Preloads>>,
+}
+
+impl PreloadNetProvider {
+    pub(crate) fn new(inner: SharedProvider<Resource>) -> Self {
+        Self {
+            inner,
+            preloads: Arc::default(),
+        }
+    }
+
+    /// Create a provider which shares this provider's preloads, but fetches resources using `inner`
+    pub(crate) fn with_inner(&self, inner: SharedProvider<Resource>) -> Self {
+        Self {
+            inner,
+            preloads: Arc::clone(&self.preloads),
+        }
+    }
+
+    /// Start fetching a resource ahead of time (unless it is already being preloaded).
+    ///
+    /// If a `tracker` is passed then the preload is counted as a pending resource until it completes. If
+    /// a `cache` is passed then preloaded fonts and images are decoded into it as soon as they are fetched.
+    pub(crate) fn preload(
+        &self,
+        doc_id: usize,
+        request: Request,
+        tracker: Option<ResourceTracker>,
+        cache: Option<(ResourceCache, ImageDecoder)>,
+    ) {
+        let url = request.url.clone();
+        match self.preloads.lock().unwrap().entries.entry(url.clone()) {
+            Entry::Occupied(_) => return,
+            Entry::Vacant(entry) => {
+                entry.insert(Preload::Pending(Vec::new()));
+            }
+        }
+
+        if let Some(tracker) = &tracker {
+            tracker.increment();
+        }
+        let handler = PreloadHandler {
+            url,
+            destination: request.destination,
+            provider: self.clone(),
+            tracker,
+            cache,
+        };
+        self.inner.fetch(doc_id, request, Box::new(handler));
+    }
+}
+
+impl NetProvider<Resource> for PreloadNetProvider {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<Resource>) {
+        if request.method != Method::GET {
+            return self.inner.fetch(doc_id, request, handler);
+        }
+
+        let mut preloads = self.preloads.lock().unwrap();
+        if let Some(Preload::Pending(waiting)) = preloads.entries.get_mut(&request.url) {
+            waiting.push((doc_id, request, handler));
+            return;
+        }
+        if let Some((response, callback)) = preloads.take_complete(&request.url) {
+            drop(preloads);
+            response.send_to(handler, doc_id, callback);
+            return;
+        }
+        drop(preloads);
+
+        self.inner.fetch(doc_id, request, handler);
+    }
+}
+
+/// Stores the response to a preload, or passes it to the requests which are waiting for it
+struct PreloadHandler {
+    url: Url,
+    destination: RequestDestination,
+    provider: PreloadNetProvider,
+    tracker: Option<ResourceTracker>,
+    cache: Option<(ResourceCache, ImageDecoder)>,
+}
+
+impl PreloadHandler {
+    fn complete(
+        self,
+        doc_id: usize,
+        response: PreloadedResponse,
+        callback: SharedCallback<Resource>,
+    ) {
+        let mut preloads = self.provider.preloads.lock().unwrap();
+        let Some(Preload::Pending(waiting)) = preloads.entries.remove(&self.url) else {
+            return;
+        };
+
+        if !waiting.is_empty() {
+            drop(preloads);
+            for (doc_id, _, handler) in waiting {
+                response.clone().send_to(handler, doc_id, callback.clone());
+            }
+            return;
+        }
+
+        preloads.insert_complete(self.url.clone(), response.clone(), callback);
+        drop(preloads);
+        if let Some((cache, decoder)) = &self.cache {
+            let PreloadedResponse { bytes, head, .. } = response;
+            warm_resource_cache(
+                doc_id,
+                self.destination,
+                &self.url,
+                bytes,
+                head.as_ref(),
+                cache,
+                decoder,
+            );
+        }
+    }
+}
+
+impl NetHandler<Resource> for PreloadHandler {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Resource>) {
+        self.bytes_with_content_type(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_content_type(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<Resource>,
+    ) {
+        let response = PreloadedResponse {
+            bytes,
+            content_type: content_type.map(String::from),
+            head: None,
+        };
+        self.complete(doc_id, response, callback);
+    }
+
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Resource>,
+    ) {
+        let response = PreloadedResponse {
+            bytes,
+            content_type: head.content_type().map(String::from),
+            head: Some(head.clone()),
+        };
+        self.complete(doc_id, response, callback);
+    }
+
+    fn failed(
+        self: Box<Self>,
+        _doc_id: usize,
+        _error: String,
+        _callback: SharedCallback<Resource>,
+    ) {
+        // Dropping the handler fetches the resource for the requests which were waiting for it
+    }
+}
+
+impl Drop for PreloadHandler {
+    fn drop(&mut self) {
+        if let Some(tracker) = &self.tracker {
+            tracker.decrement();
+        }
+
+        // If the preload failed then fetch the resource for the requests which were waiting for it
+        let mut preloads = self.provider.preloads.lock().unwrap();
+        if !matches!(preloads.entries.get(&self.url), Some(Preload::Pending(_))) {
+            return;
+        }
+        let Some(Preload::Pending(waiting)) = preloads.entries.remove(&self.url) else {
+            unreachable!();
+        };
+        drop(preloads);
+        for (doc_id, request, handler) in waiting {
+            self.provider.inner.fetch(doc_id, request, handler);
+        }
+    }
+}
+
+/// The destination of a `<link rel="preload">` from its `as` attribute. Only destinations which
+/// are loaded by Blitz are supported (e.g. scripts are not).
+fn preload_destination(value: &str) -> Option<RequestDestination> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "font" => Some(RequestDestination::Font),
+        "image" => Some(RequestDestination::Image),
+        "style" => Some(RequestDestination::Stylesheet),
+        "fetch" => Some(RequestDestination::Other),
+        _ => None,
+    }
+}
+
+impl BaseDocument {
+    /// Fetch the resource linked by a `<link rel="preload">` or `<link rel="prefetch">` ahead of time.
+    ///
+    /// Preloads start immediately, and delay the document's `load` event. Prefetches are for later navigations,
+    /// so they are decoded into the document's [`ResourceCache`] (which can be shared with the documents that are
+    /// navigated to). They are only made for images and fonts (as given by the `as` attribute) when the document
+    /// has a cache, and don't start until the document has loaded. `modulepreload` (there is no script support),
+    /// and `preconnect` and `dns-prefetch` (connections are managed by the `NetProvider`) are recognised but have no effect.
+    pub(crate) fn preload_link(&mut self, node_id: usize) {
+        let Some(element) = self.nodes[node_id].element_data() else {
+            return;
+        };
+        let (Some(rels), Some(href)) = (
+            element.attr(local_name!("rel")),
+            element.attr(local_name!("href")),
+        ) else {
+            return;
+        };
+        if element
+            .attr(local_name!("media"))
+            .is_some_and(|media| !self.evaluate_media_query(media))
+        {
+            return;
+        }
+        let Some(url) = self.url.resolve_relative(href.trim()) else {
+            return;
+        };
+
+        let mime_type = element.attr(local_name!("type"));
+        let destination = element
+            .attr(local_name!("as"))
+            .and_then(preload_destination)
+            .filter(|destination| {
+                *destination != RequestDestination::Image
+                    || mime_type.is_none_or(is_supported_image_type)
+            });
+        let mut preload = None;
+        let mut prefetch = None;
+        for rel in rels.split_ascii_whitespace() {
+            if rel.eq_ignore_ascii_case("preload") {
+                preload = destination;
+            } else if rel.eq_ignore_ascii_case("prefetch") {
+                prefetch = destination.filter(|destination| {
+                    matches!(
+                        destination,
+                        RequestDestination::Image | RequestDestination::Font
+                    )
+                });
+            }
+        }
+
+        if let Some(destination) = preload {
+            let cache = self
+                .resource_cache
+                .clone()
+                .map(|cache| (cache, self.image_decoder.clone()));
+            self.preload_net_provider.preload(
+                self.id(),
+                Request::get(url)
+                    .with_destination(destination)
+                    .with_initiator(node_id),
+                Some(self.resource_tracker.clone()),
+                cache,
+            );
+        } else if let (Some(destination), Some(_)) = (prefetch, &self.resource_cache) {
+            let request = Request::get(url)
+                .with_destination(destination)
+                .with_initiator(node_id);
+            self.pending_prefetches.push(request);
+            if self.ready_state == DocumentReadyState::Complete {
+                self.start_prefetches();
+            }
+        }
+    }
+
+    /// Start fetching the resources from `<link rel="prefetch">` elements into the document's resource cache
+    pub(crate) fn start_prefetches(&mut self) {
+        let Some(cache) = &self.resource_cache else {
+            return;
+        };
+        for request in std::mem::take(&mut self.pending_prefetches) {
+            let handler = PrefetchHandler {
+                url: request.url.clone(),
+                destination: request.destination,
+                cache: cache.clone(),
+                decoder: self.image_decoder.clone(),
+            };
+            let request = cache.revalidate(request);
+            self.preload_net_provider
+                .fetch(self.id(), request, Box::new(handler));
+        }
+    }
+}
+
+/// Decodes a prefetched resource into a [`ResourceCache`] (without loading it into the document)
+struct PrefetchHandler {
+    url: Url,
+    destination: RequestDestination,
+    cache: ResourceCache,
+    decoder: ImageDecoder,
+}
+
+impl NetHandler<Resource> for PrefetchHandler {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, _callback: SharedCallback<Resource>) {
+        warm_resource_cache(
+            doc_id,
+            self.destination,
+            &self.url,
+            bytes,
+            None,
+            &self.cache,
+            &self.decoder,
+        );
+    }
+
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        _callback: SharedCallback<Resource>,
+    ) {
+        warm_resource_cache(
+            doc_id,
+            self.destination,
+            &self.url,
+            bytes,
+            Some(head),
+            &self.cache,
+            &self.decoder,
+        );
+    }
+
+    fn failed(
+        self: Box<Self>,
+        _doc_id: usize,
+        _error: String,
+        _callback: SharedCallback<Resource>,
+    ) {
+    }
+}
+
+#[test]
+fn serves_requests_from_preloaded_responses() {
+    use blitz_traits::net::DummyNetCallback;
+    use std::sync::mpsc;
+
+    // A provider which holds on to requests so that they can be responded to later
+    #[derive(Default)]
+    struct DeferredProvider(Mutex<Vec<BoxedHandler<Resource>>>);
+    impl NetProvider<Resource> for DeferredProvider {
+        fn fetch(&self, _doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            self.0.lock().unwrap().push(handler);
+        }
+    }
+    struct SendingHandler(mpsc::Sender<Bytes>);
+    impl NetHandler<Resource> for SendingHandler {
+        fn bytes(
+            self: Box<Self>,
+            _doc_id: usize,
+            bytes: Bytes,
+            _callback: SharedCallback<Resource>,
+        ) {
+            self.0.send(bytes).unwrap();
+        }
+    }
+
+    let inner = Arc::new(DeferredProvider::default());
+    let provider = PreloadNetProvider::new(inner.clone());
+    let url = Url::parse("https://example.com/font.woff2").unwrap();
+    let (send, recv) = mpsc::channel();
+
+    // A request made while the preload is in flight waits for it
+    provider.preload(0, Request::get(url.clone()), None, None);
+    provider.preload(0, Request::get(url.clone()), None, None);
+    provider.fetch(
+        0,
+        Request::get(url.clone()),
+        Box::new(SendingHandler(send.clone())),
+    );
+    assert_eq!(inner.0.lock().unwrap().len(), 1);
+
+    let preload = inner.0.lock().unwrap().pop().unwrap();
+    preload.bytes(0, Bytes::from_static(b"font"), Arc::new(DummyNetCallback));
+    assert_eq!(recv.try_recv().unwrap(), Bytes::from_static(b"font"));
+
+    // A request made after the preload has completed uses the stored response (but only once)
+    provider.preload(0, Request::get(url.clone()), None, None);
+    let preload = inner.0.lock().unwrap().pop().unwrap();
+    preload.bytes(0, Bytes::from_static(b"font"), Arc::new(DummyNetCallback));
+    provider.fetch(
+        0,
+        Request::get(url.clone()),
+        Box::new(SendingHandler(send.clone())),
+    );
+    assert_eq!(recv.try_recv().unwrap(), Bytes::from_static(b"font"));
+    assert!(inner.0.lock().unwrap().is_empty());
+
+    provider.fetch(0, Request::get(url), Box::new(SendingHandler(send)));
+    assert_eq!(inner.0.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn discards_oldest_unused_preloads() {
+    use blitz_traits::net::DummyNetCallback;
+
+    struct NoopProvider;
+    impl NetProvider<Resource> for NoopProvider {
+        fn fetch(&self, doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            let bytes = Bytes::from(vec![0; MAX_UNUSED_PRELOAD_SIZE / 2]);
+            handler.bytes(doc_id, bytes, Arc::new(DummyNetCallback));
+        }
+    }
+
+    let provider = PreloadNetProvider::new(Arc::new(NoopProvider));
+    let urls: Vec<Url> = (0..3)
+        .map(|i| Url::parse(&format!("https://example.com/{i}")).unwrap())
+        .collect();
+    provider.preload(0, Request::get(urls[0].clone()), None, None);
+    provider.preload(0, Request::get(urls[1].clone()), None, None);
+    let is_stored = |url: &Url| provider.preloads.lock().unwrap().entries.contains_key(url);
+    assert!(is_stored(&urls[0]) && is_stored(&urls[1]));
+
+    provider.preload(0, Request::get(urls[2].clone()), None, None);
+    assert!(!is_stored(&urls[0]));
+    assert!(is_stored(&urls[1]) && is_stored(&urls[2]));
+    assert_eq!(
+        provider.preloads.lock().unwrap().unused_size,
+        MAX_UNUSED_PRELOAD_SIZE
+    );
+}
+
+#[test]
+fn prefetches_into_the_resource_cache() {
+    use blitz_traits::net::http::HeaderValue;
+    use blitz_traits::net::http::header::{ETAG, IF_NONE_MATCH};
+    use blitz_traits::net::{DummyNetCallback, HeaderMap, StatusCode};
+    use markup5ever::{QualName, ns};
+
+    // A provider which serves the same font (with an ETag) for every request
+    struct FontProvider;
+    impl NetProvider<Resource> for FontProvider {
+        fn fetch(&self, doc_id: usize, _request: Request, handler: BoxedHandler<Resource>) {
+            let mut headers = HeaderMap::new();
+            headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+            let head = ResponseHead::new(StatusCode::OK, headers);
+            let font = Bytes::from_static(b"\x00\x01\x00\x00font");
+            handler.bytes_with_head(doc_id, font, &head, Arc::new(DummyNetCallback));
+        }
+    }
+
+    let cache = ResourceCache::default();
+    let mut doc = BaseDocument::new(crate::DocumentConfig {
+        net_provider: Some(Arc::new(FontProvider)),
+        resource_cache: Some(cache.clone()),
+        ..Default::default()
+    });
+    let attr = |name, value: &str| crate::Attribute {
+        name: QualName::new(None, ns!(), name),
+        value: value.to_string(),
+    };
+    let mut mutr = doc.mutate();
+    let link = mutr.create_element(
+        QualName::new(None, ns!(html), local_name!("link")),
+        vec![
+            attr(local_name!("rel"), "prefetch"),
+            attr(local_name!("as"), "font"),
+            attr(local_name!("href"), "https://example.com/font.ttf"),
+        ],
+    );
+    mutr.append_children(0, &[link]);
+    drop(mutr);
+
+    // Prefetches don't start until the document has loaded
+    assert!(cache.is_empty());
+    doc.mark_content_loaded();
+    assert_eq!(cache.len(), 1);
+
+    // Later requests for the resource (e.g. from another document sharing the cache) are revalidated
+    let url = Url::parse("https://example.com/font.ttf").unwrap();
+    let request = cache.revalidate(Request::get(url));
+    assert_eq!(request.headers[IF_NONE_MATCH], "\"v1\"");
+}
@@ -1,6 +1,8 @@
 use selectors::SelectorList;
 use smallvec::SmallVec;
-use style::dom_apis::{MayUseInvalidation, QueryAll, QueryFirst, query_selector};
+use style::dom_apis::{
+    MayUseInvalidation, QueryAll, QueryFirst, element_closest, element_matches, query_selector,
+};
 use style::selector_parser::{SelectorImpl, SelectorParser};
 use style_traits::ParseError;
 
@@ -22,10 +24,33 @@ impl BaseDocument {
 
     /// Find the first node that matches the selector(s) specified in selector_list
     pub fn query_selector_raw(&self, selector_list: &SelectorList<SelectorImpl>) -> Option<usize> {
-        let root_node = self.root_node();
+        self.query_selector_within_raw(self.root_node().id, selector_list)
+    }
+
+    /// Find the first descendant of a node that matches the selector specified as a string.
+    /// `:scope` matches the node (or the root element if the node is the document).
+    /// Returns:
+    ///   - Err(_) if parsing the selector fails
+    ///   - Ok(None) if no descendants match
+    ///   - Ok(Some(node_id)) with the first node ID that matches if one is found
+    pub fn query_selector_within<'input>(
+        &self,
+        node_id: usize,
+        selector: &'input str,
+    ) -> Result<Option<usize>, ParseError<'input>> {
+        let selector_list = self.try_parse_selector_list(selector)?;
+        Ok(self.query_selector_within_raw(node_id, &selector_list))
+    }
+
+    /// Find the first descendant of a node that matches the selector(s) specified in selector_list
+    pub fn query_selector_within_raw(
+        &self,
+        node_id: usize,
+        selector_list: &SelectorList<SelectorImpl>,
+    ) -> Option<usize> {
         let mut result = None;
         query_selector::<&Node, QueryFirst>(
-            root_node,
+            &self.nodes[node_id],
             selector_list,
             &mut result,
             MayUseInvalidation::Yes,
@@ -51,10 +76,32 @@ impl BaseDocument {
         &self,
         selector_list: &SelectorList<SelectorImpl>,
     ) -> SmallVec<[usize; 32]> {
-        let root_node = self.root_node();
+        self.query_selector_all_within_raw(self.root_node().id, selector_list)
+    }
+
+    /// Find all descendants of a node that match the selector specified as a string.
+    /// `:scope` matches the node (or the root element if the node is the document).
+    /// Returns:
+    ///   - `Err(_)` if parsing the selector fails
+    ///   - `Ok(SmallVec<usize>)` with all matching nodes otherwise
+    pub fn query_selector_all_within<'input>(
+        &self,
+        node_id: usize,
+        selector: &'input str,
+    ) -> Result<SmallVec<[usize; 32]>, ParseError<'input>> {
+        let selector_list = self.try_parse_selector_list(selector)?;
+        Ok(self.query_selector_all_within_raw(node_id, &selector_list))
+    }
+
+    /// Find all descendants of a node that match the selector(s) specified in selector_list
+    pub fn query_selector_all_within_raw(
+        &self,
+        node_id: usize,
+        selector_list: &SelectorList<SelectorImpl>,
+    ) -> SmallVec<[usize; 32]> {
         let mut results = SmallVec::new();
         query_selector::<&Node, QueryAll>(
-            root_node,
+            &self.nodes[node_id],
             selector_list,
             &mut results,
             MayUseInvalidation::Yes,
@@ -63,6 +110,55 @@ impl BaseDocument {
         results.iter().map(|node| node.id).collect()
     }
 
+    /// Whether a node is an element that matches the selector specified as a string.
+    /// `:scope` matches the node itself.
+    /// Returns:
+    ///   - `Err(_)` if parsing the selector fails
+    ///   - `Ok(bool)` otherwise
+    pub fn matches<'input>(
+        &self,
+        node_id: usize,
+        selector: &'input str,
+    ) -> Result<bool, ParseError<'input>> {
+        let selector_list = self.try_parse_selector_list(selector)?;
+        Ok(self.matches_raw(node_id, &selector_list))
+    }
+
+    /// Whether a node is an element that matches the selector(s) specified in selector_list
+    pub fn matches_raw(&self, node_id: usize, selector_list: &SelectorList<SelectorImpl>) -> bool {
+        let node = &self.nodes[node_id];
+        node.is_element() && element_matches(&node, selector_list, self.stylist.quirks_mode())
+    }
+
+    /// Find the closest inclusive ancestor of a node that matches the selector specified as a string.
+    /// The search starts from the node's parent element if the node is not an element (e.g. a text node),
+    /// and `:scope` matches the node that the search starts from.
+    /// Returns:
+    ///   - Err(_) if parsing the selector fails
+    ///   - Ok(None) if no ancestors match
+    ///   - Ok(Some(node_id)) with the closest matching ancestor otherwise
+    pub fn closest<'input>(
+        &self,
+        node_id: usize,
+        selector: &'input str,
+    ) -> Result<Option<usize>, ParseError<'input>> {
+        let selector_list = self.try_parse_selector_list(selector)?;
+        Ok(self.closest_raw(node_id, &selector_list))
+    }
+
+    /// Find the closest inclusive ancestor of a node that matches the selector(s) specified in selector_list
+    pub fn closest_raw(
+        &self,
+        node_id: usize,
+        selector_list: &SelectorList<SelectorImpl>,
+    ) -> Option<usize> {
+        let mut node = &self.nodes[node_id];
+        while !node.is_element() {
+            node = &self.nodes[node.parent?];
+        }
+        element_closest(node, selector_list, self.stylist.quirks_mode()).map(|node| node.id)
+    }
+
     pub fn try_parse_selector_list<'input>(
         &self,
         input: &'input str,
@@ -71,3 +167,73 @@ impl BaseDocument {
         SelectorParser::parse_author_origin_no_namespace(input, &url_extra_data)
     }
 }
+
+#[test]
+fn queries_selectors_relative_to_nodes() {
+    use crate::Attribute;
+    use markup5ever::{LocalName, QualName, local_name, ns};
+
+    let mut doc = BaseDocument::new(Default::default());
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let class = |value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("class")),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    let menu = mutr.create_element(name("nav"), class("menu"));
+    let list = mutr.create_element(name("ul"), Vec::new());
+    let item = mutr.create_element(name("li"), class("item"));
+    let link = mutr.create_element(name("a"), Vec::new());
+    let text = mutr.create_text_node("Link");
+    let content = mutr.create_element(name("div"), class("content"));
+    let paragraph = mutr.create_element(name("p"), class("item"));
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[menu, content]);
+    mutr.append_children(menu, &[list]);
+    mutr.append_children(list, &[item]);
+    mutr.append_children(item, &[link]);
+    mutr.append_children(link, &[text]);
+    mutr.append_children(content, &[paragraph]);
+    drop(mutr);
+
+    // Closest inclusive ancestor (starting from the parent element of text nodes)
+    assert_eq!(doc.closest(text, ".menu").unwrap(), Some(menu));
+    assert_eq!(doc.closest(link, "a").unwrap(), Some(link));
+    assert_eq!(doc.closest(text, ".content").unwrap(), None);
+    assert_eq!(doc.closest(item, ":scope > a").unwrap(), None);
+
+    // Matching a single node
+    assert!(doc.matches(item, ".menu .item").unwrap());
+    assert!(doc.matches(item, ":scope").unwrap());
+    assert!(!doc.matches(paragraph, ".menu .item").unwrap());
+    assert!(!doc.matches(text, "*").unwrap());
+    assert!(doc.matches(item, "li[").is_err());
+
+    // Queries only match descendants of the node
+    assert_eq!(
+        doc.query_selector_within(menu, ".item").unwrap(),
+        Some(item)
+    );
+    assert_eq!(
+        doc.query_selector_within(content, ".item").unwrap(),
+        Some(paragraph)
+    );
+    assert_eq!(doc.query_selector_within(menu, ".menu").unwrap(), None);
+    assert_eq!(
+        doc.query_selector_all_within(body, ":scope > *")
+            .unwrap()
+            .as_slice(),
+        [menu, content]
+    );
+    assert_eq!(
+        doc.query_selector_all_within(0, ".item")
+            .unwrap()
+            .as_slice(),
+        [item, paragraph]
+    );
+}
@@ -0,0 +1,461 @@
+//! Programmatic scrolling: scrolling elements into view, scrolling to absolute positions and smooth scrolling
+
+use std::time::Duration;
+
+use markup5ever::local_name;
+use peniko::kurbo;
+use style::computed_values::scroll_behavior::T as ComputedScrollBehavior;
+use style::values::computed::Overflow;
+
+use crate::BaseDocument;
+use crate::traversal::TreeTraverser;
+
+/// How long a smooth scroll takes
+const SMOOTH_SCROLL_DURATION: Duration = Duration::from_millis(300);
+
+/// Whether a scroll is applied instantly or animated
+/// (see <https://drafts.csswg.org/cssom-view/#enumdef-scrollbehavior>)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrollBehavior {
+    /// Use the computed `scroll-behavior` of the scroll container (the root element's for the viewport)
+    #[default]
+    Auto,
+    /// Scroll instantly
+    Instant,
+    /// Animate the scroll over document time
+    Smooth,
+}
+
+/// How an element is aligned within a scroll container along one axis
+/// (see <https://drafts.csswg.org/cssom-view/#enumdef-scrolllogicalposition>)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrollAlignment {
+    /// Align the start edge of the element with the start edge of the scroll container
+    #[default]
+    Start,
+    /// Align the center of the element with the center of the scroll container
+    Center,
+    /// Align the end edge of the element with the end edge of the scroll container
+    End,
+    /// Scroll by the minimum amount required to make the element visible (if it isn't already)
+    Nearest,
+}
+
+/// Options for [`BaseDocument::scroll_into_view`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScrollIntoViewOptions {
+    /// The vertical alignment
+    pub block: ScrollAlignment,
+    /// The horizontal alignment
+    pub inline: ScrollAlignment,
+    pub behavior: ScrollBehavior,
+}
+
+impl Default for ScrollIntoViewOptions {
+    fn default() -> Self {
+        Self {
+            block: ScrollAlignment::Start,
+            inline: ScrollAlignment::Nearest,
+            behavior: ScrollBehavior::Auto,
+        }
+    }
+}
+
+impl ScrollIntoViewOptions {
+    /// Scroll by the minimum amount required to make the element visible
+    pub fn nearest() -> Self {
+        Self {
+            block: ScrollAlignment::Nearest,
+            inline: ScrollAlignment::Nearest,
+            behavior: ScrollBehavior::Auto,
+        }
+    }
+}
+
+/// An in-progress smooth scroll of a node (or of the viewport if `node_id` is `None`)
+#[derive(Debug, Clone)]
+pub(crate) struct ScrollAnimation {
+    pub(crate) node_id: Option<usize>,
+    pub(crate) from: kurbo::Point,
+    pub(crate) to: kurbo::Point,
+    pub(crate) start_time: Duration,
+}
+
+impl BaseDocument {
+    /// Scroll each of the scroll containers which contain a node (and then the viewport) so that the node's
+    /// border box is aligned as specified by `options`
+    pub fn scroll_into_view(&mut self, node_id: usize, options: ScrollIntoViewOptions) {
+        let Some(node) = self.nodes.get(node_id) else {
+            return;
+        };
+        let origin =
+            node.absolute_position(node.scroll_offset.x as f32, node.scroll_offset.y as f32);
+        let size = node.final_layout.size;
+        let rect = kurbo::Rect::from_origin_size(
+            (origin.x as f64, origin.y as f64),
+            (size.width as f64, size.height as f64),
+        );
+        self.scroll_rect_into_view(node_id, rect, options);
+    }
+
+    /// Scroll a node to an absolute scroll position (clamped to the node's scrollable area)
+    pub fn scroll_to(&mut self, node_id: usize, position: kurbo::Point, behavior: ScrollBehavior) {
+        let Some(node) = self.nodes.get(node_id) else {
+            return;
+        };
+        let position = kurbo::Point {
+            x: position
+                .x
+                .clamp(0.0, node.final_layout.scroll_width() as f64),
+            y: position
+                .y
+                .clamp(0.0, node.final_layout.scroll_height() as f64),
+        };
+        self.set_scroll_position(Some(node_id), position, behavior);
+    }
+
+    /// Scroll the viewport to an absolute scroll position (clamped to the scrollable area of the document)
+    pub fn scroll_viewport_to(&mut self, position: kurbo::Point, behavior: ScrollBehavior) {
+        let position = self.clamp_viewport_scroll(position);
+        self.set_scroll_position(None, position, behavior);
+    }
+
+    /// Scroll to the element identified by a URL fragment (see
+    /// <https://html.spec.whatwg.org/multipage/browsing-the-web.html#scroll-to-the-fragment-identifier>).
+    /// An empty fragment or `top` scrolls to the top of the document. Returns whether the fragment was found.
+    pub fn scroll_to_fragment(&mut self, fragment: &str) -> bool {
+        let fragment = percent_encoding::percent_decode_str(fragment).decode_utf8_lossy();
+        let target = self.nodes_to_id.get(&*fragment).copied().or_else(|| {
+            TreeTraverser::new(self).find(|&node_id| {
+                self.nodes[node_id].element_data().is_some_and(|el| {
+                    el.name.local == local_name!("a")
+                        && el.attr(local_name!("name")) == Some(&*fragment)
+                })
+            })
+        });
+        match target {
+            Some(node_id) => {
+                self.scroll_into_view(node_id, ScrollIntoViewOptions::default());
+                true
+            }
+            None if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") => {
+                self.scroll_viewport_to(kurbo::Point::ZERO, ScrollBehavior::Auto);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Scroll the scroll containers which contain a node (and then the viewport) so that `rect` is aligned as
+    /// specified by `options`. `rect` is in document coordinates (CSS pixels) as returned by
+    /// [`Node::absolute_position`](crate::Node::absolute_position).
+    pub(crate) fn scroll_rect_into_view(
+        &mut self,
+        node_id: usize,
+        rect: kurbo::Rect,
+        options: ScrollIntoViewOptions,
+    ) {
+        let mut rect = rect;
+        let mut maybe_ancestor = self.nodes[node_id].layout_parent.get();
+        while let Some(ancestor_id) = maybe_ancestor {
+            let node = &self.nodes[ancestor_id];
+            maybe_ancestor = node.layout_parent.get();
+
+            let (can_x_scroll, can_y_scroll) = node
+                .primary_styles()
+                .map(|styles| {
+                    (
+                        matches!(styles.clone_overflow_x(), Overflow::Scroll | Overflow::Auto),
+                        matches!(styles.clone_overflow_y(), Overflow::Scroll | Overflow::Auto),
+                    )
+                })
+                .unwrap_or((false, false));
+            if !can_x_scroll && !can_y_scroll {
+                continue;
+            }
+
+            // The scrollport (the padding box) of the scroll container in document coordinates
+            let layout = node.final_layout;
+            let origin = node.absolute_position(
+                layout.border.left + node.scroll_offset.x as f32,
+                layout.border.top + node.scroll_offset.y as f32,
+            );
+            let scrollport = kurbo::Rect::from_origin_size(
+                (origin.x as f64, origin.y as f64),
+                (
+                    (layout.size.width - layout.border.left - layout.border.right) as f64,
+                    (layout.size.height - layout.border.top - layout.border.bottom) as f64,
+                ),
+            );
+
+            let old_offset = node.scroll_offset;
+            let mut new_offset = old_offset;
+            if can_x_scroll {
+                let delta = scroll_delta(
+                    options.inline,
+                    scrollport.x0,
+                    scrollport.x1,
+                    rect.x0,
+                    rect.x1,
+                );
+                new_offset.x = (old_offset.x + delta).clamp(0.0, layout.scroll_width() as f64);
+            }
+            if can_y_scroll {
+                let delta = scroll_delta(
+                    options.block,
+                    scrollport.y0,
+                    scrollport.y1,
+                    rect.y0,
+                    rect.y1,
+                );
+                new_offset.y = (old_offset.y + delta).clamp(0.0, layout.scroll_height() as f64);
+            }
+            self.set_scroll_position(Some(ancestor_id), new_offset, options.behavior);
+            rect = rect - (new_offset - old_offset);
+        }
+
+        let (width, height) = self.viewport_size();
+        let (x, y) = (self.viewport_scroll.x, self.viewport_scroll.y);
+        let position = kurbo::Point {
+            x: x + scroll_delta(options.inline, x, x + width, rect.x0, rect.x1),
+            y: y + scroll_delta(options.block, y, y + height, rect.y0, rect.y1),
+        };
+        self.scroll_viewport_to(position, options.behavior);
+    }
+
+    /// Set the scroll position of a node (or of the viewport if `node_id` is `None`), either instantly or by
+    /// starting a smooth scroll animation
+    fn set_scroll_position(
+        &mut self,
+        node_id: Option<usize>,
+        position: kurbo::Point,
+        behavior: ScrollBehavior,
+    ) {
+        self.cancel_scroll_animation(node_id);
+        let current = match node_id {
+            Some(node_id) => self.nodes[node_id].scroll_offset,
+            None => self.viewport_scroll,
+        };
+        if current == position {
+            return;
+        }
+
+        match self.resolve_scroll_behavior(node_id, behavior) {
+            ScrollBehavior::Smooth => {
+                let start_time = self.current_time();
+                self.scroll_animations.push(ScrollAnimation {
+                    node_id,
+                    from: current,
+                    to: position,
+                    start_time,
+                });
+            }
+            ScrollBehavior::Auto | ScrollBehavior::Instant => match node_id {
+                Some(node_id) => self.nodes[node_id].scroll_offset = position,
+                None => self.viewport_scroll = position,
+            },
+        }
+        self.shell_provider.request_redraw();
+    }
+
+    /// Resolve [`ScrollBehavior::Auto`] to the `scroll-behavior` of a scroll container (or of the root element if
+    /// `node_id` is `None`)
+    fn resolve_scroll_behavior(
+        &self,
+        node_id: Option<usize>,
+        behavior: ScrollBehavior,
+    ) -> ScrollBehavior {
+        if behavior != ScrollBehavior::Auto {
+            return behavior;
+        }
+        let node = match node_id {
+            Some(node_id) => &self.nodes[node_id],
+            None => self.root_element(),
+        };
+        let computed = node
+            .primary_styles()
+            .map(|styles| styles.get_box().clone_scroll_behavior());
+        match computed {
+            Some(ComputedScrollBehavior::Smooth) => ScrollBehavior::Smooth,
+            _ => ScrollBehavior::Instant,
+        }
+    }
+
+    /// Stop smooth scrolling a node (or the viewport if `node_id` is `None`)
+    pub(crate) fn cancel_scroll_animation(&mut self, node_id: Option<usize>) {
+        self.scroll_animations
+            .retain(|animation| animation.node_id != node_id);
+    }
+
+    /// Advance smooth scroll animations to document time `time`
+    pub(crate) fn advance_scroll_animations(&mut self, time: Duration) {
+        let nodes = &mut self.nodes;
+        let viewport_scroll = &mut self.viewport_scroll;
+        self.scroll_animations.retain(|animation| {
+            let elapsed = time.saturating_sub(animation.start_time);
+            let progress = (elapsed.as_secs_f64() / SMOOTH_SCROLL_DURATION.as_secs_f64()).min(1.0);
+            // Ease-out (cubic)
+            let eased = 1.0 - (1.0 - progress).powi(3);
+            let position = animation.from.lerp(animation.to, eased);
+            match animation.node_id {
+                Some(node_id) => {
+                    if let Some(node) = nodes.get_mut(node_id) {
+                        node.scroll_offset = position;
+                    }
+                }
+                None => *viewport_scroll = position,
+            }
+            progress < 1.0
+        });
+    }
+
+    /// The size of the viewport in CSS pixels
+    pub(crate) fn viewport_size(&self) -> (f64, f64) {
+        let scale = self.viewport.scale_f64();
+        (
+            self.viewport.window_size.0 as f64 / scale,
+            self.viewport.window_size.1 as f64 / scale,
+        )
+    }
+
+    pub(crate) fn clamp_viewport_scroll(&self, position: kurbo::Point) -> kurbo::Point {
+        let content_size = self.root_element().final_layout.size;
+        let (width, height) = self.viewport_size();
+        kurbo::Point {
+            x: position.x.min(content_size.width as f64 - width).max(0.0),
+            y: position.y.min(content_size.height as f64 - height).max(0.0),
+        }
+    }
+}
+
+/// The amount by which to scroll (along one axis) to align the range `start..end` with the scrollport `min..max`
+fn scroll_delta(alignment: ScrollAlignment, min: f64, max: f64, start: f64, end: f64) -> f64 {
+    match alignment {
+        ScrollAlignment::Start => start - min,
+        ScrollAlignment::End => end - max,
+        ScrollAlignment::Center => (start + end - min - max) / 2.0,
+        // If the range is larger than the scrollport then it's start is aligned with the start of the scrollport
+        ScrollAlignment::Nearest if start < min => start - min,
+        ScrollAlignment::Nearest if end > max => (end - max).min(start - min),
+        ScrollAlignment::Nearest => 0.0,
+    }
+}
+
+#[test]
+fn scrolls_nodes_into_view() {
+    use crate::{Attribute, DocumentConfig};
+    use blitz_traits::shell::{ColorScheme, Viewport};
+    use markup5ever::{LocalName, QualName, ns};
+
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(400, 300, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let style = |value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("style")),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), style("margin: 0"));
+    let spacer = mutr.create_element(name("div"), style("height: 1000px"));
+    let scroller = mutr.create_element(name("div"), style("height: 100px; overflow: auto"));
+    let inner_spacer = mutr.create_element(name("div"), style("height: 500px"));
+    let target = mutr.create_element(name("div"), style("height: 20px"));
+    let inner_spacer_after = mutr.create_element(name("div"), style("height: 500px"));
+    let spacer_after = mutr.create_element(name("div"), style("height: 1000px"));
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[spacer, scroller, spacer_after]);
+    mutr.append_children(scroller, &[inner_spacer, target, inner_spacer_after]);
+    drop(mutr);
+    doc.resolve();
+
+    // The target is aligned with the top of the scroller, and the scroller with the top of the viewport
+    doc.scroll_into_view(target, ScrollIntoViewOptions::default());
+    assert_eq!(doc.nodes[scroller].scroll_offset.y, 500.0);
+    assert_eq!(doc.viewport_scroll().y, 1000.0);
+
+    // Aligning with the end of the scroller and the viewport
+    let options = ScrollIntoViewOptions {
+        block: ScrollAlignment::End,
+        ..Default::default()
+    };
+    doc.scroll_to(scroller, kurbo::Point::ZERO, ScrollBehavior::Instant);
+    doc.scroll_into_view(target, options);
+    assert_eq!(doc.nodes[scroller].scroll_offset.y, 420.0);
+    assert_eq!(doc.viewport_scroll().y, 800.0);
+
+    // Smooth scrolling is animated over document time
+    doc.scroll_viewport_to(kurbo::Point::new(0.0, 200.0), ScrollBehavior::Smooth);
+    assert_eq!(doc.viewport_scroll().y, 800.0);
+    assert!(doc.is_animating());
+    let start_time = doc.scroll_animations[0].start_time;
+    doc.advance_scroll_animations(start_time + SMOOTH_SCROLL_DURATION / 2);
+    let y = doc.viewport_scroll().y;
+    assert!(y < 800.0 && y > 200.0);
+    doc.advance_scroll_animations(start_time + SMOOTH_SCROLL_DURATION);
+    assert_eq!(doc.viewport_scroll().y, 200.0);
+    assert!(!doc.is_animating());
+}
+
+#[test]
+fn resolves_auto_scroll_behavior() {
+    use crate::{Attribute, DocumentConfig};
+    use blitz_traits::shell::{ColorScheme, Viewport};
+    use markup5ever::{LocalName, QualName, ns};
+
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(400, 300, 1.0, ColorScheme::Light)),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let style = |value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), local_name!("style")),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let stylesheet = mutr.create_element(name("style"), Vec::new());
+    let css = mutr
+        .create_text_node("html { scroll-behavior: smooth } .instant { scroll-behavior: auto }");
+    let body = mutr.create_element(name("body"), style("margin: 0; height: 2000px"));
+    let smooth = mutr.create_element(
+        name("div"),
+        style("height: 100px; overflow: auto; font-family: \"a;b\"; scroll-behavior: smooth"),
+    );
+    let instant = mutr.create_element(name("div"), style("height: 100px; overflow: auto"));
+    mutr.set_attribute(
+        instant,
+        QualName::new(None, ns!(), local_name!("class")),
+        "instant",
+    );
+    let smooth_content = mutr.create_element(name("div"), style("height: 500px"));
+    let instant_content = mutr.create_element(name("div"), style("height: 500px"));
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[stylesheet, body]);
+    mutr.append_children(stylesheet, &[css]);
+    mutr.append_children(body, &[smooth, instant]);
+    mutr.append_children(smooth, &[smooth_content]);
+    mutr.append_children(instant, &[instant_content]);
+    drop(mutr);
+    doc.resolve();
+
+    // The viewport uses the root element's `scroll-behavior` (from a stylesheet)
+    doc.scroll_viewport_to(kurbo::Point::new(0.0, 100.0), ScrollBehavior::Auto);
+    assert_eq!(doc.viewport_scroll().y, 0.0);
+    assert!(doc.is_animating());
+
+    // Scroll containers use their own `scroll-behavior` (which isn't inherited)
+    let target = kurbo::Point::new(0.0, 100.0);
+    doc.scroll_to(smooth, target, ScrollBehavior::Auto);
+    assert_eq!(doc.nodes[smooth].scroll_offset.y, 0.0);
+    doc.scroll_to(instant, target, ScrollBehavior::Auto);
+    assert_eq!(doc.nodes[instant].scroll_offset.y, 100.0);
+    assert_eq!(doc.scroll_animations.len(), 2);
+}
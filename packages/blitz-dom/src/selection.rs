@@ -0,0 +1,525 @@
+//! Document text selection (of the text outside of text inputs)
+//!
+//! Text is laid out in inline formatting contexts, so a position in the document's text is a byte offset into the
+//! text of an inline layout. Selections can span multiple inline layouts, which are ordered by their position in
+//! the layout tree.
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use keyboard_types::Key;
+use parley::{Affinity, Cursor, Selection};
+use peniko::kurbo;
+
+use crate::BaseDocument;
+use crate::node::TextLayout;
+
+/// The maximum time between two clicks for them to count as a double-click
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(500);
+/// The maximum distance (in CSS pixels) between two clicks for them to count as a double-click
+const DOUBLE_CLICK_DISTANCE: f32 = 4.0;
+
+/// A position in the document's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextPosition {
+    /// The ID of the root node of the inline formatting context
+    pub node_id: usize,
+    /// The byte offset into the text of the inline layout
+    pub offset: usize,
+}
+
+/// A range of the document's text which has been selected by the user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextSelection {
+    /// The position at which the selection was started
+    pub anchor: TextPosition,
+    /// The position to which the selection has been extended (which may be before the anchor)
+    pub focus: TextPosition,
+}
+
+impl TextSelection {
+    pub fn collapsed(position: TextPosition) -> Self {
+        Self {
+            anchor: position,
+            focus: position,
+        }
+    }
+
+    pub fn is_collapsed(&self) -> bool {
+        self.anchor == self.focus
+    }
+}
+
+/// The state of an in-progress mouse selection
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct SelectionGesture {
+    /// When and where the last mousedown happened (used to detect double-clicks)
+    pub(crate) last_mousedown: Option<(Instant, f32, f32)>,
+    /// Whether the selection is currently being extended by dragging the mouse
+    pub(crate) is_dragging: bool,
+}
+
+impl BaseDocument {
+    /// The current text selection (if any)
+    pub fn text_selection(&self) -> Option<TextSelection> {
+        self.text_selection
+            .filter(|selection| self.is_valid_position(selection.anchor))
+            .filter(|selection| self.is_valid_position(selection.focus))
+    }
+
+    /// Set the text selection. Returns `false` (and leaves the selection unchanged) if either position is not in an
+    /// inline layout.
+    pub fn set_text_selection(&mut self, anchor: TextPosition, focus: TextPosition) -> bool {
+        if !self.is_valid_position(anchor) || !self.is_valid_position(focus) {
+            return false;
+        }
+        self.text_selection = Some(TextSelection { anchor, focus });
+        true
+    }
+
+    /// Clear the text selection
+    pub fn clear_text_selection(&mut self) {
+        self.text_selection = None;
+    }
+
+    /// The position in the document's text that is closest to a point (in document coordinates)
+    pub fn text_position_from_point(&self, x: f32, y: f32) -> Option<TextPosition> {
+        let (node_id, layout, x, y) = self.inline_layout_at_point(x, y)?;
+        let cursor = Cursor::from_point(&layout.layout, x, y);
+        Some(TextPosition {
+            node_id,
+            offset: cursor.index(),
+        })
+    }
+
+    /// The range of an inline layout's text which is selected (if any)
+    pub fn selected_text_range(&self, node_id: usize) -> Option<Range<usize>> {
+        let (start, end) = self.ordered_selection()?;
+        let text = &self.nodes[node_id]
+            .element_data()?
+            .inline_layout_data
+            .as_ref()?
+            .text;
+        let range = match (start.node_id == node_id, end.node_id == node_id) {
+            (true, true) => start.offset..end.offset,
+            (true, false) => start.offset..text.len(),
+            (false, true) => 0..end.offset,
+            (false, false) => {
+                let is_between = self.compare_layout_order(start.node_id, node_id).is_lt()
+                    && self.compare_layout_order(node_id, end.node_id).is_lt();
+                if !is_between {
+                    return None;
+                }
+                0..text.len()
+            }
+        };
+        let range = clamp_offset(text, range.start)..clamp_offset(text, range.end);
+        (!range.is_empty()).then_some(range)
+    }
+
+    /// The selected text. The text of each inline layout is separated by a newline.
+    pub fn selected_text(&self) -> String {
+        let Some((start, end)) = self.ordered_selection() else {
+            return String::new();
+        };
+        let inline_roots = self.inline_roots();
+        let Some(first) = inline_roots.iter().position(|id| *id == start.node_id) else {
+            return String::new();
+        };
+        let Some(last) = inline_roots.iter().position(|id| *id == end.node_id) else {
+            return String::new();
+        };
+
+        let mut parts = Vec::new();
+        for &node_id in &inline_roots[first..=last] {
+            let text = &self.inline_layout(node_id).unwrap().text;
+            let range = self.selected_text_range(node_id).unwrap_or(0..0);
+            parts.push(&text[range]);
+        }
+        parts.join("\n")
+    }
+
+    /// The boxes (one per line) of a range of an inline layout's text in document coordinates (CSS pixels). Transforms
+    /// are applied, so each box is the bounding box of the transformed line fragment.
+    pub fn text_range_boxes(&self, node_id: usize, range: Range<usize>) -> Vec<kurbo::Rect> {
+        let Some(text_layout) = self.inline_layout(node_id) else {
+            return Vec::new();
+        };
+        let layout = &text_layout.layout;
+        let selection = Selection::new(
+            Cursor::from_byte_index(layout, range.start, Affinity::Downstream),
+            Cursor::from_byte_index(layout, range.end, Affinity::Upstream),
+        );
+
+        // The text is offset by the content box and scrolled, and then transformed like the node (the inverse of
+        // the mapping used for hit testing)
+        let node = &self.nodes[node_id];
+        let node_layout = node.final_layout;
+        let content_origin = kurbo::Vec2::new(
+            (node_layout.border.left + node_layout.padding.left) as f64,
+            (node_layout.border.top + node_layout.padding.top) as f64,
+        ) - node.scroll_offset.to_vec2();
+        let transform = self
+            .node_to_viewport_transform(node_id)
+            .then_translate(self.viewport_scroll.to_vec2());
+        let scale = layout.scale() as f64;
+        selection
+            .geometry(layout)
+            .into_iter()
+            .map(|(rect, _line_idx)| {
+                transform.transform_rect_bbox(rect.scale_from_origin(1.0 / scale) + content_origin)
+            })
+            .collect()
+    }
+
+    /// Copy the selected text to the clipboard (using the [`ShellProvider`](blitz_traits::shell::ShellProvider))
+    pub fn copy_selected_text(&self) {
+        if self.text_selection().is_some_and(|s| !s.is_collapsed()) {
+            let _ = self.shell_provider.set_clipboard_text(self.selected_text());
+        }
+    }
+
+    /// Start a mouse selection at a point (in document coordinates). A double-click selects the word at the point,
+    /// and shift-clicking extends the existing selection. Returns whether the selection changed.
+    pub(crate) fn start_text_selection(&mut self, x: f32, y: f32, extend: bool) -> bool {
+        let now = Instant::now();
+        let is_double_click =
+            self.selection_gesture
+                .last_mousedown
+                .is_some_and(|(time, prev_x, prev_y)| {
+                    now.duration_since(time) < DOUBLE_CLICK_INTERVAL
+                        && (x - prev_x).abs() <= DOUBLE_CLICK_DISTANCE
+                        && (y - prev_y).abs() <= DOUBLE_CLICK_DISTANCE
+                });
+        // The click after a double-click is treated as a new single click
+        self.selection_gesture.last_mousedown = (!is_double_click).then_some((now, x, y));
+
+        let previous = self.text_selection();
+        let Some((node_id, layout, layout_x, layout_y)) = self.inline_layout_at_point(x, y) else {
+            self.selection_gesture.is_dragging = false;
+            self.clear_text_selection();
+            return previous.is_some();
+        };
+
+        let selection = if is_double_click {
+            let word = Selection::word_from_point(&layout.layout, layout_x, layout_y);
+            let range = word.text_range();
+            TextSelection {
+                anchor: TextPosition {
+                    node_id,
+                    offset: range.start,
+                },
+                focus: TextPosition {
+                    node_id,
+                    offset: range.end,
+                },
+            }
+        } else {
+            let focus = TextPosition {
+                node_id,
+                offset: Cursor::from_point(&layout.layout, layout_x, layout_y).index(),
+            };
+            match previous {
+                Some(previous) if extend => TextSelection {
+                    anchor: previous.anchor,
+                    focus,
+                },
+                _ => TextSelection::collapsed(focus),
+            }
+        };
+
+        self.selection_gesture.is_dragging = !is_double_click;
+        self.text_selection = Some(selection);
+        previous != Some(selection)
+    }
+
+    /// Extend a mouse selection to a point (in document coordinates). Returns whether the selection changed.
+    pub(crate) fn drag_text_selection(&mut self, x: f32, y: f32) -> bool {
+        if !self.selection_gesture.is_dragging {
+            return false;
+        }
+        let Some(selection) = self.text_selection() else {
+            return false;
+        };
+        let Some(focus) = self.text_position_from_point(x, y) else {
+            return false;
+        };
+        self.text_selection = Some(TextSelection {
+            anchor: selection.anchor,
+            focus,
+        });
+        focus != selection.focus
+    }
+
+    /// Finish a mouse selection
+    pub(crate) fn end_text_selection(&mut self) {
+        self.selection_gesture.is_dragging = false;
+    }
+
+    /// Extend the selection using the keyboard (shift + arrow keys, home and end). The focus moves into the
+    /// previous or next inline layout when it reaches the start or end of an inline layout. Returns whether the
+    /// selection changed.
+    pub(crate) fn extend_text_selection(&mut self, key: &Key, by_word: bool) -> bool {
+        let Some(selection) = self.text_selection() else {
+            return false;
+        };
+        let focus = selection.focus;
+        let layout = &self.inline_layout(focus.node_id).unwrap().layout;
+        let cursor = Cursor::from_byte_index(layout, focus.offset, Affinity::Downstream);
+        let current = Selection::new(cursor, cursor);
+
+        let (moved, forwards) = match key {
+            Key::ArrowLeft if by_word => (current.previous_visual_word(layout, true), false),
+            Key::ArrowRight if by_word => (current.next_visual_word(layout, true), true),
+            Key::ArrowLeft => (current.previous_visual(layout, true), false),
+            Key::ArrowRight => (current.next_visual(layout, true), true),
+            Key::ArrowUp => (current.previous_line(layout, true), false),
+            Key::ArrowDown => (current.next_line(layout, true), true),
+            Key::Home => (current.line_start(layout, true), false),
+            Key::End => (current.line_end(layout, true), true),
+            _ => return false,
+        };
+
+        let mut new_focus = TextPosition {
+            node_id: focus.node_id,
+            offset: moved.focus().index(),
+        };
+        let is_arrow = !matches!(key, Key::Home | Key::End);
+        if new_focus == focus && is_arrow {
+            // Move into the adjacent inline layout
+            let inline_roots = self.inline_roots();
+            let index = inline_roots.iter().position(|id| *id == focus.node_id);
+            let adjacent = match (index, forwards) {
+                (Some(index), true) => inline_roots.get(index + 1),
+                (Some(index), false) => index.checked_sub(1).and_then(|i| inline_roots.get(i)),
+                (None, _) => None,
+            };
+            if let Some(&node_id) = adjacent {
+                let offset = match forwards {
+                    true => 0,
+                    false => self.inline_layout(node_id).unwrap().text.len(),
+                };
+                new_focus = TextPosition { node_id, offset };
+            }
+        }
+
+        self.text_selection = Some(TextSelection {
+            anchor: selection.anchor,
+            focus: new_focus,
+        });
+        new_focus != focus
+    }
+
+    /// The selection's (start, end) positions in document order
+    fn ordered_selection(&self) -> Option<(TextPosition, TextPosition)> {
+        let TextSelection { anchor, focus } = self.text_selection()?;
+        let ordering = match anchor.node_id == focus.node_id {
+            true => anchor.offset.cmp(&focus.offset),
+            false => self.compare_layout_order(anchor.node_id, focus.node_id),
+        };
+        match ordering {
+            Ordering::Greater => Some((focus, anchor)),
+            _ => Some((anchor, focus)),
+        }
+    }
+
+    pub(crate) fn inline_layout(&self, node_id: usize) -> Option<&TextLayout> {
+        let node = self.nodes.get(node_id)?;
+        if !node.flags.is_inline_root() {
+            return None;
+        }
+        node.element_data()?.inline_layout_data.as_deref()
+    }
+
+    fn is_valid_position(&self, position: TextPosition) -> bool {
+        self.inline_layout(position.node_id)
+            .is_some_and(|layout| position.offset <= layout.text.len())
+    }
+
+    /// The inline layout at a point (in document coordinates), and the point in the coordinates of the layout
+    fn inline_layout_at_point(&self, x: f32, y: f32) -> Option<(usize, &TextLayout, f32, f32)> {
+        let hit = self.hit(x, y)?;
+        let mut node = &self.nodes[hit.node_id];
+        while !node.flags.is_inline_root() {
+            node = &self.nodes[node.layout_parent.get()?];
+        }
+        let text_layout = self.inline_layout(node.id)?;
+
+        // Map the point into the coordinate space of the text (which is offset by the content box and scrolled)
+        let transform = self.node_to_viewport_transform(node.id);
+        let point = kurbo::Point::new(x as f64, y as f64) - self.viewport_scroll.to_vec2();
+        let local = transform.inverse() * point + node.scroll_offset.to_vec2();
+        let layout = node.final_layout;
+        let scale = text_layout.layout.scale();
+        let x = (local.x as f32 - layout.border.left - layout.padding.left) * scale;
+        let y = (local.y as f32 - layout.border.top - layout.padding.top) * scale;
+        Some((node.id, text_layout, x, y))
+    }
+
+    /// The root nodes of all inline formatting contexts, in layout tree order
+    pub(crate) fn inline_roots(&self) -> Vec<usize> {
+        let mut inline_roots = Vec::new();
+        let mut stack = vec![self.root_node().id];
+        while let Some(node_id) = stack.pop() {
+            let node = &self.nodes[node_id];
+            if node.flags.is_inline_root() {
+                inline_roots.push(node_id);
+            }
+            if let Some(children) = node.layout_children.borrow().as_ref() {
+                stack.extend(children.iter().rev());
+            }
+        }
+        inline_roots
+    }
+
+    /// Compare the position of two nodes in the layout tree (ancestors are ordered before their descendants)
+    fn compare_layout_order(&self, a: usize, b: usize) -> Ordering {
+        let ancestors = |mut node_id: usize| {
+            let mut chain = vec![node_id];
+            while let Some(parent_id) = self.nodes[node_id].layout_parent.get() {
+                chain.push(parent_id);
+                node_id = parent_id;
+            }
+            chain.reverse();
+            chain
+        };
+        let a_chain = ancestors(a);
+        let b_chain = ancestors(b);
+
+        let common = a_chain
+            .iter()
+            .zip(&b_chain)
+            .take_while(|(a, b)| a == b)
+            .count();
+        match (a_chain.get(common), b_chain.get(common)) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Less,
+            (Some(_), None) => Ordering::Greater,
+            (Some(a_child), Some(b_child)) => {
+                let Some(parent_id) = common.checked_sub(1).map(|i| a_chain[i]) else {
+                    return a_child.cmp(b_child);
+                };
+                let children = self.nodes[parent_id].layout_children.borrow();
+                let index_of = |id: &usize| {
+                    children
+                        .as_ref()
+                        .and_then(|children| children.iter().position(|child| child == id))
+                };
+                index_of(a_child).cmp(&index_of(b_child))
+            }
+        }
+    }
+}
+
+/// Clamp a byte offset to the text's length, and move it back to the nearest character boundary
+fn clamp_offset(text: &str, offset: usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+#[test]
+fn selects_text_across_inline_layouts() {
+    use crate::Attribute;
+    use crate::DocumentConfig;
+    use blitz_traits::shell::{ClipboardError, ColorScheme, ShellProvider, Viewport};
+    use markup5ever::{LocalName, QualName, ns};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Default)]
+    struct Clipboard(Mutex<String>);
+    impl ShellProvider for Clipboard {
+        fn set_clipboard_text(&self, text: String) -> Result<(), ClipboardError> {
+            *self.0.lock().unwrap() = text;
+            Ok(())
+        }
+    }
+
+    let clipboard = Arc::new(Clipboard::default());
+    let mut doc = BaseDocument::new(DocumentConfig {
+        viewport: Some(Viewport::new(800, 600, 1.0, ColorScheme::Light)),
+        shell_provider: Some(clipboard.clone()),
+        ..Default::default()
+    });
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    let first = mutr.create_element(name("p"), Vec::new());
+    let second = mutr.create_element(name("p"), Vec::new());
+    let third = mutr.create_element(
+        name("p"),
+        vec![Attribute {
+            name: QualName::new(None, ns!(), LocalName::from("style")),
+            value: "transform: translateX(100px)".to_string(),
+        }],
+    );
+    let first_text = mutr.create_text_node("Hello world");
+    let second_text = mutr.create_text_node("Second paragraph");
+    let third_text = mutr.create_text_node("The end");
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[first, second, third]);
+    mutr.append_children(first, &[first_text]);
+    mutr.append_children(second, &[second_text]);
+    mutr.append_children(third, &[third_text]);
+    drop(mutr);
+    doc.resolve();
+
+    // Select backwards, from the middle of the last paragraph to the middle of the first
+    let anchor = TextPosition {
+        node_id: third,
+        offset: 3,
+    };
+    let focus = TextPosition {
+        node_id: first,
+        offset: 6,
+    };
+    assert!(doc.set_text_selection(anchor, focus));
+    assert_eq!(doc.selected_text_range(first), Some(6..11));
+    assert_eq!(doc.selected_text_range(second), Some(0..16));
+    assert_eq!(doc.selected_text_range(third), Some(0..3));
+    assert_eq!(doc.selected_text(), "world\nSecond paragraph\nThe");
+
+    // Extending the selection past the start of a paragraph moves it into the previous paragraph
+    doc.set_text_selection(
+        TextPosition {
+            node_id: second,
+            offset: 0,
+        },
+        TextPosition {
+            node_id: second,
+            offset: 1,
+        },
+    );
+    assert!(doc.extend_text_selection(&Key::ArrowLeft, false));
+    assert!(doc.extend_text_selection(&Key::ArrowLeft, false));
+    assert_eq!(doc.text_selection().unwrap().focus.node_id, first);
+    assert_eq!(doc.selected_text(), "\n");
+
+    // Double-clicking selects a word
+    let origin = doc.nodes[first].absolute_position(0.0, 0.0);
+    let (x, y) = (origin.x + 2.0, origin.y + 5.0);
+    doc.start_text_selection(x, y, false);
+    doc.end_text_selection();
+    doc.start_text_selection(x, y, false);
+    assert_eq!(doc.selected_text(), "Hello");
+
+    doc.copy_selected_text();
+    assert_eq!(*clipboard.0.lock().unwrap(), "Hello");
+
+    // The boxes of transformed text are where it is hit tested
+    let origin = doc.nodes[third].absolute_position(0.0, 0.0);
+    let boxes = doc.text_range_boxes(third, 4..7);
+    assert_eq!(boxes.len(), 1);
+    assert!(boxes[0].x0 > origin.x as f64 + 100.0);
+    let center = boxes[0].center();
+    doc.end_text_selection();
+    doc.start_text_selection(center.x as f32, center.y as f32, false);
+    doc.end_text_selection();
+    doc.start_text_selection(center.x as f32, center.y as f32, false);
+    assert_eq!(doc.selected_text(), "end");
+}
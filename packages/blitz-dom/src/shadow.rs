@@ -0,0 +1,313 @@
+//! Shadow DOM: shadow roots, slot assignment and shadow-scoped stylesheets
+
+use markup5ever::{QualName, local_name, ns};
+use style::invalidation::element::restyle_hints::RestyleHint;
+use style::stylesheet_set::AuthorStylesheetSet;
+use style::stylesheets::DocumentStyleSheet;
+
+use crate::BaseDocument;
+use crate::node::{Node, NodeData, SpecialElementData};
+use crate::traversal::TreeTraverser;
+
+/// Whether a shadow root can be attached to an element with the specified name
+/// (see <https://dom.spec.whatwg.org/#dom-element-attachshadow>)
+pub fn can_attach_shadow(name: &QualName) -> bool {
+    if name.ns != ns!(html) {
+        return false;
+    }
+    matches!(
+        name.local,
+        local_name!("article")
+            | local_name!("aside")
+            | local_name!("blockquote")
+            | local_name!("body")
+            | local_name!("div")
+            | local_name!("footer")
+            | local_name!("h1")
+            | local_name!("h2")
+            | local_name!("h3")
+            | local_name!("h4")
+            | local_name!("h5")
+            | local_name!("h6")
+            | local_name!("header")
+            | local_name!("main")
+            | local_name!("nav")
+            | local_name!("p")
+            | local_name!("section")
+            | local_name!("span")
+    ) || is_valid_custom_element_name(&name.local)
+}
+
+/// Whether `name` is a valid custom element name: it must start with a lowercase ASCII letter, contain a hyphen,
+/// and not be one of the reserved hyphenated names from SVG and MathML.
+pub(crate) fn is_valid_custom_element_name(name: &str) -> bool {
+    const RESERVED_NAMES: [&str; 8] = [
+        "annotation-xml",
+        "color-profile",
+        "font-face",
+        "font-face-src",
+        "font-face-uri",
+        "font-face-format",
+        "font-face-name",
+        "missing-glyph",
+    ];
+
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.contains('-')
+        && !name.chars().any(|c| c.is_ascii_uppercase())
+        && !RESERVED_NAMES.contains(&name)
+}
+
+impl BaseDocument {
+    /// The shadow root attached to the node (if it is a shadow host)
+    pub fn shadow_root(&self, host_id: usize) -> Option<usize> {
+        self.nodes.get(host_id)?.element_data()?.shadow_root
+    }
+
+    /// The element the shadow root is attached to (if the node is a shadow root)
+    pub fn shadow_host(&self, shadow_root_id: usize) -> Option<usize> {
+        Some(self.nodes.get(shadow_root_id)?.shadow_root_data()?.host)
+    }
+
+    /// The root of the shadow tree that the node is in (if any)
+    pub fn containing_shadow_root(&self, node_id: usize) -> Option<usize> {
+        let mut node = self.nodes.get(node_id)?;
+        while let Some(parent_id) = node.parent {
+            node = &self.nodes[parent_id];
+        }
+        node.is_shadow_root().then_some(node.id)
+    }
+
+    /// The `<slot>` the node is assigned to (if any)
+    pub fn assigned_slot(&self, node_id: usize) -> Option<usize> {
+        self.nodes.get(node_id)?.assigned_slot
+    }
+
+    /// The nodes assigned to a `<slot>` element
+    pub fn assigned_nodes(&self, slot_id: usize) -> &[usize] {
+        self.nodes
+            .get(slot_id)
+            .and_then(|node| node.element_data())
+            .map(|element| element.assigned_nodes.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The parent of the node in the flat tree. This is the `<slot>` the node is assigned to (if any), or
+    /// else it's parent in the DOM (with shadow roots replaced by their host).
+    pub fn flat_tree_parent(&self, node_id: usize) -> Option<usize> {
+        let node = self.nodes.get(node_id)?;
+        if let Some(slot_id) = node.assigned_slot {
+            return Some(slot_id);
+        }
+        let parent = &self.nodes[node.parent?];
+        match parent.shadow_root_data() {
+            Some(data) => Some(data.host),
+            None => Some(parent.id),
+        }
+    }
+
+    /// Retarget `target` against `node` (see <https://dom.spec.whatwg.org/#retarget>).
+    ///
+    /// This is the node that an event dispatched to `target` appears to have been dispatched to when observed
+    /// from `node`: nodes inside shadow trees that `node` is outside of are replaced by their shadow host.
+    pub fn retarget(&self, mut target: usize, node: usize) -> usize {
+        loop {
+            let Some(shadow_root_id) = self.containing_shadow_root(target) else {
+                return target;
+            };
+            if self.is_shadow_including_inclusive_ancestor(shadow_root_id, node) {
+                return target;
+            }
+            target = self.nodes[shadow_root_id].shadow_root_data().unwrap().host;
+        }
+    }
+
+    fn is_shadow_including_inclusive_ancestor(&self, ancestor_id: usize, node_id: usize) -> bool {
+        let mut current = Some(node_id);
+        while let Some(id) = current {
+            if id == ancestor_id {
+                return true;
+            }
+            let Some(node) = self.nodes.get(id) else {
+                return false;
+            };
+            current = node
+                .parent
+                .or_else(|| node.shadow_root_data().map(|data| data.host));
+        }
+        false
+    }
+
+    /// Assign the children of each shadow host to the `<slot>`s in it's shadow tree (by matching the
+    /// child's `slot` attribute against the slot's `name` attribute).
+    pub(crate) fn assign_slots(&mut self) {
+        for (node_id, slot_id) in &self.slot_assignments {
+            if let Some(node) = self.nodes.get_mut(*node_id) {
+                node.assigned_slot = None;
+            }
+            if let Some(element) = self
+                .nodes
+                .get_mut(*slot_id)
+                .and_then(|slot| slot.element_data_mut())
+            {
+                element.assigned_nodes.clear();
+            }
+        }
+
+        let mut assignments = Vec::new();
+        for &shadow_root_id in &self.shadow_roots {
+            let Some(host_id) = self.shadow_host(shadow_root_id) else {
+                continue;
+            };
+            let slots: Vec<(usize, &str)> = TreeTraverser::new_with_root(self, shadow_root_id)
+                .filter_map(|id| {
+                    let element = self.nodes[id].element_data()?;
+                    let is_slot =
+                        element.name.local == local_name!("slot") && element.name.ns == ns!(html);
+                    is_slot.then(|| (id, element.attr(local_name!("name")).unwrap_or("")))
+                })
+                .collect();
+
+            for &child_id in &self.nodes[host_id].children {
+                let slot_name = match &self.nodes[child_id].data {
+                    NodeData::Element(element) => element.attr(local_name!("slot")).unwrap_or(""),
+                    NodeData::Text(_) => "",
+                    _ => continue,
+                };
+                if let Some(&(slot_id, _)) = slots.iter().find(|(_, name)| *name == slot_name) {
+                    assignments.push((child_id, slot_id));
+                }
+            }
+        }
+
+        for &(node_id, slot_id) in &assignments {
+            self.nodes[node_id].assigned_slot = Some(slot_id);
+            let slot = self.nodes[slot_id].element_data_mut().unwrap();
+            slot.assigned_nodes.push(node_id);
+        }
+
+        if assignments != self.slot_assignments {
+            let host_ids: Vec<usize> = self
+                .shadow_roots
+                .iter()
+                .filter_map(|id| self.shadow_host(*id))
+                .collect();
+            for host_id in host_ids {
+                self.nodes[host_id].set_restyle_hint(RestyleHint::restyle_subtree());
+            }
+        }
+        self.slot_assignments = assignments;
+    }
+
+    /// Rebuild the cascade data of shadow roots whose `<style>` and `<link>` stylesheets have changed
+    pub(crate) fn flush_shadow_stylesheets(&mut self) {
+        if !std::mem::take(&mut self.shadow_stylesheets_dirty) {
+            return;
+        }
+
+        let shadow_root_ids: Vec<usize> = self.shadow_roots.iter().copied().collect();
+        for shadow_root_id in shadow_root_ids {
+            let sheets: Vec<DocumentStyleSheet> =
+                TreeTraverser::new_with_root(self, shadow_root_id)
+                    .filter_map(|id| match &self.nodes[id].element_data()?.special_data {
+                        SpecialElementData::Stylesheet(sheet) => Some(sheet.clone()),
+                        _ => None,
+                    })
+                    .collect();
+
+            let Some(Node {
+                data: NodeData::ShadowRoot(data),
+                ..
+            }) = self.nodes.get_mut(shadow_root_id)
+            else {
+                continue;
+            };
+            let styles = &mut data.author_styles;
+            if !styles.stylesheets.dirty() && styles.stylesheets.iter().eq(sheets.iter()) {
+                continue;
+            }
+
+            let guard = self.guard.read();
+            let mut stylesheets = AuthorStylesheetSet::new();
+            for sheet in sheets {
+                stylesheets.append_stylesheet(None, sheet, &guard);
+            }
+            stylesheets.force_dirty();
+            let flusher = stylesheets.flush::<&Node>(None, None);
+            let result = self
+                .stylist
+                .rebuild_author_data(&styles.data, flusher.sheets, &guard);
+            if let Ok(Some(new_data)) = result {
+                styles.data = new_data;
+            }
+            styles.stylesheets = stylesheets;
+            let host_id = data.host;
+            drop(guard);
+
+            self.nodes[host_id].set_restyle_hint(RestyleHint::restyle_subtree());
+        }
+    }
+}
+
+#[test]
+fn styles_shadow_trees_and_assigns_slots() {
+    use crate::node::ShadowRootMode;
+    use crate::{Attribute, DocumentConfig};
+    use markup5ever::LocalName;
+    use style::values::computed::Display;
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let name = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    let attr = |name: &str, value: &str| {
+        vec![Attribute {
+            name: QualName::new(None, ns!(), LocalName::from(name)),
+            value: value.to_string(),
+        }]
+    };
+    let mut mutr = doc.mutate();
+    let html = mutr.create_element(name("html"), Vec::new());
+    let body = mutr.create_element(name("body"), Vec::new());
+    let host = mutr.create_element(name("my-widget"), Vec::new());
+    let named = mutr.create_element(name("span"), attr("slot", "title"));
+    let unnamed = mutr.create_element(name("span"), Vec::new());
+    mutr.append_children(0, &[html]);
+    mutr.append_children(html, &[body]);
+    mutr.append_children(body, &[host]);
+    mutr.append_children(host, &[named, unnamed]);
+
+    let shadow_root = mutr.attach_shadow(host, ShadowRootMode::Open).unwrap();
+    let style = mutr.create_element(name("style"), Vec::new());
+    let style_text = mutr.create_text_node("span { display: none } slot { display: block }");
+    let title_slot = mutr.create_element(name("slot"), attr("name", "title"));
+    let inner = mutr.create_element(name("span"), Vec::new());
+    let default_slot = mutr.create_element(name("slot"), Vec::new());
+    mutr.append_children(style, &[style_text]);
+    mutr.append_children(shadow_root, &[style, title_slot, inner, default_slot]);
+    drop(mutr);
+    assert!(doc.shadow_stylesheets_dirty);
+    doc.resolve();
+    assert!(!doc.shadow_stylesheets_dirty);
+
+    assert_eq!(doc.assigned_nodes(title_slot), &[named]);
+    assert_eq!(doc.assigned_slot(unnamed), Some(default_slot));
+    assert_eq!(doc.flat_tree_parent(named), Some(title_slot));
+    assert_eq!(doc.flat_tree_parent(title_slot), Some(host));
+    assert_eq!(doc.containing_shadow_root(inner), Some(shadow_root));
+    assert_eq!(doc.retarget(inner, body), host);
+    assert_eq!(doc.retarget(inner, title_slot), inner);
+
+    // Shadow tree styles apply to the shadow tree but not to the (light DOM) children of the host
+    let display = |id: usize| doc.nodes[id].primary_styles().unwrap().clone_display();
+    assert_eq!(display(inner), Display::None);
+    assert_eq!(display(title_slot), Display::Block);
+    assert_ne!(display(named), Display::None);
+
+    // Removing the shadow tree's stylesheet rebuilds it's cascade data
+    doc.mutate().remove_node(style);
+    assert!(doc.shadow_stylesheets_dirty);
+    doc.resolve();
+    assert_ne!(
+        doc.nodes[inner].primary_styles().unwrap().clone_display(),
+        Display::None
+    );
+}
@@ -0,0 +1,362 @@
+//! Selection of the image source for `<img>` elements from their `src`, `srcset` and `sizes`
+//! attributes, and from `<source>` elements in a parent `<picture>` element.
+//!
+//! <https://html.spec.whatwg.org/multipage/images.html#selecting-an-image-source>
+
+use cssparser::{Parser, ParserInput};
+use markup5ever::local_name;
+use selectors::matching::QuirksMode;
+use style::media_queries::MediaList;
+use style::parser::ParserContext;
+use style::stylesheets::{CssRuleType, Origin};
+use style::values::specified::source_size_list::SourceSizeList;
+use style_traits::ParsingMode;
+
+use crate::BaseDocument;
+use crate::node::{ElementData, ImageSource};
+
+/// The descriptor of an image candidate in a `srcset` attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Descriptor {
+    /// A width descriptor (e.g. `400w`)
+    Width(f32),
+    /// A pixel density descriptor (e.g. `2x`)
+    Density(f32),
+    /// No descriptor (equivalent to `1x`)
+    None,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ImageCandidate<'a> {
+    url: &'a str,
+    descriptor: Descriptor,
+}
+
+/// Parse a `srcset` attribute. Invalid candidates are skipped.
+///
+/// <https://html.spec.whatwg.org/multipage/images.html#parsing-a-srcset-attribute>
+fn parse_srcset(srcset: &str) -> Vec<ImageCandidate<'_>> {
+    let mut candidates = Vec::new();
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+
+        let url_end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let (mut url, after_url) = rest.split_at(url_end);
+
+        // A URL which ends with a comma has no descriptors
+        let descriptors = if url.ends_with(',') {
+            url = url.trim_end_matches(',');
+            rest = after_url;
+            ""
+        } else {
+            // Descriptors continue until the next comma which is not inside parentheses
+            let mut depth = 0;
+            let end = after_url
+                .char_indices()
+                .find(|&(_, c)| {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        ',' if depth <= 0 => return true,
+                        _ => {}
+                    }
+                    false
+                })
+                .map_or(after_url.len(), |(idx, _)| idx);
+            rest = &after_url[end..];
+            &after_url[..end]
+        };
+
+        if let Some(descriptor) = parse_descriptors(descriptors) {
+            candidates.push(ImageCandidate { url, descriptor });
+        }
+    }
+}
+
+fn parse_descriptors(descriptors: &str) -> Option<Descriptor> {
+    let mut descriptor = Descriptor::None;
+    for token in descriptors.split_ascii_whitespace() {
+        let (value, kind) = token.split_at(token.len() - 1);
+        let parsed = match kind {
+            "w" => Descriptor::Width(value.parse::<u32>().ok().filter(|&w| w > 0)? as f32),
+            "x" => Descriptor::Density(value.parse::<f32>().ok().filter(|d| *d >= 0.0)?),
+            // Height descriptors are not used for image selection
+            "h" if value.parse::<u32>().is_ok() => continue,
+            _ => return None,
+        };
+        // Only one width or density descriptor is allowed
+        if descriptor != Descriptor::None {
+            return None;
+        }
+        descriptor = parsed;
+    }
+    Some(descriptor)
+}
+
+/// Whether the MIME type in a `<source type>` attribute is a format that we can decode
+pub(crate) fn is_supported_image_type(mime_type: &str) -> bool {
+    let mime_type = mime_type
+        .split(';')
+        .next()
+        .unwrap()
+        .trim()
+        .to_ascii_lowercase();
+    if cfg!(feature = "svg") && mime_type == "image/svg+xml" {
+        return true;
+    }
+    image::ImageFormat::from_mime_type(&mime_type).is_some_and(|format| format.reading_enabled())
+}
+
+impl BaseDocument {
+    /// Select the image source for an `<img>` element based on the current [`Viewport`](blitz_traits::shell::Viewport)
+    pub(crate) fn select_image_source(&self, img_id: usize) -> Option<ImageSource> {
+        let img = self.nodes[img_id].element_data()?;
+
+        // Use the first matching <source> element which precedes the <img> in a <picture>
+        let picture = self.nodes[img_id]
+            .parent
+            .map(|id| &self.nodes[id])
+            .filter(|parent| {
+                parent
+                    .data
+                    .is_element_with_tag_name(&local_name!("picture"))
+            });
+        if let Some(picture) = picture {
+            let sources = picture
+                .children
+                .iter()
+                .take_while(|&&id| id != img_id)
+                .filter_map(|&id| self.nodes[id].element_data())
+                .filter(|el| el.name.local == local_name!("source"));
+            for source in sources {
+                if let Some(source) = self.select_from_source_element(source) {
+                    return source;
+                }
+            }
+        }
+
+        let candidates = img
+            .attr(local_name!("srcset"))
+            .map(parse_srcset)
+            .unwrap_or_default();
+        let src = img.attr(local_name!("src")).filter(|src| !src.is_empty());
+        self.select_image_candidate(candidates, img.attr(local_name!("sizes")), src)
+    }
+
+    /// Returns `None` if the `<source>` does not match, or `Some(source)` if it does
+    fn select_from_source_element(&self, source: &ElementData) -> Option<Option<ImageSource>> {
+        let srcset = source.attr(local_name!("srcset"))?;
+        let candidates = parse_srcset(srcset);
+        if candidates.is_empty() {
+            return None;
+        }
+        if source
+            .attr(local_name!("media"))
+            .is_some_and(|media| !self.evaluate_media_query(media))
+        {
+            return None;
+        }
+        if source
+            .attr(local_name!("type"))
+            .is_some_and(|mime_type| !is_supported_image_type(mime_type))
+        {
+            return None;
+        }
+
+        let sizes = source.attr(local_name!("sizes"));
+        Some(self.select_image_candidate(candidates, sizes, None))
+    }
+
+    fn select_image_candidate<'a>(
+        &self,
+        mut candidates: Vec<ImageCandidate<'a>>,
+        sizes: Option<&str>,
+        src: Option<&'a str>,
+    ) -> Option<ImageSource> {
+        let has_width_descriptors = candidates
+            .iter()
+            .any(|c| matches!(c.descriptor, Descriptor::Width(_)));
+
+        // The `src` attribute is a 1x candidate unless `srcset` already contains one
+        if let Some(src) = src {
+            let has_1x_candidate = candidates.iter().any(|c| match c.descriptor {
+                Descriptor::Density(density) => density == 1.0,
+                Descriptor::None => true,
+                Descriptor::Width(_) => false,
+            });
+            if !has_width_descriptors && !has_1x_candidate {
+                candidates.push(ImageCandidate {
+                    url: src,
+                    descriptor: Descriptor::None,
+                });
+            }
+        }
+
+        // Width descriptors are converted to densities using the size the image will be displayed at
+        let source_size = if has_width_descriptors {
+            self.evaluate_source_size_list(sizes.unwrap_or(""))
+        } else {
+            0.0
+        };
+        let density = |candidate: &ImageCandidate| match candidate.descriptor {
+            Descriptor::Width(width) => width / source_size,
+            Descriptor::Density(density) => density,
+            Descriptor::None => 1.0,
+        };
+
+        // Use the lowest density candidate which is at least the device pixel ratio, or
+        // the highest density candidate if there isn't one.
+        let target_density = self.viewport.scale();
+        let (candidate, density) = candidates
+            .iter()
+            .map(|candidate| (candidate, density(candidate)))
+            .filter(|(_, density)| density.is_finite() && *density > 0.0)
+            .min_by(|(_, a), (_, b)| {
+                let a_is_enough = *a >= target_density;
+                let b_is_enough = *b >= target_density;
+                b_is_enough
+                    .cmp(&a_is_enough)
+                    .then_with(|| match a_is_enough {
+                        true => a.total_cmp(b),
+                        false => b.total_cmp(a),
+                    })
+            })?;
+
+        Some(ImageSource {
+            url: self.url.resolve_relative(candidate.url)?,
+            density,
+        })
+    }
+
+    pub(crate) fn parse_with_context<T>(
+        &self,
+        css: &str,
+        parse: impl FnOnce(&ParserContext, &mut Parser) -> T,
+    ) -> T {
+        let url_data = self.url.url_extra_data();
+        let context = ParserContext::new(
+            Origin::Author,
+            &url_data,
+            Some(CssRuleType::Style),
+            ParsingMode::DEFAULT,
+            QuirksMode::NoQuirks,
+            Default::default(),
+            None,
+            None,
+        );
+        let mut input = ParserInput::new(css);
+        parse(&context, &mut Parser::new(&mut input))
+    }
+
+    /// Evaluate a media query list (e.g. from a `<source media>` attribute) against the current viewport
+    pub(crate) fn evaluate_media_query(&self, media: &str) -> bool {
+        self.parse_with_context(media, MediaList::parse)
+            .evaluate(self.stylist.device(), QuirksMode::NoQuirks)
+    }
+
+    /// Evaluate a `sizes` attribute against the current viewport, returning the size in CSS pixels
+    fn evaluate_source_size_list(&self, sizes: &str) -> f32 {
+        self.parse_with_context(sizes, SourceSizeList::parse)
+            .evaluate(self.stylist.device(), QuirksMode::NoQuirks)
+            .to_f32_px()
+    }
+
+    /// Re-select the image source of `<img>` elements (e.g. after the viewport or scale factor has changed),
+    /// loading the newly selected image for any elements whose source has changed.
+    pub(crate) fn update_image_sources(&mut self) {
+        let responsive_images: Vec<usize> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| {
+                node.flags.is_in_document()
+                    && node.element_data().is_some_and(|el| {
+                        el.name.local == local_name!("img") && el.image_source.is_some()
+                    })
+            })
+            .map(|(id, _)| id)
+            .collect();
+
+        let mut mutr = self.mutate();
+        for id in responsive_images {
+            mutr.load_image(id);
+        }
+    }
+}
+
+#[test]
+fn parses_srcset() {
+    let candidates = parse_srcset(
+        " a.png, b.png 2x,c.png   400w , data:image/png;base64,AAA= 3x, bad.png 1q, d.png 1x 2x,e.png,,",
+    );
+    let expected = [
+        ("a.png", Descriptor::None),
+        ("b.png", Descriptor::Density(2.0)),
+        ("c.png", Descriptor::Width(400.0)),
+        ("data:image/png;base64,AAA=", Descriptor::Density(3.0)),
+        ("e.png", Descriptor::None),
+    ];
+    assert_eq!(
+        candidates,
+        expected.map(|(url, descriptor)| ImageCandidate { url, descriptor })
+    );
+}
+
+#[test]
+fn selects_image_for_device_pixel_ratio() {
+    use crate::DocumentConfig;
+    use blitz_traits::shell::{ColorScheme, Viewport};
+
+    let viewport = |scale| Viewport::new(800, 600, scale, ColorScheme::Light);
+    // Note: `BaseDocument::set_viewport` requires the document to have a root element
+    let set_viewport = |doc: &mut BaseDocument, viewport: Viewport| {
+        doc.viewport = viewport;
+        doc.set_stylist_device(crate::document::make_device(&doc.viewport));
+    };
+    let mut doc = BaseDocument::new(DocumentConfig {
+        base_url: Some("https://example.com/".into()),
+        viewport: Some(viewport(1.0)),
+        ..Default::default()
+    });
+
+    let select = |doc: &BaseDocument, srcset: &str, sizes: Option<&str>| {
+        let source =
+            doc.select_image_candidate(parse_srcset(srcset), sizes, Some("fallback.png"))?;
+        Some((source.url.path().to_string(), source.density))
+    };
+
+    let densities = "small.png 1x, large.png 2x";
+    assert_eq!(
+        select(&doc, densities, None),
+        Some(("/small.png".into(), 1.0))
+    );
+    set_viewport(&mut doc, viewport(2.0));
+    assert_eq!(
+        select(&doc, densities, None),
+        Some(("/large.png".into(), 2.0))
+    );
+    set_viewport(&mut doc, viewport(3.0));
+    assert_eq!(
+        select(&doc, densities, None),
+        Some(("/large.png".into(), 2.0))
+    );
+
+    // 400 CSS px at 2x requires an image at least 800px wide
+    let widths = "a.png 400w, b.png 800w, c.png 1600w";
+    set_viewport(&mut doc, viewport(2.0));
+    let sizes = Some("(max-width: 300px) 100vw, 400px");
+    assert_eq!(select(&doc, widths, sizes), Some(("/b.png".into(), 2.0)));
+    // The viewport is 300 CSS px wide, so the image is displayed at 100vw = 300px
+    set_viewport(&mut doc, Viewport::new(600, 600, 2.0, ColorScheme::Light));
+    assert_eq!(
+        select(&doc, widths, sizes),
+        Some(("/b.png".into(), 800.0 / 300.0))
+    );
+
+    assert_eq!(select(&doc, "", None), Some(("/fallback.png".into(), 1.0)));
+}
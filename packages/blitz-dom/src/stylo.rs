@@ -11,7 +11,7 @@ use crate::net::ImageHandler;
 use crate::node::NodeData;
 use crate::util::ImageType;
 use atomic_refcell::{AtomicRef, AtomicRefMut};
-use markup5ever::{LocalName, LocalNameStaticSet, Namespace, NamespaceStaticSet, local_name};
+use markup5ever::{LocalName, LocalNameStaticSet, Namespace, NamespaceStaticSet, local_name, ns};
 use selectors::{
     Element, OpaqueElement,
     attr::{AttrSelectorOperation, AttrSelectorOperator, NamespaceConstraint},
@@ -99,11 +99,19 @@ impl crate::document::BaseDocument {
                                 break;
                             }
 
-                            self.net_provider.fetch(
-                                doc_id,
-                                Request::get((**new_url).clone()),
-                                Box::new(ImageHandler::new(node_id, ImageType::Background(idx))),
+                            let mut handler = ImageHandler::new(
+                                node_id,
+                                ImageType::Background(idx),
+                                self.image_decoder.clone(),
                             );
+                            let mut request = Request::get((**new_url).clone())
+                                .with_destination(RequestDestination::Image)
+                                .with_initiator(node_id);
+                            if let Some(cache) = &self.resource_cache {
+                                handler = handler.with_cache(cache.clone(), (**new_url).clone());
+                                request = cache.revalidate(request);
+                            }
+                            self.net_provider.fetch(doc_id, request, Box::new(handler));
 
                             let bg_image_data = BackgroundImageData::new(new_url.clone());
                             Some(bg_image_data)
@@ -180,6 +188,8 @@ impl crate::document::BaseDocument {
     pub fn resolve_stylist(&mut self) {
         style::thread_state::enter(ThreadState::LAYOUT);
 
+        self.flush_shadow_stylesheets();
+
         let guard = &self.guard;
         let guards = StylesheetGuards {
             author: &guard.read(),
@@ -267,14 +277,16 @@ impl<'a> TShadowRoot for BlitzNode<'a> {
     }
 
     fn host(&self) -> <Self::ConcreteNode as TNode>::ConcreteElement {
-        todo!("Shadow roots not implemented")
+        let data = self.shadow_root_data().expect("Not a shadow root");
+        self.with(data.host)
     }
 
     fn style_data<'b>(&self) -> Option<&'b style::stylist::CascadeData>
     where
         Self: 'b,
     {
-        todo!("Shadow roots not implemented")
+        let node: &'b Node = self;
+        Some(&node.shadow_root_data()?.author_styles.data)
     }
 }
 
@@ -312,12 +324,17 @@ impl<'a> TNode for BlitzNode<'a> {
         true
     }
 
-    // I think this is the same as parent_node only in the cases when the direct parent is not a real element, forcing us
-    // to travel upwards
-    //
-    // For the sake of this demo, we're just going to return the parent node ann
+    // The parent in the flat tree: the slot the node is assigned to, or the shadow host for the children of a
+    // shadow root
     fn traversal_parent(&self) -> Option<Self::ConcreteElement> {
-        self.parent_node().and_then(|node| node.as_element())
+        if let Some(slot_id) = self.assigned_slot {
+            return Some(self.with(slot_id));
+        }
+        let parent = self.parent_node()?;
+        match parent.as_shadow_root() {
+            Some(shadow_root) => Some(shadow_root.host()),
+            None => parent.as_element(),
+        }
     }
 
     fn opaque(&self) -> OpaqueNode {
@@ -343,8 +360,10 @@ impl<'a> TNode for BlitzNode<'a> {
     }
 
     fn as_shadow_root(&self) -> Option<Self::ConcreteShadowRoot> {
-        // TODO: implement shadow DOM
-        None
+        match self.data {
+            NodeData::ShadowRoot(_) => Some(self),
+            _ => None,
+        }
     }
 }
 
@@ -359,15 +378,16 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn parent_element(&self) -> Option<Self> {
-        TElement::traversal_parent(self)
+        self.parent_node().and_then(|node| node.as_element())
     }
 
     fn parent_node_is_shadow_root(&self) -> bool {
-        false
+        self.parent_node()
+            .is_some_and(|node| node.as_shadow_root().is_some())
     }
 
     fn containing_shadow_host(&self) -> Option<Self> {
-        None
+        TElement::containing_shadow(self).map(|shadow_root| shadow_root.host())
     }
 
     fn is_pseudo_element(&self) -> bool {
@@ -557,7 +577,12 @@ impl selectors::Element for BlitzNode<'_> {
     }
 
     fn is_html_slot_element(&self) -> bool {
-        false
+        self.element_data()
+            .is_some_and(|data| data.name.local == local_name!("slot") && data.name.ns == ns!(html))
+    }
+
+    fn assigned_slot(&self) -> Option<Self> {
+        self.assigned_slot.map(|id| self.with(id))
     }
 
     fn has_id(
@@ -592,13 +617,23 @@ impl selectors::Element for BlitzNode<'_> {
 
     fn imported_part(
         &self,
-        _name: &<Self::Impl as selectors::SelectorImpl>::Identifier,
+        name: &<Self::Impl as selectors::SelectorImpl>::Identifier,
     ) -> Option<<Self::Impl as selectors::SelectorImpl>::Identifier> {
-        None
+        // The exportparts attribute is a comma-separated list of "inner" or "inner: outer" mappings
+        let exportparts = self.data.attr(local_name!("exportparts"))?;
+        exportparts.split(',').find_map(|mapping| {
+            let (inner, outer) = match mapping.split_once(':') {
+                Some((inner, outer)) => (inner.trim(), outer.trim()),
+                None => (mapping.trim(), mapping.trim()),
+            };
+            (!inner.is_empty() && *outer == **name).then(|| AtomIdent::from(inner))
+        })
     }
 
-    fn is_part(&self, _name: &<Self::Impl as selectors::SelectorImpl>::Identifier) -> bool {
-        false
+    fn is_part(&self, name: &<Self::Impl as selectors::SelectorImpl>::Identifier) -> bool {
+        self.data
+            .attr(local_name!("part"))
+            .is_some_and(|parts| parts.split_ascii_whitespace().any(|part| *part == **name))
     }
 
     fn is_empty(&self) -> bool {
@@ -607,8 +642,7 @@ impl selectors::Element for BlitzNode<'_> {
 
     fn is_root(&self) -> bool {
         self.parent_node()
-            .and_then(|parent| parent.parent_node())
-            .is_none()
+            .is_some_and(|parent| parent.as_document().is_some())
     }
 
     fn has_custom_state(
@@ -633,14 +667,13 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn implicit_scope_for_sheet_in_shadow_root(
-        _opaque_host: OpaqueElement,
+        opaque_host: OpaqueElement,
         _sheet_index: usize,
     ) -> Option<ImplicitScopeRoot> {
-        // We cannot currently implement this as we are using the NodeId as the OpaqueElement,
-        // and need a reference to the Slab to convert it back into an Element
-        //
-        // Luckily it is only needed for shadow dom.
-        todo!();
+        // We are using the NodeId as the OpaqueElement, and would need a reference to the Slab to convert it
+        // back into an Element to find the `<style>` element's parent. So we approximate the implicit scope
+        // root of all sheets in a shadow tree as the shadow root itself.
+        Some(ImplicitScopeRoot::ShadowHost(opaque_host))
     }
 
     fn traversal_children(&self) -> style::dom::LayoutIterator<Self::TraversalChildrenIterator> {
@@ -651,6 +684,10 @@ impl<'a> TElement for BlitzNode<'a> {
         })
     }
 
+    fn inheritance_parent(&self) -> Option<Self> {
+        TElement::traversal_parent(self)
+    }
+
     fn is_html_element(&self) -> bool {
         self.is_element()
     }
@@ -692,11 +729,11 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn has_part_attr(&self) -> bool {
-        false
+        self.data.has_attr(local_name!("part"))
     }
 
     fn exports_any_part(&self) -> bool {
-        false
+        self.data.has_attr(local_name!("exportparts"))
     }
 
     fn id(&self) -> Option<&style::Atom> {
@@ -819,10 +856,18 @@ impl<'a> TElement for BlitzNode<'a> {
     }
 
     fn shadow_root(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
-        None
+        let shadow_root_id = self.element_data()?.shadow_root?;
+        Some(self.with(shadow_root_id))
     }
 
     fn containing_shadow(&self) -> Option<<Self::ConcreteNode as TNode>::ConcreteShadowRoot> {
+        let mut node = self.parent_node();
+        while let Some(current) = node {
+            if current.as_shadow_root().is_some() {
+                return Some(current);
+            }
+            node = current.parent_node();
+        }
         None
     }
 
@@ -1077,7 +1122,7 @@ impl<'a> Iterator for Traverser<'a> {
     type Item = BlitzNode<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let node_id = self.parent.children.get(self.child_index)?;
+        let node_id = self.parent.flat_tree_children().get(self.child_index)?;
         let node = self.parent.with(*node_id);
 
         self.child_index += 1;
@@ -1102,7 +1147,7 @@ impl RegisteredSpeculativePainters for RegisteredPaintersImpl {
     }
 }
 
-use blitz_traits::net::Request;
+use blitz_traits::net::{Request, RequestDestination};
 use style::traversal::recalc_style_at;
 
 pub struct RecalcStyle<'a> {
@@ -59,13 +59,18 @@ impl Iterator for AncestorTraverser<'_> {
 }
 
 impl BaseDocument {
-    /// Collect the nodes into a chain by traversing upwards
+    /// Collect the nodes into a chain by traversing upwards through the flat tree
+    /// (so the chain passes through assigned slots and crosses shadow boundaries into shadow hosts)
     pub fn node_chain(&self, node_id: usize) -> Vec<usize> {
         let mut chain = Vec::with_capacity(16);
         chain.push(node_id);
-        chain.extend(
-            AncestorTraverser::new(self, node_id).filter(|id| self.nodes[*id].is_element()),
-        );
+        let mut current = node_id;
+        while let Some(parent_id) = self.flat_tree_parent(current) {
+            if self.nodes[parent_id].is_element() {
+                chain.push(parent_id);
+            }
+            current = parent_id;
+        }
         chain
     }
 
@@ -108,6 +113,35 @@ impl BaseDocument {
         self.nodes[node_id].children = children;
     }
 
+    /// Call `cb` for each of the node's children in the flat tree (see [`Node::flat_tree_children`])
+    pub fn iter_flat_tree_children_mut(
+        &mut self,
+        node_id: usize,
+        mut cb: impl FnMut(usize, &mut BaseDocument),
+    ) {
+        let children = self.nodes[node_id].flat_tree_children().to_vec();
+        for child_id in children {
+            cb(child_id, self);
+        }
+    }
+
+    /// Call `cb` for the node's `::before` pseudo-element, each of it's children in the flat tree, and it's
+    /// `::after` pseudo-element
+    pub fn iter_flat_tree_children_and_pseudos_mut(
+        &mut self,
+        node_id: usize,
+        mut cb: impl FnMut(usize, &mut BaseDocument),
+    ) {
+        if let Some(before_node_id) = self.nodes[node_id].before {
+            cb(before_node_id, self)
+        }
+        self.iter_flat_tree_children_mut(node_id, &mut cb);
+        if let Some(after_node_id) = self.nodes[node_id].after {
+            cb(after_node_id, self)
+        }
+    }
+
+    /// Call `cb` for the node and each of it's descendants (including the contents of any shadow trees)
     pub fn iter_subtree_mut(
         &mut self,
         node_id: usize,
@@ -120,6 +154,14 @@ impl BaseDocument {
             node_id: usize,
             cb: &mut impl FnMut(usize, &mut BaseDocument),
         ) {
+            let shadow_root = doc.nodes[node_id]
+                .element_data()
+                .and_then(|element| element.shadow_root);
+            if let Some(shadow_root_id) = shadow_root {
+                cb(shadow_root_id, doc);
+                iter_subtree_mut_inner(doc, shadow_root_id, cb);
+            }
+
             let children = std::mem::take(&mut doc.nodes[node_id].children);
             for child_id in children.iter().cloned() {
                 cb(child_id, doc);
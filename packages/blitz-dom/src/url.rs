@@ -17,8 +17,32 @@ impl DocumentUrl {
     }
 
     pub(crate) fn resolve_relative(&self, raw: &str) -> Option<url::Url> {
+        if let Some(base_url) = hierarchical_base_url(&self.base_url) {
+            return base_url.join(raw).ok();
+        }
         self.base_url.join(raw).ok()
     }
+
+    /// The fragment of `url` if it only differs from the document's URL by it's fragment (in which case following
+    /// a link to it should scroll to the fragment rather than navigate)
+    pub(crate) fn same_document_fragment<'a>(&self, url: &'a Url) -> Option<&'a str> {
+        let fragment = url.fragment()?;
+        let mut url_without_fragment = url.clone();
+        url_without_fragment.set_fragment(None);
+        let document_url = self.resolve_relative("")?;
+        (url_without_fragment == document_url).then_some(fragment)
+    }
+}
+
+/// URLs with custom schemes (e.g. `app:index.html`) that lack a `//` are "cannot-be-a-base" URLs which
+/// relative URLs cannot be resolved against. Treat them as hierarchical (`app:///index.html`) instead so that
+/// relative links in documents served by custom scheme handlers work.
+fn hierarchical_base_url(url: &Url) -> Option<Url> {
+    if !url.cannot_be_a_base() || matches!(url.scheme(), "data" | "about" | "blob" | "javascript") {
+        return None;
+    }
+    let path = url.path().trim_start_matches('/');
+    Url::parse(&format!("{}:///{}", url.scheme(), path)).ok()
 }
 
 impl Default for DocumentUrl {
@@ -51,3 +75,14 @@ impl Deref for DocumentUrl {
         &self.base_url
     }
 }
+
+#[test]
+fn resolve_against_custom_scheme() {
+    let base = DocumentUrl::from_str("app://ui/pages/index.html").unwrap();
+    let resolved = base.resolve_relative("../style.css").unwrap();
+    assert_eq!(resolved.as_str(), "app://ui/style.css");
+
+    let base = DocumentUrl::from_str("asset:pages/index.html").unwrap();
+    let resolved = base.resolve_relative("img/logo.png").unwrap();
+    assert_eq!(resolved.as_str(), "asset:///pages/img/logo.png");
+}
@@ -56,6 +56,8 @@ pub fn walk_tree(indent: usize, node: &Node) {
 
         NodeData::Comment => println!("<!-- COMMENT {id} -->"),
 
+        NodeData::ShadowRoot(_) => println!("#shadow-root {id}"),
+
         NodeData::AnonymousBlock(_) => println!("{id} AnonymousBlock"),
 
         NodeData::Element(data) => {
@@ -63,7 +65,7 @@ pub fn walk_tree(indent: usize, node: &Node) {
             for attr in data.attrs.iter() {
                 print!(" {}=\"{}\"", attr.name.local, attr.value);
             }
-            if !node.children.is_empty() {
+            if !node.children.is_empty() || data.shadow_root.is_some() {
                 println!(">");
             } else {
                 println!("/>");
@@ -76,6 +78,10 @@ pub fn walk_tree(indent: usize, node: &Node) {
           // NodeData::ProcessingInstruction { .. } => unreachable!(),
     }
 
+    if let Some(shadow_root_id) = node.element_data().and_then(|data| data.shadow_root) {
+        walk_tree(indent + 2, node.with(shadow_root_id));
+    }
+
     if !node.children.is_empty() {
         for child_id in node.children.iter() {
             walk_tree(indent + 2, node.with(*child_id));
@@ -0,0 +1,242 @@
+use std::borrow::Cow;
+
+use blitz_dom::encoding::{Encoding, encoding_from_content_type};
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE, WINDOWS_1252, X_USER_DEFINED};
+
+/// The number of bytes which are scanned for a `<meta charset>` declaration
+pub(crate) const PRESCAN_LENGTH: usize = 1024;
+
+/// Decode an HTML document to text, returning the text and the encoding that was used.
+///
+/// The encoding is determined by (in order of precedence): a byte order mark, the `charset` of the `content_type`
+/// (usually from the HTTP `Content-Type` header), and a `<meta charset>` or `<meta http-equiv="Content-Type">` declaration
+/// near the start of the document. If none of these are present then the document is decoded as UTF-8 if it is valid UTF-8,
+/// and as windows-1252 otherwise.
+pub fn decode_html<'a>(
+    bytes: &'a [u8],
+    content_type: Option<&str>,
+) -> (Cow<'a, str>, &'static Encoding) {
+    let encoding = sniff_html_encoding(bytes, content_type);
+    let (html, encoding, _had_errors) = encoding.decode(bytes);
+    (html, encoding)
+}
+
+/// Determine the encoding of an HTML document following (a simplified version of)
+/// <https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding>
+pub fn sniff_html_encoding(bytes: &[u8], content_type: Option<&str>) -> &'static Encoding {
+    if let Some((encoding, _bom_length)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+    if let Some(encoding) = content_type.and_then(encoding_from_content_type) {
+        return encoding;
+    }
+    if let Some(encoding) = prescan(&bytes[..bytes.len().min(PRESCAN_LENGTH)]) {
+        return encoding;
+    }
+    // A multi-byte character which is cut off at the end of the bytes is allowed so that the
+    // start of a document can be sniffed before the rest of it has been received.
+    match std::str::from_utf8(bytes) {
+        Ok(_) => UTF_8,
+        Err(err) if err.error_len().is_none() => UTF_8,
+        Err(_) => WINDOWS_1252,
+    }
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding>
+fn prescan(bytes: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            // The "--" of the closing "-->" may overlap with the opening "<!--"
+            pos += find(&rest[2..], b"-->").map_or(rest.len(), |idx| idx + 5);
+        } else if starts_with_ignore_case(rest, b"<meta")
+            && rest
+                .get(5)
+                .is_some_and(|&b| b.is_ascii_whitespace() || b == b'/')
+        {
+            pos += 5;
+            if let Some(encoding) = meta_encoding(bytes, &mut pos) {
+                return match encoding {
+                    encoding if encoding == UTF_16BE || encoding == UTF_16LE => Some(UTF_8),
+                    encoding if encoding == X_USER_DEFINED => Some(WINDOWS_1252),
+                    encoding => Some(encoding),
+                };
+            }
+        } else if rest.starts_with(b"<") && rest.get(1).is_some_and(|b| b.is_ascii_alphabetic()) {
+            // Skip the tag's name and attributes
+            pos += 1;
+            while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        } else if rest.starts_with(b"<") {
+            // Skip "</", "<!" and "<?" constructs
+            pos += find(rest, b">").map_or(rest.len(), |idx| idx + 1);
+        } else {
+            pos += 1;
+        }
+    }
+
+    None
+}
+
+/// Process the attributes of a `<meta>` tag, returning the encoding it declares (if any)
+fn meta_encoding(bytes: &[u8], pos: &mut usize) -> Option<&'static Encoding> {
+    let mut got_pragma = false;
+    let mut charset = None;
+    let mut content_charset = None;
+
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        match name.as_slice() {
+            b"http-equiv" => got_pragma |= value.eq_ignore_ascii_case(b"content-type"),
+            b"charset" if charset.is_none() && content_charset.is_none() => {
+                charset = Encoding::for_label(&value);
+            }
+            b"content" if charset.is_none() && content_charset.is_none() => {
+                content_charset = charset_from_meta_content(&value);
+            }
+            _ => {}
+        }
+    }
+
+    charset.or(content_charset.filter(|_| got_pragma))
+}
+
+/// <https://html.spec.whatwg.org/multipage/urls-and-fetching.html#algorithm-for-extracting-a-character-encoding-from-a-meta-element>
+fn charset_from_meta_content(content: &[u8]) -> Option<&'static Encoding> {
+    let mut pos = 0;
+    loop {
+        pos += find_ignore_case(&content[pos..], b"charset")? + 7;
+        while content.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            break;
+        }
+    }
+
+    pos += 1;
+    while content.get(pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        pos += 1;
+    }
+    let value = match content.get(pos)? {
+        &quote @ (b'"' | b'\'') => {
+            let rest = &content[pos + 1..];
+            &rest[..rest.iter().position(|&b| b == quote)?]
+        }
+        _ => {
+            let rest = &content[pos..];
+            let end = rest
+                .iter()
+                .position(|&b| b.is_ascii_whitespace() || b == b';')
+                .unwrap_or(rest.len());
+            &rest[..end]
+        }
+    };
+    Encoding::for_label(value)
+}
+
+/// <https://html.spec.whatwg.org/multipage/parsing.html#concept-get-attributes-when-sniffing>
+///
+/// Returns the (lowercased) name and value of the next attribute, or `None` once the end of the tag is reached.
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    while bytes
+        .get(*pos)
+        .is_some_and(|&b| b.is_ascii_whitespace() || b == b'/')
+    {
+        *pos += 1;
+    }
+    if *bytes.get(*pos)? == b'>' {
+        *pos += 1;
+        return None;
+    }
+
+    let mut name = Vec::new();
+    while let Some(&b) = bytes.get(*pos) {
+        if (b == b'=' && !name.is_empty()) || b.is_ascii_whitespace() || b == b'/' || b == b'>' {
+            break;
+        }
+        name.push(b.to_ascii_lowercase());
+        *pos += 1;
+    }
+
+    while bytes.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+    if bytes.get(*pos) != Some(&b'=') {
+        return Some((name, Vec::new()));
+    }
+    *pos += 1;
+    while bytes.get(*pos).is_some_and(|b| b.is_ascii_whitespace()) {
+        *pos += 1;
+    }
+
+    let mut value = Vec::new();
+    match bytes.get(*pos) {
+        Some(&quote @ (b'"' | b'\'')) => {
+            *pos += 1;
+            while let Some(&b) = bytes.get(*pos) {
+                *pos += 1;
+                if b == quote {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+            }
+        }
+        _ => {
+            while let Some(&b) = bytes.get(*pos) {
+                if b.is_ascii_whitespace() || b == b'>' {
+                    break;
+                }
+                value.push(b.to_ascii_lowercase());
+                *pos += 1;
+            }
+        }
+    }
+
+    Some((name, value))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn find_ignore_case(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle))
+}
+
+fn starts_with_ignore_case(bytes: &[u8], prefix: &[u8]) -> bool {
+    bytes.len() >= prefix.len() && bytes[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+#[test]
+fn sniffs_html_encoding() {
+    use encoding_rs::SHIFT_JIS;
+
+    let meta =
+        b"<!doctype html><!-- <meta charset=utf-8> --><html><head><meta charset=\"Shift_JIS\">";
+    assert_eq!(sniff_html_encoding(meta, None), SHIFT_JIS);
+    assert_eq!(
+        sniff_html_encoding(meta, Some("text/html; charset=windows-1252")),
+        WINDOWS_1252
+    );
+
+    let pragma = b"<head><meta http-equiv=Content-Type content='text/html; charset=iso-8859-1'>";
+    assert_eq!(sniff_html_encoding(pragma, None), WINDOWS_1252);
+    let no_pragma = b"<head><meta content='text/html; charset=shift_jis'>";
+    assert_eq!(sniff_html_encoding(no_pragma, None), UTF_8);
+
+    assert_eq!(
+        sniff_html_encoding(b"\xEF\xBB\xBF<meta charset=shift_jis>", None),
+        UTF_8
+    );
+    assert_eq!(sniff_html_encoding(b"<p>caf\xE9</p>", None), WINDOWS_1252);
+
+    let (html, _) = decode_html(b"<meta charset=shift_jis><p>\x93\xfa\x96\x7b</p>", None);
+    assert!(html.contains("日本"));
+}
@@ -0,0 +1,77 @@
+//! Parsing of HTML fragments into an existing document (for `innerHTML` and similar APIs)
+
+use blitz_dom::{DocumentMutator, HtmlParserProvider};
+use html5ever::tendril::TendrilSink;
+
+use crate::DocumentHtmlParser;
+use crate::html_sink::html_parse_opts;
+
+/// An [`HtmlParserProvider`] which parses fragments using html5ever. Documents created by
+/// [`HtmlDocument`](crate::HtmlDocument) use this provider by default.
+#[derive(Default)]
+pub struct HtmlProvider;
+
+impl HtmlParserProvider for HtmlProvider {
+    fn parse_fragment(
+        &self,
+        mutr: &mut DocumentMutator<'_>,
+        container_id: usize,
+        context_id: usize,
+        html: &str,
+    ) {
+        // The fragment parsing algorithm parses into a new document which contains a single `<html>` element
+        let document_id = mutr.create_comment_node();
+
+        let mut sink = DocumentHtmlParser::new(mutr.doc);
+        sink.document_id = document_id;
+        html5ever::driver::parse_fragment_for_element(
+            sink,
+            html_parse_opts(),
+            context_id,
+            false,
+            None,
+        )
+        .one(html);
+
+        if let Some(html_id) = mutr.last_child_id(document_id) {
+            mutr.reparent_children(html_id, container_id);
+        }
+        mutr.remove_and_drop_node(document_id);
+    }
+}
+
+#[test]
+fn sets_inner_and_outer_html() {
+    use crate::HtmlDocument;
+    use blitz_dom::{AdjacentPosition, DocumentConfig};
+
+    let mut doc = HtmlDocument::from_html(
+        "<table id=table></table><ul id=list><li id=item>One</li></ul>",
+        DocumentConfig::default(),
+    );
+    let table_id = doc.query_selector("#table").unwrap().unwrap();
+    let item_id = doc.query_selector("#item").unwrap().unwrap();
+    let list_id = doc.query_selector("#list").unwrap().unwrap();
+
+    // Table rows are only parsed correctly with the `<table>` as the context element
+    let mut mutr = doc.mutate();
+    mutr.set_inner_html(table_id, "<tr><td>Cell</td></tr>");
+    mutr.set_outer_html(item_id, "<li>Two</li>text<li id=three>Three</li>");
+    mutr.insert_adjacent_html(list_id, AdjacentPosition::AfterBegin, "<li>First</li>");
+    mutr.insert_adjacent_html(list_id, AdjacentPosition::AfterEnd, "<p>After</p>");
+    drop(mutr);
+
+    assert_eq!(
+        doc.get_node(table_id).unwrap().outer_html(),
+        "<table id=\"table\"><tbody><tr><td>Cell</td></tr></tbody></table>"
+    );
+    assert_eq!(
+        doc.get_node(list_id).unwrap().outer_html(),
+        "<ul id=\"list\"><li>First</li><li>Two</li>text<li id=\"three\">Three</li></ul>"
+    );
+    assert!(doc.get_node(item_id).is_none());
+    assert!(doc.query_selector("#three").unwrap().is_some());
+    let list = doc.get_node(list_id).unwrap();
+    let next_id = list.forward(1).unwrap().id;
+    assert_eq!(doc.get_node(next_id).unwrap().outer_html(), "<p>After</p>");
+}
@@ -1,11 +1,18 @@
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::task::Context as TaskContext;
 
-use crate::DocumentHtmlParser;
+use crate::html_stream::{HtmlStreamReceiver, html_stream_channel};
+use crate::{
+    DocumentHtmlParser, HtmlProvider, HtmlStreamParser, HtmlStreamSender, decode_html, is_xhtml,
+};
 
 use blitz_dom::{BaseDocument, DEFAULT_CSS, Document, DocumentConfig};
 
 pub struct HtmlDocument {
     inner: BaseDocument,
+    /// The parser and the receiving end of the stream for documents which are still being streamed in
+    stream: Option<(HtmlStreamParser, HtmlStreamReceiver)>,
 }
 
 // Implement DocumentLike and required traits for HtmlDocument
@@ -26,6 +33,24 @@ impl From<HtmlDocument> for BaseDocument {
     }
 }
 impl Document for HtmlDocument {
+    fn poll(&mut self, task_context: Option<TaskContext>) -> bool {
+        let Some((parser, receiver)) = &mut self.stream else {
+            return false;
+        };
+
+        let (bytes, finished) = receiver.take(task_context.as_ref().map(|cx| cx.waker()));
+        if !bytes.is_empty() {
+            parser.feed(&mut self.inner, &bytes);
+        }
+        if finished {
+            let (parser, _) = self.stream.take().unwrap();
+            parser.finish(&mut self.inner);
+            self.inner.mark_content_loaded();
+        }
+
+        !bytes.is_empty() || finished
+    }
+
     fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
         self
     }
@@ -33,15 +58,65 @@ impl Document for HtmlDocument {
 
 impl HtmlDocument {
     /// Parse HTML (or XHTML) into an [`HtmlDocument`]
-    pub fn from_html(html: &str, mut config: DocumentConfig) -> Self {
+    pub fn from_html(html: &str, config: DocumentConfig) -> Self {
+        let mut doc = Self::create_base_document(config);
+        DocumentHtmlParser::parse_into_doc(&mut doc, html);
+        doc.mark_content_loaded();
+        HtmlDocument {
+            inner: doc,
+            stream: None,
+        }
+    }
+
+    /// Decode and parse HTML (or XHTML) bytes into an [`HtmlDocument`].
+    ///
+    /// The character encoding is detected from the bytes and the `content_type` (usually
+    /// the HTTP `Content-Type` header) as described in [`decode_html`], and whether the document
+    /// is XHTML as described in [`is_xhtml`].
+    pub fn from_html_bytes(
+        bytes: &[u8],
+        content_type: Option<&str>,
+        config: DocumentConfig,
+    ) -> Self {
+        let (html, encoding) = decode_html(bytes, content_type);
+        let mut doc = Self::create_base_document(config);
+        doc.set_encoding(encoding);
+        DocumentHtmlParser::parse_into_doc_as(&mut doc, &html, is_xhtml(content_type, bytes));
+        doc.mark_content_loaded();
+        HtmlDocument {
+            inner: doc,
+            stream: None,
+        }
+    }
+
+    /// Create an empty [`HtmlDocument`] into which HTML is parsed incrementally as it is sent through
+    /// the returned [`HtmlStreamSender`] (e.g. as it is received from the network).
+    ///
+    /// Chunks are parsed when the document is [polled](Document::poll), so the document can be painted
+    /// while the rest of it is still loading. `DOMContentLoaded` is fired once the sender is dropped.
+    /// See [`HtmlStreamParser`] for details.
+    pub fn from_html_stream(
+        content_type: Option<&str>,
+        config: DocumentConfig,
+    ) -> (Self, HtmlStreamSender) {
+        let (sender, receiver) = html_stream_channel();
+        let doc = HtmlDocument {
+            inner: Self::create_base_document(config),
+            stream: Some((HtmlStreamParser::new(content_type), receiver)),
+        };
+        (doc, sender)
+    }
+
+    fn create_base_document(mut config: DocumentConfig) -> BaseDocument {
         if let Some(ss) = &mut config.ua_stylesheets {
             if !ss.iter().any(|s| s == DEFAULT_CSS) {
                 ss.push(String::from(DEFAULT_CSS));
             }
         }
-        let mut doc = BaseDocument::new(config);
-        DocumentHtmlParser::parse_into_doc(&mut doc, html);
-        HtmlDocument { inner: doc }
+        if config.html_parser_provider.is_none() {
+            config.html_parser_provider = Some(Arc::new(HtmlProvider));
+        }
+        BaseDocument::new(config)
     }
 
     /// Convert the [`HtmlDocument`] into it's inner [`BaseDocument`]
@@ -7,7 +7,7 @@ use std::borrow::Cow;
 use std::cell::{Cell, Ref, RefCell, RefMut};
 
 use blitz_dom::node::Attribute;
-use blitz_dom::{BaseDocument, DocumentMutator};
+use blitz_dom::{BaseDocument, DocumentMutator, ShadowRootMode, local_name};
 use html5ever::{
     QualName,
     tendril::{StrTendril, TendrilSink},
@@ -16,13 +16,98 @@ use html5ever::{
 
 /// Convert an html5ever Attribute which uses tendril for its value to a blitz Attribute
 /// which uses String.
-fn html5ever_to_blitz_attr(attr: html5ever::Attribute) -> Attribute {
+pub(crate) fn html5ever_to_blitz_attr(attr: html5ever::Attribute) -> Attribute {
     Attribute {
         name: attr.name,
         value: attr.value.to_string(),
     }
 }
 
+/// Whether a document should be parsed as XHTML, judging by its `content_type` (usually the HTTP `Content-Type`
+/// header) and the start of its `bytes` (an XML declaration or an XHTML doctype on the first line)
+pub fn is_xhtml(content_type: Option<&str>, bytes: &[u8]) -> bool {
+    let mime_type = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(str::trim);
+    if mime_type.is_some_and(|mime| mime.eq_ignore_ascii_case("application/xhtml+xml")) {
+        return true;
+    }
+
+    let bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    first_line.starts_with("<?xml")
+        || first_line.starts_with("<!DOCTYPE")
+            && (first_line.contains("XHTML") || first_line.contains("xhtml"))
+}
+
+pub(crate) fn html_parse_opts() -> ParseOpts {
+    ParseOpts {
+        tokenizer: TokenizerOpts::default(),
+        tree_builder: TreeBuilderOpts {
+            exact_errors: false,
+            scripting_enabled: false, // Enables parsing of <noscript> tags
+            iframe_srcdoc: false,
+            drop_doctype: true,
+            quirks_mode: QuirksMode::NoQuirks,
+        },
+    }
+}
+
+/// The mode of the shadow root declared by a `<template shadowrootmode>` element (if any)
+pub(crate) fn declarative_shadow_root_mode(
+    attrs: &[html5ever::Attribute],
+) -> Option<ShadowRootMode> {
+    let attr = attrs
+        .iter()
+        .find(|attr| attr.name.local == local_name!("shadowrootmode"))?;
+    match attr.value.as_ref() {
+        "open" => Some(ShadowRootMode::Open),
+        "closed" => Some(ShadowRootMode::Closed),
+        _ => None,
+    }
+}
+
+pub(crate) fn append(mutr: &mut DocumentMutator, parent_id: usize, child: NodeOrText<usize>) {
+    match child {
+        NodeOrText::AppendNode(id) => mutr.append_children(parent_id, &[id]),
+        // If content to append is text, first attempt to append it to the last child of parent.
+        // Else create a new text node and append it to the parent
+        NodeOrText::AppendText(text) => {
+            let has_appended = mutr
+                .last_child_id(parent_id)
+                .is_some_and(|id| mutr.append_text_to_node(id, &text).is_ok());
+            if !has_appended {
+                let new_child_id = mutr.create_text_node(&text);
+                mutr.append_children(parent_id, &[new_child_id]);
+            }
+        }
+    }
+}
+
+// Note: The tree builder promises we won't have a text node after the insertion point.
+// https://github.com/servo/html5ever/blob/main/rcdom/lib.rs#L338
+pub(crate) fn append_before_sibling(
+    mutr: &mut DocumentMutator,
+    sibling_id: usize,
+    new_node: NodeOrText<usize>,
+) {
+    match new_node {
+        NodeOrText::AppendNode(id) => mutr.insert_nodes_before(sibling_id, &[id]),
+        // If content to append is text, first attempt to append it to the node before sibling_node
+        // Else create a new text node and insert it before sibling_node
+        NodeOrText::AppendText(text) => {
+            let has_appended = mutr
+                .previous_sibling_id(sibling_id)
+                .is_some_and(|id| mutr.append_text_to_node(id, &text).is_ok());
+            if !has_appended {
+                let new_child_id = mutr.create_text_node(&text);
+                mutr.insert_nodes_before(sibling_id, &[new_child_id]);
+            }
+        }
+    }
+}
+
 pub struct DocumentHtmlParser<'doc> {
     document_mutator: RefCell<DocumentMutator<'doc>>,
 
@@ -32,6 +117,9 @@ pub struct DocumentHtmlParser<'doc> {
     /// The document's quirks mode.
     pub quirks_mode: Cell<QuirksMode>,
     pub is_xml: bool,
+
+    /// The node which is used as the document. This is the document's root node, except when parsing fragments.
+    pub(crate) document_id: usize,
 }
 
 impl<'doc> DocumentHtmlParser<'doc> {
@@ -49,19 +137,24 @@ impl DocumentHtmlParser<'_> {
             errors: RefCell::new(Vec::new()),
             quirks_mode: Cell::new(QuirksMode::NoQuirks),
             is_xml: false,
+            document_id: 0,
         }
     }
 
+    /// Parse HTML into the document, or XHTML if it starts with an XML declaration or an XHTML doctype
     pub fn parse_into_doc<'d>(doc: &'d mut BaseDocument, html: &str) -> &'d mut BaseDocument {
-        let mut sink = Self::new(doc);
+        Self::parse_into_doc_as(doc, html, is_xhtml(None, html.as_bytes()))
+    }
 
-        let is_xhtml_doc = html.starts_with("<?xml")
-            || html.starts_with("<!DOCTYPE") && {
-                let first_line = html.lines().next().unwrap();
-                first_line.contains("XHTML") || first_line.contains("xhtml")
-            };
+    /// Parse XHTML (with xml5ever) into the document if `xhtml` is true, or else HTML
+    pub(crate) fn parse_into_doc_as<'d>(
+        doc: &'d mut BaseDocument,
+        html: &str,
+        xhtml: bool,
+    ) -> &'d mut BaseDocument {
+        let mut sink = Self::new(doc);
 
-        if is_xhtml_doc {
+        if xhtml {
             // Parse as XHTML
             sink.is_xml = true;
             xml5ever::driver::parse_document(sink, Default::default())
@@ -71,17 +164,7 @@ impl DocumentHtmlParser<'_> {
         } else {
             // Parse as HTML
             sink.is_xml = false;
-            let opts = ParseOpts {
-                tokenizer: TokenizerOpts::default(),
-                tree_builder: TreeBuilderOpts {
-                    exact_errors: false,
-                    scripting_enabled: false, // Enables parsing of <noscript> tags
-                    iframe_srcdoc: false,
-                    drop_doctype: true,
-                    quirks_mode: QuirksMode::NoQuirks,
-                },
-            };
-            html5ever::parse_document(sink, opts)
+            html5ever::parse_document(sink, html_parse_opts())
                 .from_utf8()
                 .read_from(&mut html.as_bytes())
                 .unwrap();
@@ -114,7 +197,7 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
     }
 
     fn get_document(&self) -> Self::Handle {
-        0
+        self.document_id
     }
 
     fn elem_name<'a>(&'a self, target: &'a Self::Handle) -> Self::ElemName<'a> {
@@ -143,46 +226,11 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
     }
 
     fn append(&self, parent_id: &Self::Handle, child: NodeOrText<Self::Handle>) {
-        match child {
-            NodeOrText::AppendNode(id) => self.mutr().append_children(*parent_id, &[id]),
-            // If content to append is text, first attempt to append it to the last child of parent.
-            // Else create a new text node and append it to the parent
-            NodeOrText::AppendText(text) => {
-                let last_child_id = self.mutr().last_child_id(*parent_id);
-                let has_appended = if let Some(id) = last_child_id {
-                    self.mutr().append_text_to_node(id, &text).is_ok()
-                } else {
-                    false
-                };
-                if !has_appended {
-                    let new_child_id = self.mutr().create_text_node(&text);
-                    self.mutr().append_children(*parent_id, &[new_child_id]);
-                }
-            }
-        }
+        append(&mut self.mutr(), *parent_id, child);
     }
 
-    // Note: The tree builder promises we won't have a text node after the insertion point.
-    // https://github.com/servo/html5ever/blob/main/rcdom/lib.rs#L338
     fn append_before_sibling(&self, sibling_id: &Self::Handle, new_node: NodeOrText<Self::Handle>) {
-        match new_node {
-            NodeOrText::AppendNode(id) => self.mutr().insert_nodes_before(*sibling_id, &[id]),
-            // If content to append is text, first attempt to append it to the node before sibling_node
-            // Else create a new text node and insert it before sibling_node
-            NodeOrText::AppendText(text) => {
-                let previous_sibling_id = self.mutr().previous_sibling_id(*sibling_id);
-                let has_appended = if let Some(id) = previous_sibling_id {
-                    self.mutr().append_text_to_node(id, &text).is_ok()
-                } else {
-                    false
-                };
-                if !has_appended {
-                    let new_child_id = self.mutr().create_text_node(&text);
-                    self.mutr()
-                        .insert_nodes_before(*sibling_id, &[new_child_id]);
-                }
-            }
-        };
+        append_before_sibling(&mut self.mutr(), *sibling_id, new_node);
     }
 
     fn append_based_on_parent_node(
@@ -209,7 +257,32 @@ impl<'b> TreeSink for DocumentHtmlParser<'b> {
 
     fn get_template_contents(&self, target: &Self::Handle) -> Self::Handle {
         // TODO: implement templates properly. This should allow to function like regular elements.
-        *target
+        let mutr = self.mutr();
+        let element = mutr
+            .doc
+            .get_node(*target)
+            .and_then(|node| node.element_data());
+        element
+            .and_then(|element| element.template_contents)
+            .unwrap_or(*target)
+    }
+
+    fn attach_declarative_shadow(
+        &self,
+        location: &Self::Handle,
+        template: &Self::Handle,
+        attrs: &[html5ever::Attribute],
+    ) -> bool {
+        let Some(mode) = declarative_shadow_root_mode(attrs) else {
+            return false;
+        };
+        let mut mutr = self.mutr();
+        let Ok(shadow_root_id) = mutr.attach_shadow(*location, mode) else {
+            return false;
+        };
+        let template = mutr.doc.get_node_mut(*template).unwrap();
+        template.element_data_mut().unwrap().template_contents = Some(shadow_root_id);
+        true
     }
 
     fn same_node(&self, x: &Self::Handle, y: &Self::Handle) -> bool {
@@ -252,3 +325,17 @@ fn parses_some_html() {
 
     // Now our tree should have some nodes in it
 }
+
+#[test]
+fn detects_xhtml_documents() {
+    let xhtml_doctype = b"<!DOCTYPE html PUBLIC \"-//W3C//DTD XHTML 1.0 Strict//EN\"\n<html>";
+    assert!(is_xhtml(None, xhtml_doctype));
+    assert!(is_xhtml(None, b"\xEF\xBB\xBF<?xml version=\"1.0\"?>"));
+    assert!(is_xhtml(
+        Some("application/xhtml+xml; charset=utf-8"),
+        b"<html>"
+    ));
+
+    assert!(!is_xhtml(None, b"<!DOCTYPE html>\n<p>XHTML</p>"));
+    assert!(!is_xhtml(Some("text/html"), b"<html>"));
+}
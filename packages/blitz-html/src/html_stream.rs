@@ -0,0 +1,465 @@
+//! Incremental parsing of HTML which is received in chunks (e.g. from the network)
+
+use std::borrow::Cow;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+
+use blitz_dom::node::Attribute;
+use blitz_dom::{BaseDocument, ShadowRootMode, can_attach_shadow};
+use encoding_rs::{CoderResult, Decoder};
+use html5ever::{
+    QualName,
+    tendril::{StrTendril, TendrilSink},
+    tree_builder::{ElementFlags, NodeOrText, QuirksMode, TreeSink},
+};
+
+use crate::encoding::{PRESCAN_LENGTH, sniff_html_encoding};
+use crate::html_sink::{
+    append, append_before_sibling, declarative_shadow_root_mode, html_parse_opts,
+    html5ever_to_blitz_attr,
+};
+use crate::{DocumentHtmlParser, decode_html, is_xhtml};
+
+/// A parser which builds a document incrementally from chunks of HTML bytes as they are received.
+///
+/// Unlike [`DocumentHtmlParser`](crate::DocumentHtmlParser), the parser does not borrow the document
+/// between chunks, so the partially parsed document can be styled, laid out and painted while the
+/// rest of it is still loading. Nodes are added to the document through a [`DocumentMutator`](blitz_dom::DocumentMutator) which is
+/// flushed at the end of each chunk.
+///
+/// The character encoding is determined once the first [`PRESCAN_LENGTH`] bytes (or the whole document
+/// if it is shorter) have been received, as described in [`decode_html`](crate::decode_html). So is whether
+/// the document is XHTML (see [`is_xhtml`](crate::is_xhtml)). XHTML can't be parsed incrementally, so it is
+/// parsed once the whole document has been received.
+pub struct HtmlStreamParser {
+    parser: html5ever::Parser<StreamSink>,
+    content_type: Option<String>,
+    /// Whether the document is XHTML (in which case all of it is buffered)
+    xhtml: bool,
+    /// Bytes which are held back until enough have been received to determine the encoding
+    buffer: Vec<u8>,
+    decoder: Option<Decoder>,
+    /// The node id corresponding to each of the sink's handles
+    node_ids: Vec<usize>,
+}
+
+impl HtmlStreamParser {
+    /// Create a parser for a document with the specified `content_type` (usually the HTTP `Content-Type` header)
+    pub fn new(content_type: Option<&str>) -> Self {
+        Self {
+            parser: html5ever::parse_document(StreamSink::new(), html_parse_opts()),
+            content_type: content_type.map(String::from),
+            xhtml: false,
+            buffer: Vec::new(),
+            decoder: None,
+            node_ids: vec![0],
+        }
+    }
+
+    /// Parse a chunk of bytes, adding the resulting nodes to `doc`
+    pub fn feed(&mut self, doc: &mut BaseDocument, bytes: &[u8]) {
+        if self.decoder.is_some() {
+            self.decode(bytes, false);
+        } else {
+            self.buffer.extend_from_slice(bytes);
+            if self.xhtml || self.buffer.len() < PRESCAN_LENGTH || !self.start_decoding(doc) {
+                return;
+            }
+        }
+
+        let ops = self.parser.tokenizer.sink.sink.ops.take();
+        Self::apply_ops(doc, &mut self.node_ids, ops);
+    }
+
+    /// Parse any remaining input once the whole document has been received
+    pub fn finish(mut self, doc: &mut BaseDocument) {
+        if self.decoder.is_none() && !self.start_decoding(doc) {
+            let (xhtml, encoding) = decode_html(&self.buffer, self.content_type.as_deref());
+            doc.set_encoding(encoding);
+            DocumentHtmlParser::parse_into_doc_as(doc, &xhtml, true);
+            return;
+        }
+        self.decode(&[], true);
+
+        let sink = self.parser.finish();
+        Self::apply_ops(doc, &mut self.node_ids, sink.ops.into_inner());
+    }
+
+    /// Determine the encoding from the buffered bytes and start parsing them. Returns false (without
+    /// starting) if the document is XHTML.
+    fn start_decoding(&mut self, doc: &mut BaseDocument) -> bool {
+        if is_xhtml(self.content_type.as_deref(), &self.buffer) {
+            self.xhtml = true;
+            return false;
+        }
+
+        let encoding = sniff_html_encoding(&self.buffer, self.content_type.as_deref());
+        doc.set_encoding(encoding);
+        self.decoder = Some(encoding.new_decoder());
+
+        let buffer = mem::take(&mut self.buffer);
+        self.decode(&buffer, false);
+        true
+    }
+
+    fn decode(&mut self, mut bytes: &[u8], last: bool) {
+        let decoder = self.decoder.as_mut().unwrap();
+        let mut text = String::new();
+        loop {
+            let capacity = decoder.max_utf8_buffer_length(bytes.len());
+            text.reserve(capacity.unwrap_or(bytes.len()));
+            let (result, read, _had_errors) = decoder.decode_to_string(bytes, &mut text, last);
+            bytes = &bytes[read..];
+            if result == CoderResult::InputEmpty {
+                break;
+            }
+        }
+
+        if !text.is_empty() {
+            self.parser.process(StrTendril::from(text));
+        }
+    }
+
+    fn apply_ops(doc: &mut BaseDocument, node_ids: &mut Vec<usize>, ops: Vec<TreeOp>) {
+        if ops.is_empty() {
+            return;
+        }
+
+        let mut mutr = doc.mutate();
+        for op in ops {
+            match op {
+                TreeOp::CreateElement(name, attrs) => {
+                    node_ids.push(mutr.create_element(name, attrs));
+                }
+                TreeOp::CreateComment => node_ids.push(mutr.create_comment_node()),
+                TreeOp::AttachShadow(host, mode) => {
+                    // The sink only records shadow roots which can be attached, but fall back to a
+                    // (detached) comment node so that handles stay in sync with node ids regardless
+                    let shadow_root_id = mutr
+                        .attach_shadow(node_ids[host], mode)
+                        .unwrap_or_else(|_| mutr.create_comment_node());
+                    node_ids.push(shadow_root_id);
+                }
+                TreeOp::Append(parent, child) => {
+                    append(&mut mutr, node_ids[parent], map_child(node_ids, child));
+                }
+                TreeOp::AppendBeforeSibling(sibling, child) => {
+                    let child = map_child(node_ids, child);
+                    append_before_sibling(&mut mutr, node_ids[sibling], child);
+                }
+                TreeOp::AppendBasedOnParentNode(element, prev_element, child) => {
+                    let child = map_child(node_ids, child);
+                    if mutr.node_has_parent(node_ids[element]) {
+                        append_before_sibling(&mut mutr, node_ids[element], child);
+                    } else {
+                        append(&mut mutr, node_ids[prev_element], child);
+                    }
+                }
+                TreeOp::AddAttrsIfMissing(target, attrs) => {
+                    mutr.add_attrs_if_missing(node_ids[target], attrs);
+                }
+                TreeOp::RemoveFromParent(target) => mutr.remove_node(node_ids[target]),
+                TreeOp::ReparentChildren(old_parent, new_parent) => {
+                    mutr.reparent_children(node_ids[old_parent], node_ids[new_parent]);
+                }
+            }
+        }
+    }
+}
+
+fn map_child(node_ids: &[usize], child: NodeOrText<usize>) -> NodeOrText<usize> {
+    match child {
+        NodeOrText::AppendNode(handle) => NodeOrText::AppendNode(node_ids[handle]),
+        NodeOrText::AppendText(text) => NodeOrText::AppendText(text),
+    }
+}
+
+/// A tree operation recorded by [`StreamSink`]. Nodes are referred to by the sink's handles.
+enum TreeOp {
+    CreateElement(QualName, Vec<Attribute>),
+    CreateComment,
+    AttachShadow(usize, ShadowRootMode),
+    Append(usize, NodeOrText<usize>),
+    AppendBeforeSibling(usize, NodeOrText<usize>),
+    AppendBasedOnParentNode(usize, usize, NodeOrText<usize>),
+    AddAttrsIfMissing(usize, Vec<Attribute>),
+    RemoveFromParent(usize),
+    ReparentChildren(usize, usize),
+}
+
+/// A [`TreeSink`] which records tree operations to be applied to the document after each chunk,
+/// rather than applying them directly (which would require borrowing the document for the whole parse).
+///
+/// Handles are allocated sequentially in the order that nodes are created, so they can be mapped
+/// to node ids as the operations are applied.
+struct StreamSink {
+    ops: RefCell<Vec<TreeOp>>,
+    /// The name of each handle's element (`None` for the document, comments and shadow roots)
+    names: RefCell<Vec<Option<QualName>>>,
+    /// Map from the handles of declarative shadow root `<template>`s to the handles of their shadow roots
+    template_contents: RefCell<HashMap<usize, usize>>,
+    /// The handles of elements which have a shadow root attached
+    shadow_hosts: RefCell<HashSet<usize>>,
+}
+
+impl StreamSink {
+    fn new() -> Self {
+        Self {
+            ops: RefCell::new(Vec::new()),
+            names: RefCell::new(vec![None]),
+            template_contents: RefCell::new(HashMap::new()),
+            shadow_hosts: RefCell::new(HashSet::new()),
+        }
+    }
+
+    fn create_node(&self, name: Option<QualName>, op: TreeOp) -> usize {
+        let mut names = self.names.borrow_mut();
+        names.push(name);
+        self.ops.borrow_mut().push(op);
+        names.len() - 1
+    }
+
+    fn push_op(&self, op: TreeOp) {
+        self.ops.borrow_mut().push(op);
+    }
+}
+
+impl TreeSink for StreamSink {
+    type Output = Self;
+
+    type Handle = usize;
+
+    type ElemName<'a>
+        = Ref<'a, QualName>
+    where
+        Self: 'a;
+
+    fn finish(self) -> Self::Output {
+        self
+    }
+
+    fn parse_error(&self, _msg: Cow<'static, str>) {}
+
+    fn get_document(&self) -> Self::Handle {
+        0
+    }
+
+    fn elem_name<'a>(&'a self, target: &'a Self::Handle) -> Self::ElemName<'a> {
+        Ref::map(self.names.borrow(), |names| {
+            names[*target]
+                .as_ref()
+                .expect("TreeSink::elem_name called on a node which is not an element!")
+        })
+    }
+
+    fn create_element(
+        &self,
+        name: QualName,
+        attrs: Vec<html5ever::Attribute>,
+        _flags: ElementFlags,
+    ) -> Self::Handle {
+        let attrs = attrs.into_iter().map(html5ever_to_blitz_attr).collect();
+        self.create_node(Some(name.clone()), TreeOp::CreateElement(name, attrs))
+    }
+
+    fn create_comment(&self, _text: StrTendril) -> Self::Handle {
+        self.create_node(None, TreeOp::CreateComment)
+    }
+
+    fn create_pi(&self, _target: StrTendril, _data: StrTendril) -> Self::Handle {
+        self.create_node(None, TreeOp::CreateComment)
+    }
+
+    fn append(&self, parent: &Self::Handle, child: NodeOrText<Self::Handle>) {
+        self.push_op(TreeOp::Append(*parent, child));
+    }
+
+    fn append_before_sibling(&self, sibling: &Self::Handle, new_node: NodeOrText<Self::Handle>) {
+        self.push_op(TreeOp::AppendBeforeSibling(*sibling, new_node));
+    }
+
+    fn append_based_on_parent_node(
+        &self,
+        element: &Self::Handle,
+        prev_element: &Self::Handle,
+        child: NodeOrText<Self::Handle>,
+    ) {
+        self.push_op(TreeOp::AppendBasedOnParentNode(
+            *element,
+            *prev_element,
+            child,
+        ));
+    }
+
+    fn append_doctype_to_document(
+        &self,
+        _name: StrTendril,
+        _public_id: StrTendril,
+        _system_id: StrTendril,
+    ) {
+        // Ignore. We don't care about the DOCTYPE for now.
+    }
+
+    fn get_template_contents(&self, target: &Self::Handle) -> Self::Handle {
+        let template_contents = self.template_contents.borrow();
+        template_contents.get(target).copied().unwrap_or(*target)
+    }
+
+    fn attach_declarative_shadow(
+        &self,
+        location: &Self::Handle,
+        template: &Self::Handle,
+        attrs: &[html5ever::Attribute],
+    ) -> bool {
+        let Some(mode) = declarative_shadow_root_mode(attrs) else {
+            return false;
+        };
+        let is_valid_host = self.names.borrow()[*location]
+            .as_ref()
+            .is_some_and(can_attach_shadow);
+        if !is_valid_host || !self.shadow_hosts.borrow_mut().insert(*location) {
+            return false;
+        }
+
+        let shadow_root = self.create_node(None, TreeOp::AttachShadow(*location, mode));
+        self.template_contents
+            .borrow_mut()
+            .insert(*template, shadow_root);
+        true
+    }
+
+    fn same_node(&self, x: &Self::Handle, y: &Self::Handle) -> bool {
+        x == y
+    }
+
+    fn set_quirks_mode(&self, _mode: QuirksMode) {}
+
+    fn add_attrs_if_missing(&self, target: &Self::Handle, attrs: Vec<html5ever::Attribute>) {
+        let attrs = attrs.into_iter().map(html5ever_to_blitz_attr).collect();
+        self.push_op(TreeOp::AddAttrsIfMissing(*target, attrs));
+    }
+
+    fn remove_from_parent(&self, target: &Self::Handle) {
+        self.push_op(TreeOp::RemoveFromParent(*target));
+    }
+
+    fn reparent_children(&self, old_parent: &Self::Handle, new_parent: &Self::Handle) {
+        self.push_op(TreeOp::ReparentChildren(*old_parent, *new_parent));
+    }
+}
+
+#[derive(Default)]
+struct StreamQueue {
+    bytes: Vec<u8>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// Sends chunks of HTML to a document created with [`HtmlDocument::from_html_stream`](crate::HtmlDocument::from_html_stream).
+///
+/// Chunks may be sent from any thread, and are parsed the next time the document is polled.
+/// The document is finished (and `DOMContentLoaded` is fired) once the sender is dropped.
+pub struct HtmlStreamSender {
+    queue: Arc<Mutex<StreamQueue>>,
+}
+
+impl HtmlStreamSender {
+    /// Send a chunk of bytes to the document
+    pub fn send(&self, bytes: &[u8]) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.bytes.extend_from_slice(bytes);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Signal that the whole document has been sent. Equivalent to dropping the sender.
+    pub fn finish(self) {}
+}
+
+impl Drop for HtmlStreamSender {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.finished = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct HtmlStreamReceiver {
+    queue: Arc<Mutex<StreamQueue>>,
+}
+
+impl HtmlStreamReceiver {
+    /// Take the bytes that have been received so far, and whether the stream has finished.
+    ///
+    /// If the stream has not finished then the `waker` (if any) will be woken when more bytes are sent.
+    pub(crate) fn take(&self, waker: Option<&Waker>) -> (Vec<u8>, bool) {
+        let mut queue = self.queue.lock().unwrap();
+        if !queue.finished {
+            queue.waker = waker.cloned();
+        }
+        (mem::take(&mut queue.bytes), queue.finished)
+    }
+}
+
+pub(crate) fn html_stream_channel() -> (HtmlStreamSender, HtmlStreamReceiver) {
+    let queue = Arc::new(Mutex::new(StreamQueue::default()));
+    let sender = HtmlStreamSender {
+        queue: queue.clone(),
+    };
+    (sender, HtmlStreamReceiver { queue })
+}
+
+#[test]
+fn parses_html_in_chunks() {
+    use blitz_dom::DocumentConfig;
+
+    let html = "<!DOCTYPE html><html><head><title>Report</title><style>p { color: red }</style></head>\
+        <body><h1>caf\u{e9}</h1><table><tr><td>1</td></tr>stray</table><p>a<b>b<i>c</b>d</i></p></body></html>";
+
+    let mut expected = BaseDocument::new(DocumentConfig::default());
+    crate::DocumentHtmlParser::parse_into_doc(&mut expected, html);
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let mut parser = HtmlStreamParser::new(Some("text/html; charset=utf-8"));
+    // Use small chunks (which split the multi-byte "é") and force decoding to start early
+    for chunk in html.as_bytes().chunks(7) {
+        parser.feed(&mut doc, chunk);
+        if parser.decoder.is_none() && parser.buffer.len() > 20 {
+            parser.start_decoding(&mut doc);
+        }
+    }
+    parser.finish(&mut doc);
+
+    let html = doc.root_element().outer_html();
+    assert_eq!(html, expected.root_element().outer_html());
+    assert!(html.contains("<h1>café</h1>"));
+}
+
+#[test]
+fn parses_xhtml_once_complete() {
+    use blitz_dom::DocumentConfig;
+
+    let xhtml = "<html xmlns=\"http://www.w3.org/1999/xhtml\"><body><p/><span>caf\u{e9}</span></body></html>";
+
+    let mut doc = BaseDocument::new(DocumentConfig::default());
+    let mut parser = HtmlStreamParser::new(Some("application/xhtml+xml"));
+    for chunk in xhtml.as_bytes().chunks(7) {
+        parser.feed(&mut doc, chunk);
+        if parser.decoder.is_none() && parser.buffer.len() > 20 {
+            assert!(!parser.start_decoding(&mut doc));
+        }
+    }
+    assert!(parser.xhtml);
+    assert_eq!(doc.root_node().children.len(), 0);
+    parser.finish(&mut doc);
+
+    // `<p/>` is self-closing in XHTML (rather than containing the `<span>`)
+    let html = doc.root_element().outer_html();
+    assert!(html.contains("<p /><span>café</span>"), "{html}");
+}
@@ -1,5 +1,11 @@
+mod encoding;
+mod fragment;
 mod html_document;
 mod html_sink;
+mod html_stream;
 
+pub use encoding::{decode_html, sniff_html_encoding};
+pub use fragment::HtmlProvider;
 pub use html_document::HtmlDocument;
-pub use html_sink::DocumentHtmlParser;
+pub use html_sink::{DocumentHtmlParser, is_xhtml};
+pub use html_stream::{HtmlStreamParser, HtmlStreamSender};
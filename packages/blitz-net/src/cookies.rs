@@ -0,0 +1,253 @@
+//! A cookie jar which can be inspected, modified, and saved to and loaded from disk
+
+use blitz_traits::net::{Bytes, Url};
+use cookie_store::{CookieExpiration, CookieStore};
+use reqwest::header::HeaderValue;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+/// A cookie stored in a [`CookieJar`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// The domain the cookie belongs to (without a leading `.`)
+    pub domain: String,
+    /// Whether the cookie is only sent to `domain` itself (rather than also to it's subdomains)
+    pub host_only: bool,
+    pub path: String,
+    /// When the cookie expires. `None` for session cookies (which expire when the jar is dropped).
+    pub expires: Option<SystemTime>,
+    pub secure: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    fn from_stored(cookie: &cookie_store::Cookie<'static>) -> Self {
+        Self {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            domain: String::from(&cookie.domain),
+            host_only: matches!(cookie.domain, cookie_store::CookieDomain::HostOnly(_)),
+            path: String::from(&cookie.path),
+            expires: match cookie.expires {
+                CookieExpiration::AtUtc(time) => Some(SystemTime::from(time)),
+                CookieExpiration::SessionEnd => None,
+            },
+            secure: cookie.secure().unwrap_or(false),
+            http_only: cookie.http_only().unwrap_or(false),
+        }
+    }
+}
+
+/// The cookie jar used by a [`Provider`](crate::Provider) (with the `cookies` feature enabled).
+///
+/// Cookies set by responses are stored in the jar and sent with subsequent requests. Embedders can list, set
+/// and remove cookies, and persist the jar between sessions with [`save`](Self::save) and [`load`](Self::load).
+/// By default session cookies (those without an `Expires` or `Max-Age` attribute) are not saved, as (like in
+/// browsers) they only last for the current session. Servers often send login cookies without an expiry, so to
+/// keep users logged in across restarts enable [`with_session_cookies_saved`](Self::with_session_cookies_saved).
+///
+/// Handles are cheap to clone, and may be shared between several providers.
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    store: Arc<RwLock<CookieStore>>,
+    save_session_cookies: bool,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also save session cookies with [`save`](Self::save) and [`to_json`](Self::to_json), so that they are kept
+    /// when the jar is loaded again (in the same way as browsers which restore the previous session)
+    pub fn with_session_cookies_saved(mut self, save_session_cookies: bool) -> Self {
+        self.save_session_cookies = save_session_cookies;
+        self
+    }
+
+    /// Load a jar from JSON created by [`to_json`](Self::to_json). Expired cookies are skipped.
+    pub fn from_json(json: &str) -> Result<Self, CookieJarError> {
+        let store =
+            cookie_store::serde::json::load(json.as_bytes()).map_err(CookieJarError::Json)?;
+        Ok(Self {
+            store: Arc::new(RwLock::new(store)),
+            save_session_cookies: false,
+        })
+    }
+
+    /// Serialize the (unexpired, persistent) cookies in the jar to JSON. Session cookies are only included if
+    /// enabled with [`with_session_cookies_saved`](Self::with_session_cookies_saved).
+    pub fn to_json(&self) -> Result<String, CookieJarError> {
+        let store = self.store.read().unwrap();
+        let mut json = Vec::new();
+        if self.save_session_cookies {
+            // Expired cookies are also included, but they are skipped when the JSON is loaded
+            cookie_store::serde::json::save_incl_expired_and_nonpersistent(&store, &mut json)
+        } else {
+            cookie_store::serde::json::save(&store, &mut json)
+        }
+        .map_err(CookieJarError::Json)?;
+        Ok(String::from_utf8(json).expect("serde_json produces UTF-8"))
+    }
+
+    /// Load a jar from a file on disk
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, CookieJarError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// Save the jar to a file on disk (see [`to_json`](Self::to_json) for which cookies are saved)
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CookieJarError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    /// All of the (unexpired) cookies in the jar
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let store = self.store.read().unwrap();
+        store.iter_unexpired().map(Cookie::from_stored).collect()
+    }
+
+    /// The (unexpired) cookies which belong to `domain`. Cookies belonging to a parent domain (which
+    /// would also be sent to `domain`) are not included: use [`cookies_for_url`](Self::cookies_for_url) for that.
+    pub fn cookies_for_domain(&self, domain: &str) -> Vec<Cookie> {
+        let domain = normalize_domain(domain);
+        self.cookies()
+            .into_iter()
+            .filter(|cookie| cookie.domain == domain)
+            .collect()
+    }
+
+    /// The cookies which would be sent with a request to `url`
+    pub fn cookies_for_url(&self, url: &Url) -> Vec<Cookie> {
+        let store = self.store.read().unwrap();
+        store
+            .matches(url)
+            .into_iter()
+            .map(Cookie::from_stored)
+            .collect()
+    }
+
+    /// Set a cookie as if it was received in a `Set-Cookie` header (e.g. `"session=abc; Max-Age=3600"`)
+    /// in a response from `url`
+    pub fn set(&self, url: &Url, set_cookie: &str) -> Result<(), CookieJarError> {
+        self.store
+            .write()
+            .unwrap()
+            .parse(set_cookie, url)
+            .map_err(|err| CookieJarError::InvalidCookie(err.to_string()))?;
+        Ok(())
+    }
+
+    /// Remove a cookie, returning whether it was in the jar
+    pub fn remove(&self, domain: &str, path: &str, name: &str) -> bool {
+        let domain = normalize_domain(domain);
+        let mut store = self.store.write().unwrap();
+        store.remove(&domain, path, name).is_some()
+    }
+
+    /// Remove all of the cookies which belong to `domain`
+    pub fn clear_domain(&self, domain: &str) {
+        for cookie in self.cookies_for_domain(domain) {
+            self.remove(&cookie.domain, &cookie.path, &cookie.name);
+        }
+    }
+
+    /// Remove all of the cookies in the jar
+    pub fn clear(&self) {
+        self.store.write().unwrap().clear();
+    }
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim_start_matches('.').to_ascii_lowercase()
+}
+
+impl reqwest::cookie::CookieStore for CookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies = cookie_headers
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| cookie_store::RawCookie::parse(value.to_string()).ok());
+        self.store
+            .write()
+            .unwrap()
+            .store_response_cookies(cookies, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let header = self
+            .store
+            .read()
+            .unwrap()
+            .get_request_values(url)
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if header.is_empty() {
+            return None;
+        }
+        HeaderValue::from_maybe_shared(Bytes::from(header)).ok()
+    }
+}
+
+#[derive(Debug)]
+pub enum CookieJarError {
+    Io(std::io::Error),
+    Json(Box<dyn std::error::Error + Send + Sync>),
+    /// A cookie passed to [`CookieJar::set`] could not be parsed, or was rejected (e.g. because it's domain
+    /// doesn't match the URL)
+    InvalidCookie(String),
+}
+
+impl From<std::io::Error> for CookieJarError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+#[test]
+fn saves_and_loads_persistent_cookies() {
+    let url = Url::parse("https://intranet.example.com/login").unwrap();
+    let jar = CookieJar::new();
+    jar.set(
+        &url,
+        "session=abc; Max-Age=3600; Domain=example.com; Secure",
+    )
+    .unwrap();
+    jar.set(&url, "theme=dark; Path=/").unwrap();
+    jar.set(&url, "temporary=1").unwrap();
+    assert!(jar.set(&url, "other=1; Domain=example.org").is_err());
+
+    let cookies = jar.cookies_for_domain("example.com");
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].value, "abc");
+    assert!(cookies[0].secure && !cookies[0].host_only);
+    assert!(cookies[0].expires.is_some());
+    assert_eq!(jar.cookies_for_domain("intranet.example.com").len(), 2);
+    assert_eq!(
+        jar.cookies_for_url(&Url::parse("https://www.example.com/").unwrap())
+            .len(),
+        1
+    );
+
+    // Session cookies aren't saved
+    let loaded = CookieJar::from_json(&jar.to_json().unwrap()).unwrap();
+    let cookies = loaded.cookies();
+    assert_eq!(cookies.len(), 1);
+    assert_eq!(cookies[0].name, "session");
+
+    // Unless the jar is configured to save them
+    let jar = jar.with_session_cookies_saved(true);
+    let loaded = CookieJar::from_json(&jar.to_json().unwrap()).unwrap();
+    let cookies = loaded.cookies_for_domain("intranet.example.com");
+    assert_eq!(cookies.len(), 2);
+    assert!(cookies.iter().all(|cookie| cookie.expires.is_none()));
+
+    jar.clear_domain("intranet.example.com");
+    assert_eq!(jar.cookies().len(), 1);
+    assert!(jar.remove("example.com", "/", "session"));
+    assert!(jar.cookies().is_empty());
+}
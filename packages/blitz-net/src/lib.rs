@@ -1,36 +1,117 @@
 //! Networking (HTTP, filesystem, Data URIs) for Blitz
 //!
 //! Provides an implementation of the [`blitz_traits::net::NetProvider`] trait.
+//!
+//! Custom URL schemes (e.g. `app://`) can be served by registering a [`SchemeHandler`]
+//! with [`Provider::with_scheme_handler`].
+//!
+//! ## Feature flags
+//!  - `tracing` (default): Emits [`tracing`](https://docs.rs/tracing) events when requests are made, complete and fail.
+//!  - `cookies`: Enables a [`CookieJar`] which stores cookies set by responses. The jar can be inspected, modified,
+//!    and saved to and loaded from disk (to keep cookies across sessions).
+//!  - `recording`: Enables `RecordingNetProvider` and `ReplayNetProvider` for recording network traffic
+//!    and replaying it offline (e.g. in tests).
 
-use blitz_traits::net::{BoxedHandler, Bytes, NetCallback, NetProvider, Request, SharedCallback};
+use blitz_traits::net::{
+    BoxedHandler, Bytes, HeaderMap, NetCallback, NetProvider, Request, ResponseHead,
+    SharedCallback, StatusCode,
+};
 use data_url::DataUrl;
-use reqwest::Client;
-use std::sync::Arc;
+use reqwest::{
+    Client,
+    header::{CONTENT_TYPE, HeaderValue},
+};
+use std::{collections::HashMap, sync::Arc};
 use tokio::{
     runtime::Handle,
     sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel},
 };
 
+#[cfg(feature = "cookies")]
+mod cookies;
+#[cfg(feature = "cookies")]
+pub use cookies::{Cookie, CookieJar, CookieJarError};
+
+mod scheme;
+pub use scheme::{MemorySchemeHandler, SchemeHandler, SchemeResponse};
+
+#[cfg(feature = "recording")]
+mod recording;
+#[cfg(feature = "recording")]
+pub use recording::{
+    ArchiveEntry, ArchiveError, NetArchive, RecordingNetProvider, ReplayNetProvider,
+    UnmatchedRequestPolicy,
+};
+
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:60.0) Gecko/20100101 Firefox/81.0";
 
+type SchemeHandlers = Arc<HashMap<String, Arc<dyn SchemeHandler>>>;
+
 pub struct Provider<D> {
     rt: Handle,
     client: Client,
+    scheme_handlers: SchemeHandlers,
     resource_callback: SharedCallback<D>,
+    #[cfg(feature = "cookies")]
+    cookie_jar: CookieJar,
 }
 impl<D: 'static> Provider<D> {
     pub fn new(resource_callback: SharedCallback<D>) -> Self {
         #[cfg(feature = "cookies")]
-        let client = Client::builder().cookie_store(true).build().unwrap();
+        let cookie_jar = CookieJar::new();
+        #[cfg(feature = "cookies")]
+        let client = Self::client_with_cookie_jar(&cookie_jar);
         #[cfg(not(feature = "cookies"))]
         let client = Client::new();
 
         Self {
             rt: Handle::current(),
             client,
+            scheme_handlers: Arc::new(HashMap::new()),
             resource_callback,
+            #[cfg(feature = "cookies")]
+            cookie_jar,
         }
     }
+
+    #[cfg(feature = "cookies")]
+    fn client_with_cookie_jar(cookie_jar: &CookieJar) -> Client {
+        Client::builder()
+            .cookie_provider(Arc::new(cookie_jar.clone()))
+            .build()
+            .unwrap()
+    }
+
+    /// Use `cookie_jar` to store cookies (e.g. a jar which was loaded from disk, or which is shared with another provider)
+    #[cfg(feature = "cookies")]
+    pub fn with_cookie_jar(mut self, cookie_jar: CookieJar) -> Self {
+        self.client = Self::client_with_cookie_jar(&cookie_jar);
+        self.cookie_jar = cookie_jar;
+        self
+    }
+
+    /// The jar in which cookies are stored
+    #[cfg(feature = "cookies")]
+    pub fn cookie_jar(&self) -> &CookieJar {
+        &self.cookie_jar
+    }
+
+    /// Register a [`SchemeHandler`] that serves requests for URLs with the given scheme (e.g. `"app"`).
+    ///
+    /// Handlers cannot override the built-in `data`, `file`, `http` and `https` schemes.
+    pub fn with_scheme_handler(
+        mut self,
+        scheme: impl Into<String>,
+        handler: impl SchemeHandler,
+    ) -> Self {
+        let scheme = scheme.into().to_ascii_lowercase();
+        assert!(
+            !matches!(scheme.as_str(), "data" | "file" | "http" | "https"),
+            "Cannot register a scheme handler for built-in scheme {scheme}"
+        );
+        Arc::make_mut(&mut self.scheme_handlers).insert(scheme, Arc::new(handler));
+        self
+    }
     pub fn shared(res_callback: SharedCallback<D>) -> Arc<dyn NetProvider<D>> {
         Arc::new(Self::new(res_callback))
     }
@@ -41,17 +122,45 @@ impl<D: 'static> Provider<D> {
 impl<D: 'static> Provider<D> {
     async fn fetch_inner(
         client: Client,
+        scheme_handlers: SchemeHandlers,
+        request: Request,
+    ) -> Result<Response, ProviderError> {
+        Self::fetch_stream_inner(client, scheme_handlers, request)
+            .await?
+            .into_response()
+            .await
+    }
+
+    async fn fetch_stream_inner(
+        client: Client,
+        scheme_handlers: SchemeHandlers,
         request: Request,
-    ) -> Result<(String, Bytes), ProviderError> {
+    ) -> Result<ResponseStream, ProviderError> {
+        if let Some(handler) = scheme_handlers.get(request.url.scheme()) {
+            let response = handler
+                .handle(&request)
+                .ok_or_else(|| ProviderError::NotFound(request.url.to_string()))?;
+            return Ok(ResponseStream::buffered(
+                request.url.to_string(),
+                Some(response.content_type),
+                response.body,
+            ));
+        }
+
         Ok(match request.url.scheme() {
             "data" => {
                 let data_url = DataUrl::process(request.url.as_str())?;
+                let content_type = data_url.mime_type().to_string();
                 let decoded = data_url.decode_to_vec()?;
-                (request.url.to_string(), Bytes::from(decoded.0))
+                ResponseStream::buffered(
+                    request.url.to_string(),
+                    Some(content_type),
+                    Bytes::from(decoded.0),
+                )
             }
             "file" => {
                 let file_content = std::fs::read(request.url.path())?;
-                (request.url.to_string(), Bytes::from(file_content))
+                ResponseStream::buffered(request.url.to_string(), None, Bytes::from(file_content))
             }
             _ => {
                 let response = client
@@ -62,20 +171,33 @@ impl<D: 'static> Provider<D> {
                     .send()
                     .await?;
 
-                (response.url().to_string(), response.bytes().await?)
+                let content_type = response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .map(String::from);
+                ResponseStream {
+                    url: response.url().to_string(),
+                    status: response.status(),
+                    headers: response.headers().clone(),
+                    content_type,
+                    body: ResponseBody::Http(response),
+                }
             }
         })
     }
 
     async fn fetch_with_handler(
         client: Client,
+        scheme_handlers: SchemeHandlers,
         doc_id: usize,
         request: Request,
         handler: BoxedHandler<D>,
         res_callback: SharedCallback<D>,
     ) -> Result<(), ProviderError> {
-        let (_response_url, bytes) = Self::fetch_inner(client, request).await?;
-        handler.bytes(doc_id, bytes, res_callback);
+        let response = Self::fetch_inner(client, scheme_handlers, request).await?;
+        let head = ResponseHead::new(response.status, response.headers);
+        handler.bytes_with_head(doc_id, response.body, &head, res_callback);
         Ok(())
     }
 
@@ -83,30 +205,35 @@ impl<D: 'static> Provider<D> {
     pub fn fetch_with_callback(
         &self,
         request: Request,
-        callback: Box<dyn FnOnce(Result<(String, Bytes), ProviderError>) + Send + Sync + 'static>,
+        callback: Box<dyn FnOnce(Result<Response, ProviderError>) + Send + Sync + 'static>,
     ) {
         let client = self.client.clone();
+        let scheme_handlers = self.scheme_handlers.clone();
         self.rt.spawn(async move {
             let url = request.url.to_string();
-            let result = Self::fetch_inner(client, request).await;
-            if let Err(e) = &result {
-                eprintln!("Error fetching {url}: {e:?}");
-            } else {
-                println!("Success {url}");
-            }
+            let result = Self::fetch_inner(client, scheme_handlers, request).await;
+            log_result(&url, &result);
             callback(result);
         });
     }
 
-    pub async fn fetch_async(&self, request: Request) -> Result<(String, Bytes), ProviderError> {
+    pub async fn fetch_async(&self, request: Request) -> Result<Response, ProviderError> {
         let client = self.client.clone();
+        let scheme_handlers = self.scheme_handlers.clone();
         let url = request.url.to_string();
-        let result = Self::fetch_inner(client, request).await;
-        if let Err(e) = &result {
-            eprintln!("Error fetching {url}: {e:?}");
-        } else {
-            println!("Success {url}");
-        }
+        let result = Self::fetch_inner(client, scheme_handlers, request).await;
+        log_result(&url, &result);
+        result
+    }
+
+    /// Fetch a resource, returning as soon as the response headers have been received so that
+    /// the body can be read incrementally with [`ResponseStream::chunk`].
+    pub async fn fetch_stream(&self, request: Request) -> Result<ResponseStream, ProviderError> {
+        let client = self.client.clone();
+        let scheme_handlers = self.scheme_handlers.clone();
+        let url = request.url.to_string();
+        let result = Self::fetch_stream_inner(client, scheme_handlers, request).await;
+        log_result(&url, &result);
         result
     }
 }
@@ -114,26 +241,122 @@ impl<D: 'static> Provider<D> {
 impl<D: 'static> NetProvider<D> for Provider<D> {
     fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<D>) {
         let client = self.client.clone();
+        let scheme_handlers = self.scheme_handlers.clone();
         let callback = Arc::clone(&self.resource_callback);
-        println!("Fetching {}", &request.url);
+        #[cfg(feature = "tracing")]
+        tracing::debug!("Fetching {}", &request.url);
         self.rt.spawn(async move {
             let url = request.url.to_string();
-            let res = Self::fetch_with_handler(client, doc_id, request, handler, callback).await;
-            if let Err(e) = res {
-                eprintln!("Error fetching {url}: {e:?}");
-            } else {
-                println!("Success {url}");
-            }
+            let res = Self::fetch_with_handler(
+                client,
+                scheme_handlers,
+                doc_id,
+                request,
+                handler,
+                callback,
+            )
+            .await;
+            log_result(&url, &res);
         });
     }
 }
 
+/// Emit a `tracing` event for the result of a request (if the `tracing` feature is enabled)
+fn log_result<T>(url: &str, result: &Result<T, ProviderError>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(_) => tracing::debug!("Fetched {url}"),
+        Err(e) => tracing::warn!("Error fetching {url}: {e:?}"),
+    }
+    #[cfg(not(feature = "tracing"))]
+    let _ = (url, result);
+}
+
+/// A response returned by [`Provider::fetch_async`] and [`Provider::fetch_with_callback`]
+#[derive(Debug, Clone)]
+pub struct Response {
+    /// The final URL of the response (after following any redirects)
+    pub url: String,
+    /// The HTTP status of the response (`200 OK` for responses to non-HTTP requests)
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The `Content-Type` of the response (if known)
+    pub content_type: Option<String>,
+    /// The body of the response
+    pub body: Bytes,
+}
+
+/// A response returned by [`Provider::fetch_stream`] whose body is read incrementally
+#[derive(Debug)]
+pub struct ResponseStream {
+    /// The final URL of the response (after following any redirects)
+    pub url: String,
+    /// The HTTP status of the response (`200 OK` for responses to non-HTTP requests)
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    /// The `Content-Type` of the response (if known)
+    pub content_type: Option<String>,
+    body: ResponseBody,
+}
+
+#[derive(Debug)]
+enum ResponseBody {
+    /// A body which is already available in full (e.g. from a `data:` or `file:` URL)
+    Buffered(Option<Bytes>),
+    Http(reqwest::Response),
+}
+
+impl ResponseStream {
+    /// A response to a non-HTTP request whose body is already available in full
+    fn buffered(url: String, content_type: Option<String>, body: Bytes) -> Self {
+        let mut headers = HeaderMap::new();
+        if let Some(value) = content_type
+            .as_deref()
+            .and_then(|content_type| HeaderValue::from_str(content_type).ok())
+        {
+            headers.insert(CONTENT_TYPE, value);
+        }
+        Self {
+            url,
+            status: StatusCode::OK,
+            headers,
+            content_type,
+            body: ResponseBody::Buffered(Some(body)),
+        }
+    }
+
+    /// Read the next chunk of the body, returning `None` once the whole body has been read
+    pub async fn chunk(&mut self) -> Result<Option<Bytes>, ProviderError> {
+        match &mut self.body {
+            ResponseBody::Buffered(body) => Ok(body.take()),
+            ResponseBody::Http(response) => Ok(response.chunk().await?),
+        }
+    }
+
+    /// Read the rest of the body into a [`Response`]
+    pub async fn into_response(self) -> Result<Response, ProviderError> {
+        let body = match self.body {
+            ResponseBody::Buffered(body) => body.unwrap_or_default(),
+            ResponseBody::Http(response) => response.bytes().await?,
+        };
+        Ok(Response {
+            url: self.url,
+            status: self.status,
+            headers: self.headers,
+            content_type: self.content_type,
+            body,
+        })
+    }
+}
+
 #[derive(Debug)]
 pub enum ProviderError {
     Io(std::io::Error),
     DataUrl(data_url::DataUrlError),
     DataUrlBase64(data_url::forgiving_base64::InvalidBase64),
     ReqwestError(reqwest::Error),
+    /// A custom [`SchemeHandler`] had no resource for the requested URL
+    NotFound(String),
 }
 
 impl From<std::io::Error> for ProviderError {
@@ -0,0 +1,493 @@
+//! Recording and replaying of network traffic for deterministic offline rendering.
+//!
+//! A [`RecordingNetProvider`] wraps another [`NetProvider`] and records every successful response
+//! into a [`NetArchive`] (a subset of the [HAR](http://www.softwareishard.com/blog/har-12-spec/) format).
+//! A [`ReplayNetProvider`] then serves those responses from the archive without touching the network.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use blitz_traits::net::{
+    BoxedHandler, Bytes, HeaderMap, NetHandler, NetProvider, Request, ResponseHead, SharedCallback,
+    SharedProvider, StatusCode, http::HeaderValue, http::header::CONTENT_TYPE,
+};
+use data_url::DataUrl;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+/// An archive of recorded network responses. Serializes to a HAR-like JSON document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NetArchive {
+    log: ArchiveLog,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveLog {
+    version: String,
+    creator: ArchiveCreator,
+    entries: Vec<ArchiveEntry>,
+}
+
+impl Default for ArchiveLog {
+    fn default() -> Self {
+        Self {
+            version: String::from("1.2"),
+            creator: ArchiveCreator {
+                name: String::from("blitz-net"),
+                version: String::from(env!("CARGO_PKG_VERSION")),
+            },
+            entries: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveCreator {
+    name: String,
+    version: String,
+}
+
+/// A single recorded request/response pair
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    request: ArchiveRequest,
+    response: ArchiveResponse,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveRequest {
+    method: String,
+    url: String,
+}
+
+/// The status is `200` unless the provider passed the status of the response to the [`NetHandler`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveResponse {
+    #[serde(default = "default_status")]
+    status: u16,
+    content: ArchiveContent,
+}
+
+fn default_status() -> u16 {
+    StatusCode::OK.as_u16()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ArchiveContent {
+    size: usize,
+    #[serde(rename = "mimeType")]
+    mime_type: String,
+    encoding: String,
+    text: String,
+}
+
+impl ArchiveEntry {
+    fn new(
+        method: &str,
+        url: &str,
+        status: StatusCode,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) -> Self {
+        Self {
+            request: ArchiveRequest {
+                method: method.to_string(),
+                url: url.to_string(),
+            },
+            response: ArchiveResponse {
+                status: status.as_u16(),
+                content: ArchiveContent {
+                    size: body.len(),
+                    mime_type: content_type.unwrap_or_default().to_string(),
+                    encoding: String::from("base64"),
+                    text: BASE64.encode(body),
+                },
+            },
+        }
+    }
+
+    /// The HTTP method of the recorded request
+    pub fn method(&self) -> &str {
+        &self.request.method
+    }
+
+    /// The URL of the recorded request
+    pub fn url(&self) -> &str {
+        &self.request.url
+    }
+
+    /// The HTTP status of the recorded response
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.response.status).unwrap_or(StatusCode::OK)
+    }
+
+    /// The `Content-Type` of the recorded response (if known)
+    pub fn content_type(&self) -> Option<&str> {
+        Some(self.response.content.mime_type.as_str()).filter(|mime_type| !mime_type.is_empty())
+    }
+
+    /// The decoded body of the recorded response
+    pub fn body(&self) -> Result<Bytes, ArchiveError> {
+        let content = &self.response.content;
+        match content.encoding.as_str() {
+            "base64" => Ok(Bytes::from(BASE64.decode(&content.text)?)),
+            _ => Ok(Bytes::from(content.text.clone())),
+        }
+    }
+}
+
+impl NetArchive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The recorded entries, in the order in which their responses were received
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.log.entries
+    }
+
+    /// Record a response. If an entry for the same request already exists then it is replaced.
+    pub fn record(
+        &mut self,
+        method: &str,
+        url: &str,
+        status: StatusCode,
+        content_type: Option<&str>,
+        body: &[u8],
+    ) {
+        self.log
+            .entries
+            .retain(|entry| !(entry.method() == method && entry.url() == url));
+        self.log
+            .entries
+            .push(ArchiveEntry::new(method, url, status, content_type, body));
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ArchiveError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, ArchiveError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Load an archive from a file on disk
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ArchiveError> {
+        let json = std::fs::read_to_string(path)?;
+        Self::from_json(&json)
+    }
+
+    /// Save the archive to a file on disk
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ArchiveError> {
+        std::fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Base64(base64::DecodeError),
+}
+
+impl From<std::io::Error> for ArchiveError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for ArchiveError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<base64::DecodeError> for ArchiveError {
+    fn from(value: base64::DecodeError) -> Self {
+        Self::Base64(value)
+    }
+}
+
+/// A [`NetProvider`] which forwards requests to another provider and records all responses into a [`NetArchive`]
+pub struct RecordingNetProvider<D> {
+    inner: SharedProvider<D>,
+    archive: Arc<Mutex<NetArchive>>,
+}
+
+impl<D: 'static> RecordingNetProvider<D> {
+    pub fn new(inner: SharedProvider<D>) -> Self {
+        Self {
+            inner,
+            archive: Arc::new(Mutex::new(NetArchive::new())),
+        }
+    }
+
+    /// Get a snapshot of the responses recorded so far
+    pub fn archive(&self) -> NetArchive {
+        self.archive
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Save the responses recorded so far to a file on disk
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ArchiveError> {
+        self.archive().save(path)
+    }
+}
+
+impl<D: 'static> NetProvider<D> for RecordingNetProvider<D> {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<D>) {
+        let handler = RecordingHandler {
+            method: request.method.to_string(),
+            url: request.url.to_string(),
+            archive: self.archive.clone(),
+            inner: handler,
+        };
+        self.inner.fetch(doc_id, request, Box::new(handler));
+    }
+}
+
+struct RecordingHandler<D> {
+    method: String,
+    url: String,
+    archive: Arc<Mutex<NetArchive>>,
+    inner: BoxedHandler<D>,
+}
+
+impl<D: 'static> NetHandler<D> for RecordingHandler<D> {
+    fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<D>) {
+        self.bytes_with_content_type(doc_id, bytes, None, callback);
+    }
+
+    fn bytes_with_content_type(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<D>,
+    ) {
+        self.archive
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .record(
+                &self.method,
+                &self.url,
+                StatusCode::OK,
+                content_type,
+                &bytes,
+            );
+        self.inner
+            .bytes_with_content_type(doc_id, bytes, content_type, callback);
+    }
+
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<D>,
+    ) {
+        self.archive
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .record(
+                &self.method,
+                &self.url,
+                head.status,
+                head.content_type(),
+                &bytes,
+            );
+        self.inner.bytes_with_head(doc_id, bytes, head, callback);
+    }
+
+    fn failed(self: Box<Self>, doc_id: usize, error: String, callback: SharedCallback<D>) {
+        self.inner.failed(doc_id, error, callback);
+    }
+}
+
+/// What a [`ReplayNetProvider`] should do when it receives a request that is not in its archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnmatchedRequestPolicy {
+    /// Report the request as failed to the provider's [`NetCallback`](blitz_traits::net::NetCallback)
+    #[default]
+    Error,
+    /// Log the request (as a `tracing` warning, if the `tracing` feature is enabled) and report it as
+    /// failed to the request's [`NetHandler`]
+    Log,
+}
+
+/// A [`NetProvider`] which serves responses from a [`NetArchive`] without accessing the network.
+///
+/// `data:` URLs are decoded directly and do not need to be present in the archive.
+pub struct ReplayNetProvider<D> {
+    responses: HashMap<(String, String), (ResponseHead, Bytes)>,
+    unmatched_policy: UnmatchedRequestPolicy,
+    unmatched_requests: Mutex<Vec<String>>,
+    resource_callback: SharedCallback<D>,
+}
+
+impl<D: 'static> ReplayNetProvider<D> {
+    pub fn new(
+        archive: &NetArchive,
+        resource_callback: SharedCallback<D>,
+    ) -> Result<Self, ArchiveError> {
+        let responses = archive
+            .entries()
+            .iter()
+            .map(|entry| {
+                let key = (entry.method().to_string(), entry.url().to_string());
+                let head = response_head(entry.status(), entry.content_type());
+                Ok((key, (head, entry.body()?)))
+            })
+            .collect::<Result<_, ArchiveError>>()?;
+
+        Ok(Self {
+            responses,
+            unmatched_policy: UnmatchedRequestPolicy::default(),
+            unmatched_requests: Mutex::new(Vec::new()),
+            resource_callback,
+        })
+    }
+
+    /// Set what happens to requests that are not in the archive
+    pub fn with_unmatched_policy(mut self, policy: UnmatchedRequestPolicy) -> Self {
+        self.unmatched_policy = policy;
+        self
+    }
+
+    /// The URLs of all requests received so far which were not in the archive
+    pub fn unmatched_requests(&self) -> Vec<String> {
+        self.unmatched_requests
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn lookup(&self, request: &Request) -> Option<(ResponseHead, Bytes)> {
+        if request.url.scheme() == "data" {
+            let data_url = DataUrl::process(request.url.as_str()).ok()?;
+            let content_type = data_url.mime_type().to_string();
+            let (decoded, _) = data_url.decode_to_vec().ok()?;
+            let head = response_head(StatusCode::OK, Some(&content_type));
+            return Some((head, Bytes::from(decoded)));
+        }
+
+        let key = (request.method.to_string(), request.url.to_string());
+        self.responses.get(&key).cloned()
+    }
+}
+
+impl<D: 'static> NetProvider<D> for ReplayNetProvider<D> {
+    fn fetch(&self, doc_id: usize, request: Request, handler: BoxedHandler<D>) {
+        let callback = Arc::clone(&self.resource_callback);
+        if let Some((head, bytes)) = self.lookup(&request) {
+            handler.bytes_with_head(doc_id, bytes, &head, callback);
+            return;
+        }
+
+        let url = request.url.to_string();
+        self.unmatched_requests
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(url.clone());
+
+        match self.unmatched_policy {
+            UnmatchedRequestPolicy::Error => {
+                callback.call(doc_id, Err(Some(format!("No recorded response for {url}"))));
+            }
+            UnmatchedRequestPolicy::Log => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("No recorded response for {url}");
+                handler.failed(doc_id, format!("No recorded response for {url}"), callback);
+            }
+        }
+    }
+}
+
+fn response_head(status: StatusCode, content_type: Option<&str>) -> ResponseHead {
+    let mut headers = HeaderMap::new();
+    if let Some(value) =
+        content_type.and_then(|content_type| HeaderValue::from_str(content_type).ok())
+    {
+        headers.insert(CONTENT_TYPE, value);
+    }
+    ResponseHead::new(status, headers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MemorySchemeHandler, MpscCallback, Provider};
+    use blitz_traits::net::Url;
+
+    /// Passes the body of the response to the callback
+    struct BodyHandler;
+    impl NetHandler<Bytes> for BodyHandler {
+        fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Bytes>) {
+            callback.call(doc_id, Ok(bytes));
+        }
+
+        fn failed(self: Box<Self>, doc_id: usize, error: String, callback: SharedCallback<Bytes>) {
+            callback.call(doc_id, Err(Some(format!("handler: {error}"))));
+        }
+    }
+
+    #[test]
+    fn records_and_replays_responses() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (mut recv, callback) = MpscCallback::new();
+        let assets =
+            MemorySchemeHandler::new().with_resource("ui/index.html", "text/html", "<h1>Hi</h1>");
+        let provider = {
+            let _guard = rt.enter();
+            Provider::new(Arc::new(callback)).with_scheme_handler("app", assets)
+        };
+        let recorder = RecordingNetProvider::new(Arc::new(provider));
+        let url = Url::parse("app://ui/index.html").unwrap();
+        recorder.fetch(0, Request::get(url.clone()), Box::new(BodyHandler));
+        let (_, body) = rt.block_on(recv.recv()).unwrap();
+        assert_eq!(&body[..], b"<h1>Hi</h1>");
+
+        let path =
+            std::env::temp_dir().join(format!("blitz-net-archive-{}.har", std::process::id()));
+        recorder.save(&path).unwrap();
+        let archive = NetArchive::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        let archive = archive.unwrap();
+        assert_eq!(archive.entries().len(), 1);
+        assert_eq!(archive.entries()[0].status(), StatusCode::OK);
+        assert_eq!(archive.entries()[0].content_type(), Some("text/html"));
+
+        let (send, replayed) = std::sync::mpsc::channel();
+        let callback = move |_doc_id, result: Result<Bytes, Option<String>>| {
+            send.send(result).unwrap();
+        };
+        let replay = ReplayNetProvider::new(&archive, Arc::new(callback)).unwrap();
+        replay.fetch(0, Request::get(url), Box::new(BodyHandler));
+        assert_eq!(&replayed.try_recv().unwrap().unwrap()[..], b"<h1>Hi</h1>");
+
+        // Requests which are not in the archive fail by default
+        let missing = Url::parse("app://ui/missing.css").unwrap();
+        replay.fetch(0, Request::get(missing.clone()), Box::new(BodyHandler));
+        let error = replayed.try_recv().unwrap().unwrap_err().unwrap();
+        assert_eq!(error, "No recorded response for app://ui/missing.css");
+
+        // Or are logged and reported as failed to the handler
+        let replay = replay.with_unmatched_policy(UnmatchedRequestPolicy::Log);
+        replay.fetch(0, Request::get(missing), Box::new(BodyHandler));
+        let error = replayed.try_recv().unwrap().unwrap_err().unwrap();
+        assert_eq!(
+            error,
+            "handler: No recorded response for app://ui/missing.css"
+        );
+        assert_eq!(replay.unmatched_requests(), ["app://ui/missing.css"; 2]);
+    }
+}
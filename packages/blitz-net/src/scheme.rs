@@ -0,0 +1,86 @@
+//! Handlers for custom URL schemes such as `app://` or `asset://`
+
+use blitz_traits::net::{Bytes, Request, Url};
+use std::collections::HashMap;
+
+/// A resource served by a [`SchemeHandler`]
+#[derive(Debug, Clone)]
+pub struct SchemeResponse {
+    /// The MIME type of the resource (e.g. `text/html; charset=utf-8`)
+    pub content_type: String,
+    /// The content of the resource
+    pub body: Bytes,
+}
+
+impl SchemeResponse {
+    pub fn new(content_type: impl Into<String>, body: impl Into<Bytes>) -> Self {
+        Self {
+            content_type: content_type.into(),
+            body: body.into(),
+        }
+    }
+}
+
+/// A type that serves requests for a custom URL scheme.
+///
+/// Returning `None` indicates that no resource exists for the requested URL.
+pub trait SchemeHandler: Send + Sync + 'static {
+    fn handle(&self, request: &Request) -> Option<SchemeResponse>;
+}
+
+impl<F: Fn(&Request) -> Option<SchemeResponse> + Send + Sync + 'static> SchemeHandler for F {
+    fn handle(&self, request: &Request) -> Option<SchemeResponse> {
+        self(request)
+    }
+}
+
+/// A [`SchemeHandler`] that serves resources from an in-memory map (e.g. assets embedded in the binary).
+///
+/// Resources are keyed by the part of the URL following the scheme, without leading slashes, query or fragment.
+/// So `app://ui/index.html` and `app:///ui/index.html` both resolve to the key `ui/index.html`.
+#[derive(Debug, Clone, Default)]
+pub struct MemorySchemeHandler {
+    resources: HashMap<String, SchemeResponse>,
+}
+
+impl MemorySchemeHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a resource at the specified path
+    pub fn with_resource(
+        mut self,
+        path: impl Into<String>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        self.insert(path, content_type, body);
+        self
+    }
+
+    /// Add a resource at the specified path, replacing any existing resource at that path
+    pub fn insert(
+        &mut self,
+        path: impl Into<String>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) {
+        let path = path.into();
+        self.resources.insert(
+            path.trim_start_matches('/').to_string(),
+            SchemeResponse::new(content_type, body),
+        );
+    }
+
+    fn key_for_url(url: &Url) -> &str {
+        let after_scheme = &url[url::Position::AfterScheme..url::Position::AfterPath];
+        after_scheme.trim_start_matches(':').trim_start_matches('/')
+    }
+}
+
+impl SchemeHandler for MemorySchemeHandler {
+    fn handle(&self, request: &Request) -> Option<SchemeResponse> {
+        self.resources.get(Self::key_for_url(&request.url)).cloned()
+    }
+}
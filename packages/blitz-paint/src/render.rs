@@ -2,6 +2,7 @@ mod background;
 mod box_shadow;
 mod form_controls;
 
+use std::ops::Range;
 use std::sync::Arc;
 
 use super::multicolor_rounded_rect::{Edge, ElementFrame};
@@ -12,12 +13,11 @@ use crate::sizing::compute_object_fit;
 use anyrender::{CustomPaint, Paint, PaintScene};
 use blitz_dom::node::{
     ListItemLayout, ListItemLayoutPosition, Marker, NodeData, RasterImageData, TextInputData,
-    TextNodeData,
+    TextLayout, TextNodeData,
 };
-use blitz_dom::{BaseDocument, ElementData, Node, local_name};
+use blitz_dom::{BaseDocument, CustomElementPaintCtx, ElementData, Node, local_name};
 use blitz_traits::devtools::DevtoolSettings;
 
-use euclid::Transform3D;
 use style::{
     dom::TElement,
     properties::{
@@ -30,11 +30,20 @@ use style::{
     },
 };
 
-use kurbo::{self, Affine, Point, Rect, Stroke, Vec2};
-use peniko::{self, Fill};
+use kurbo::{self, Affine, BezPath, Point, Rect, Shape, Stroke, Vec2};
+use parley::{Affinity, Cursor, Selection};
+use peniko::{self, Fill, Mix};
 use style::values::generics::color::GenericColor;
 use taffy::Layout;
 
+/// The highlight color of selected text (when the text has no `::selection` styles)
+const DEFAULT_SELECTION_COLOR: Color = Color::from_rgb8(0xb4, 0xd5, 0xfe);
+
+/// The highlight color of text which matches a find-in-page search
+const FIND_MATCH_COLOR: Color = Color::from_rgb8(0xff, 0xff, 0x00);
+/// The highlight color of the active find-in-page match
+const ACTIVE_FIND_MATCH_COLOR: Color = Color::from_rgb8(0xff, 0x96, 0x32);
+
 /// A short-lived struct which holds a bunch of parameters for rendering a scene so
 /// that we don't have to pass them down as parameters
 pub struct BlitzDomPainter<'dom> {
@@ -245,6 +254,7 @@ impl BlitzDomPainter<'_> {
             #[cfg(feature = "svg")]
             cx.draw_svg(scene);
             cx.draw_canvas(scene);
+            cx.draw_custom_element(scene);
             cx.draw_input(scene);
 
             cx.draw_text_input_text(scene, content_position);
@@ -267,6 +277,7 @@ impl BlitzDomPainter<'_> {
                 // unreachable!()
             }
             NodeData::Document => {}
+            NodeData::ShadowRoot(_) => {}
             // NodeData::Doctype => {}
             NodeData::Comment => {} // NodeData::ProcessingInstruction { .. } => {}
         }
@@ -298,40 +309,12 @@ impl BlitzDomPainter<'_> {
         // By performing the transform, we prevent the cache from becoming invalid when the page shifts around
         let mut transform = Affine::translate(box_position.to_vec2() * scale);
 
-        // Apply CSS transform property (where transforms are 2d)
+        // Apply CSS transform property (where transforms are 2d). The transform is in CSS pixels, so it is
+        // converted to device pixels by scaling around it.
         //
-        // TODO: Handle hit testing correctly for transformed nodes
         // TODO: Implement nested transforms
-        let (t, has_3d) = &style
-            .get_box()
-            .transform
-            .to_transform_3d_matrix(None)
-            .unwrap_or((Transform3D::default(), false));
-        if !has_3d {
-            // See: https://drafts.csswg.org/css-transforms-2/#two-dimensional-subset
-            // And https://docs.rs/kurbo/latest/kurbo/struct.Affine.html#method.new
-            let kurbo_transform =
-                Affine::new([t.m11, t.m12, t.m21, t.m22, t.m41, t.m42].map(|v| v as f64));
-
-            // Apply the transform origin by:
-            //   - Translating by the origin offset
-            //   - Applying our transform
-            //   - Translating by the inverse of the origin offset
-            let transform_origin = &style.get_box().transform_origin;
-            let origin_translation = Affine::translate(Vec2 {
-                x: transform_origin
-                    .horizontal
-                    .resolve(CSSPixelLength::new(frame.border_box.width() as f32))
-                    .px() as f64,
-                y: transform_origin
-                    .vertical
-                    .resolve(CSSPixelLength::new(frame.border_box.width() as f32))
-                    .px() as f64,
-            });
-            let kurbo_transform =
-                origin_translation * kurbo_transform * origin_translation.inverse();
-
-            transform *= kurbo_transform;
+        if let Some(css_transform) = node.css_transform() {
+            transform *= Affine::scale(scale) * css_transform * Affine::scale(1.0 / scale);
         }
 
         let element = node.element_data().unwrap();
@@ -403,8 +386,92 @@ impl ElementCx<'_> {
                     panic!("Tried to render node marked as inline root that does not have an inline layout: {:?}", self.node);
                 });
 
-            // Render text
-            crate::text::stroke_text(self.scale, scene, text_layout.layout.lines(), pos);
+            self.draw_find_highlights(scene, pos, text_layout);
+
+            // Render the selection highlight (and the text)
+            match self.context.dom.selected_text_range(self.node.id) {
+                Some(range) => self.draw_selected_text(scene, pos, text_layout, range),
+                None => {
+                    crate::text::stroke_text(self.scale, scene, text_layout.layout.lines(), pos)
+                }
+            }
+        }
+    }
+
+    fn draw_find_highlights(
+        &self,
+        scene: &mut impl PaintScene,
+        pos: Point,
+        text_layout: &TextLayout,
+    ) {
+        let layout = &text_layout.layout;
+        let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+        for (range, is_active) in self.context.dom.find_highlights(self.node.id) {
+            let color = match is_active {
+                true => ACTIVE_FIND_MATCH_COLOR,
+                false => FIND_MATCH_COLOR,
+            };
+            let selection = Selection::new(
+                Cursor::from_byte_index(layout, range.start, Affinity::Downstream),
+                Cursor::from_byte_index(layout, range.end, Affinity::Upstream),
+            );
+            selection.geometry_with(layout, |rect, _line_idx| {
+                scene.fill(Fill::NonZero, transform, color, None, &rect);
+            });
+        }
+    }
+
+    fn draw_selected_text(
+        &self,
+        scene: &mut impl PaintScene,
+        pos: Point,
+        text_layout: &TextLayout,
+        range: Range<usize>,
+    ) {
+        let layout = &text_layout.layout;
+        let transform = Affine::translate((pos.x * self.scale, pos.y * self.scale));
+        let selection = Selection::new(
+            Cursor::from_byte_index(layout, range.start, Affinity::Downstream),
+            Cursor::from_byte_index(layout, range.end, Affinity::Upstream),
+        );
+        let mut highlight = BezPath::new();
+        selection.geometry_with(layout, |rect, _line_idx| {
+            highlight.extend(rect.path_elements(0.1));
+        });
+
+        // Use the `::selection` styles of the inline root (or of it's parent if it is an anonymous block)
+        let selection_style = self.node.selection_styles().or_else(|| {
+            let parent = self.context.dom.get_node(self.node.parent?)?;
+            parent.selection_styles()
+        });
+        let (background, color) = match selection_style {
+            Some(style) => {
+                let current_color = style.clone_color();
+                let background = style
+                    .get_background()
+                    .background_color
+                    .resolve_to_absolute(&current_color)
+                    .as_srgb_color();
+                (background, Some(current_color.as_srgb_color()))
+            }
+            None => (DEFAULT_SELECTION_COLOR, None),
+        };
+
+        scene.fill(Fill::NonZero, transform, background, None, &highlight);
+        crate::text::stroke_text(self.scale, scene, layout.lines(), pos);
+
+        // Repaint the selected text in the `::selection` color
+        if let Some(color) = color {
+            let brush = peniko::Brush::Solid(color);
+            scene.push_layer(Mix::Clip, 1.0, transform, &highlight);
+            crate::text::stroke_text_with_brush(
+                self.scale,
+                scene,
+                layout.lines(),
+                pos,
+                Some(&brush),
+            );
+            scene.pop_layer();
         }
     }
 
@@ -549,9 +616,16 @@ impl ElementCx<'_> {
                 width: width as f32,
                 height: height as f32,
             };
+            // The image's natural size in device pixels (taking into account the pixel density
+            // of images selected from a `srcset`)
+            let density = self
+                .element
+                .image_source
+                .as_ref()
+                .map_or(1.0, |source| source.density);
             let object_size = taffy::Size {
-                width: image.width as f32,
-                height: image.height as f32,
+                width: image.width as f32 / density * self.scale as f32,
+                height: image.height as f32 / density * self.scale as f32,
             };
             let paint_size = compute_object_fit(container_size, Some(object_size), object_fit);
 
@@ -565,8 +639,8 @@ impl ElementCx<'_> {
             let x = x + x_offset.px() as f64;
             let y = y + y_offset.px() as f64;
 
-            let x_scale = paint_size.width as f64 / object_size.width as f64;
-            let y_scale = paint_size.height as f64 / object_size.height as f64;
+            let x_scale = paint_size.width as f64 / image.width as f64;
+            let y_scale = paint_size.height as f64 / image.height as f64;
             let transform = self
                 .transform
                 .pre_scale_non_uniform(x_scale, y_scale)
@@ -601,6 +675,29 @@ impl ElementCx<'_> {
         }
     }
 
+    fn draw_custom_element(&self, scene: &mut impl PaintScene) {
+        if !self.node.flags.is_custom_element() {
+            return;
+        }
+        let Some(custom_element) = self.context.dom.as_ref().custom_element(self.node.id) else {
+            return;
+        };
+
+        let content_box = self.frame.content_box;
+        let ctx = CustomElementPaintCtx {
+            node_id: self.node.id,
+            element: self.element,
+            style: &self.style,
+            size: content_box.size() / self.scale,
+            scale: self.scale,
+            transform: self
+                .transform
+                .pre_translate(content_box.origin().to_vec2())
+                .pre_scale(self.scale),
+        };
+        custom_element.paint(&ctx, scene);
+    }
+
     fn stroke_devtools(&self, scene: &mut impl PaintScene) {
         if self.devtools.show_layout {
             let shape = &self.frame.border_box;
@@ -163,7 +163,7 @@ impl ElementCx<'_> {
         let Some(Some(bg_image)) = bg_image.as_ref() else {
             return;
         };
-        let ImageData::Raster(image_data) = &bg_image.image else {
+        let Some(image_data) = bg_image.image.raster() else {
             return;
         };
 
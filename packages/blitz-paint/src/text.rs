@@ -2,13 +2,25 @@ use anyrender::PaintScene;
 use blitz_dom::node::TextBrush;
 use kurbo::{Affine, Point, Stroke};
 use parley::{Line, PositionedLayoutItem};
-use peniko::Fill;
+use peniko::{Brush, Fill};
 
 pub(crate) fn stroke_text<'a>(
     scale: f64,
     scene: &mut impl PaintScene,
     lines: impl Iterator<Item = Line<'a, TextBrush>>,
     pos: Point,
+) {
+    stroke_text_with_brush(scale, scene, lines, pos, None);
+}
+
+/// Like [`stroke_text`], but if `brush` is `Some` then the text (and it's decorations) are painted with it
+/// instead of with the text's own color
+pub(crate) fn stroke_text_with_brush<'a>(
+    scale: f64,
+    scene: &mut impl PaintScene,
+    lines: impl Iterator<Item = Line<'a, TextBrush>>,
+    pos: Point,
+    brush: Option<&Brush>,
 ) {
     let transform = Affine::translate((pos.x * scale, pos.y * scale));
     for line in lines {
@@ -33,7 +45,7 @@ pub(crate) fn stroke_text<'a>(
                     true, // hint
                     run.normalized_coords(),
                     Fill::NonZero,
-                    &style.brush.brush,
+                    brush.unwrap_or(&style.brush.brush),
                     1.0, // alpha
                     transform,
                     glyph_xform,
@@ -50,19 +62,20 @@ pub(crate) fn stroke_text<'a>(
                     }),
                 );
 
-                let mut draw_decoration_line = |offset: f32, size: f32, brush: &TextBrush| {
-                    let x = glyph_run.offset() as f64;
-                    let w = glyph_run.advance() as f64;
-                    let y = (glyph_run.baseline() - offset + size / 2.0) as f64;
-                    let line = kurbo::Line::new((x, y), (x + w, y));
-                    scene.stroke(
-                        &Stroke::new(size as f64),
-                        transform,
-                        &brush.brush,
-                        None,
-                        &line,
-                    )
-                };
+                let mut draw_decoration_line =
+                    |offset: f32, size: f32, decoration_brush: &TextBrush| {
+                        let x = glyph_run.offset() as f64;
+                        let w = glyph_run.advance() as f64;
+                        let y = (glyph_run.baseline() - offset + size / 2.0) as f64;
+                        let line = kurbo::Line::new((x, y), (x + w, y));
+                        scene.stroke(
+                            &Stroke::new(size as f64),
+                            transform,
+                            brush.unwrap_or(&decoration_brush.brush),
+                            None,
+                            &line,
+                        )
+                    };
 
                 if let Some(underline) = &style.underline {
                     let offset = underline.offset.unwrap_or(metrics.underline_offset);
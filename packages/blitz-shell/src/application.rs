@@ -93,6 +93,7 @@ impl<Rend: WindowRenderer> ApplicationHandler<BlitzShellEvent> for BlitzApplicat
                 // TODO: Handle multiple documents per window
                 if let Some(window) = self.window_mut_by_doc_id(doc_id) {
                     window.doc.as_mut().load_resource(data);
+                    window.doc.poll_ready_state(None);
                     window.request_redraw();
                 }
             }
@@ -22,7 +22,7 @@ pub use crate::window::{View, WindowConfig};
 
 use blitz_dom::net::Resource;
 use blitz_traits::net::NetCallback;
-use blitz_traits::shell::ShellProvider;
+use blitz_traits::shell::{ShellProvider, WindowIcon};
 use std::sync::Arc;
 pub use winit::event_loop::{ControlFlow, EventLoop, EventLoopProxy};
 pub use winit::window::{CursorIcon, Window};
@@ -108,6 +108,18 @@ impl ShellProvider for BlitzShellProvider {
     fn set_window_title(&self, title: String) {
         self.window.set_title(&title);
     }
+    fn set_window_icon(&self, icon: Option<WindowIcon>) {
+        let icon = icon.and_then(|icon| {
+            winit::window::Icon::from_rgba(icon.rgba, icon.width, icon.height).ok()
+        });
+        self.window.set_window_icon(icon);
+    }
+    #[cfg(target_os = "windows")]
+    fn set_theme_color(&self, color: Option<[u8; 4]>) {
+        use winit::platform::windows::{Color, WindowExtWindows};
+        let color = color.map(|[r, g, b, _]| Color::from_rgb(r, g, b));
+        self.window.set_title_background_color(color);
+    }
 
     #[cfg(all(
         feature = "clipboard",
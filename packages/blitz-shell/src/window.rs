@@ -177,8 +177,15 @@ impl<Rend: WindowRenderer> View<Rend> {
 
     pub fn poll(&mut self) -> bool {
         if let Some(waker) = &self.waker {
+            let changed = self.doc.poll(Some(std::task::Context::from_waker(waker)));
+
+            // Polled after the document so that the lifecycle events of a document which has
+            // just finished parsing are fired straight away
             let cx = std::task::Context::from_waker(waker);
-            if self.doc.poll(Some(cx)) {
+            if self.doc.poll_ready_state(Some(&cx)) {
+                self.request_redraw();
+            }
+            if changed {
                 #[cfg(feature = "accessibility")]
                 {
                     if self.doc.has_changes() {
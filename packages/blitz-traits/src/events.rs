@@ -92,6 +92,10 @@ pub enum DomEventData {
     KeyUp(BlitzKeyEvent),
     Input(BlitzInputEvent),
     Ime(BlitzImeEvent),
+    /// The document has been fully parsed (stylesheets, images, etc may still be loading)
+    ContentLoaded,
+    /// The document and all of it's resources have finished loading
+    Load,
 }
 
 impl DomEventData {
@@ -106,6 +110,8 @@ impl DomEventData {
             Self::KeyUp { .. } => "keyup",
             Self::Ime { .. } => "composition",
             Self::Input { .. } => "input",
+            Self::ContentLoaded => "DOMContentLoaded",
+            Self::Load => "load",
         }
     }
 
@@ -120,6 +126,8 @@ impl DomEventData {
             Self::KeyPress { .. } => true,
             Self::Ime { .. } => true,
             Self::Input { .. } => false,
+            Self::ContentLoaded => false,
+            Self::Load => false,
         }
     }
 
@@ -134,6 +142,8 @@ impl DomEventData {
             Self::KeyPress { .. } => true,
             Self::Ime { .. } => true,
             Self::Input { .. } => true,
+            Self::ContentLoaded => true,
+            Self::Load => false,
         }
     }
 }
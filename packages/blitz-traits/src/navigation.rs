@@ -4,7 +4,7 @@ use bytes::Bytes;
 use http::{HeaderMap, HeaderValue, Method};
 use url::Url;
 
-use crate::net::Request;
+use crate::net::{Request, RequestDestination};
 
 /// An abstraction to allow embedders to hook into "navigation events" such as clicking a link
 /// or submitting a form.
@@ -61,6 +61,8 @@ impl NavigationOptions {
                 method: Method::POST,
                 headers,
                 body: document_resource,
+                destination: RequestDestination::Document,
+                initiator: None,
             }
         } else {
             Request {
@@ -68,6 +70,8 @@ impl NavigationOptions {
                 method: Method::GET,
                 headers,
                 body: Bytes::new(),
+                destination: RequestDestination::Document,
+                initiator: None,
             }
         }
     }
@@ -1,7 +1,7 @@
 //! Abstractions of networking so that custom networking implementations can be provided
 
 pub use bytes::Bytes;
-pub use http::{self, HeaderMap, Method};
+pub use http::{self, HeaderMap, Method, StatusCode};
 use std::sync::Arc;
 pub use url::Url;
 
@@ -20,6 +20,47 @@ pub trait NetProvider<Data>: Send + Sync + 'static {
 /// the NetCallack with the result.
 pub trait NetHandler<Data>: Send + Sync + 'static {
     fn bytes(self: Box<Self>, doc_id: usize, bytes: Bytes, callback: SharedCallback<Data>);
+
+    /// Like [`bytes`](NetHandler::bytes), but also receives the `Content-Type` of the response (if known).
+    /// Handlers for text resources can use this to determine the character encoding of the bytes.
+    ///
+    /// NetProviders should call this method rather than `bytes` where possible (or [`bytes_with_head`](NetHandler::bytes_with_head)
+    /// if they know the status and headers of the response). The default implementation ignores the content type and calls `bytes`.
+    fn bytes_with_content_type(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        content_type: Option<&str>,
+        callback: SharedCallback<Data>,
+    ) {
+        let _ = content_type;
+        self.bytes(doc_id, bytes, callback)
+    }
+
+    /// Like [`bytes_with_content_type`](NetHandler::bytes_with_content_type), but receives the status and headers
+    /// of the response. Note that responses with an error status (e.g. `404 Not Found`) are also passed to this method.
+    ///
+    /// The default implementation calls `bytes_with_content_type` with the `Content-Type` header of the response.
+    fn bytes_with_head(
+        self: Box<Self>,
+        doc_id: usize,
+        bytes: Bytes,
+        head: &ResponseHead,
+        callback: SharedCallback<Data>,
+    ) {
+        self.bytes_with_content_type(doc_id, bytes, head.content_type(), callback)
+    }
+
+    /// Called instead of [`bytes`](NetHandler::bytes) when the request fails. NetProviders may also
+    /// report a failure by dropping the handler.
+    ///
+    /// The default implementation passes the error to the callback.
+    fn failed(self: Box<Self>, doc_id: usize, error: String, callback: SharedCallback<Data>)
+    where
+        Data: 'static,
+    {
+        callback.call(doc_id, Err(Some(error)))
+    }
 }
 
 /// A type which accepts the parsed result of a network request and sends it back to the Document
@@ -42,6 +83,10 @@ pub struct Request {
     pub method: Method,
     pub headers: HeaderMap,
     pub body: Bytes,
+    /// The kind of resource being requested
+    pub destination: RequestDestination,
+    /// The id of the DOM node which caused the request to be made (if any)
+    pub initiator: Option<usize>,
 }
 impl Request {
     /// A get request to the specified Url and an empty body
@@ -51,8 +96,58 @@ impl Request {
             method: Method::GET,
             headers: HeaderMap::new(),
             body: Bytes::new(),
+            destination: RequestDestination::Other,
+            initiator: None,
         }
     }
+
+    /// Set the kind of resource being requested
+    pub fn with_destination(mut self, destination: RequestDestination) -> Self {
+        self.destination = destination;
+        self
+    }
+
+    /// Set the id of the DOM node which caused the request to be made
+    pub fn with_initiator(mut self, node_id: usize) -> Self {
+        self.initiator = Some(node_id);
+        self
+    }
+}
+
+/// The status and headers of a response to a [`Request`]
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct ResponseHead {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+}
+impl ResponseHead {
+    pub fn new(status: StatusCode, headers: HeaderMap) -> Self {
+        Self { status, headers }
+    }
+
+    /// The `Content-Type` of the response (if known)
+    pub fn content_type(&self) -> Option<&str> {
+        self.headers
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+}
+
+/// The kind of resource a [`Request`] is for. Loosely represents <https://fetch.spec.whatwg.org/#concept-request-destination>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum RequestDestination {
+    /// A document being navigated to
+    Document,
+    /// A stylesheet (`<link rel="stylesheet">` or `@import`)
+    Stylesheet,
+    /// An image (`<img>` or a CSS image)
+    Image,
+    /// A web font (`@font-face`)
+    Font,
+    /// Any other resource
+    #[default]
+    Other,
 }
 
 /// A default noop NetProvider
@@ -16,6 +16,15 @@ pub trait ShellProvider {
     fn set_window_title(&self, title: String) {
         let _ = title;
     }
+    /// Set the icon of the window (from a `<link rel="icon">`). `None` restores the default icon.
+    fn set_window_icon(&self, icon: Option<WindowIcon>) {
+        let _ = icon;
+    }
+    /// Set the theme color of the window (from a `<meta name="theme-color">`) as sRGB RGBA8 components.
+    /// `None` restores the default color.
+    fn set_theme_color(&self, color: Option<[u8; 4]>) {
+        let _ = color;
+    }
     fn get_clipboard_text(&self) -> Result<String, ClipboardError> {
         Err(ClipboardError)
     }
@@ -28,6 +37,15 @@ pub trait ShellProvider {
 pub struct DummyShellProvider;
 impl ShellProvider for DummyShellProvider {}
 
+/// An image to use as the icon of a window
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowIcon {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels of the icon in (non-premultiplied) RGBA8 format
+    pub rgba: Vec<u8>,
+}
+
 /// The system color scheme (light and dark mode)
 #[derive(Default, Debug, Clone, Copy)]
 pub enum ColorScheme {
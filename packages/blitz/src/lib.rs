@@ -8,6 +8,9 @@
 //! It does not bring any unique functionality, but rather, it re-exports the relevant crates as modules.
 //! The exported crate corresponding to each module is also available in a stand-alone manner, i.e. [`blitz-dom`] as [`blitz::dom`](crate::dom).
 //!
+//! With the `screenshot` feature enabled it also provides [`render_to_image`] for rendering documents to images
+//! without opening a window (e.g. for generating previews or for visual testing).
+//!
 //! [`dioxus-native`]: https://docs.rs/dioxus-native
 //! [`blitz-dom`]: https://docs.rs/blitz-dom
 
@@ -21,7 +24,7 @@ use blitz_shell::{
     BlitzApplication, BlitzShellEvent, BlitzShellNetCallback, Config, EventLoop, WindowConfig,
     create_default_event_loop,
 };
-use blitz_traits::net::{NetProvider, Request};
+use blitz_traits::net::{NetProvider, Request, RequestDestination};
 
 #[doc(inline)]
 /// Re-export of [`blitz_dom`].
@@ -42,6 +45,11 @@ pub use blitz_shell as shell;
 /// Re-export of [`blitz_traits`](https://docs.rs/blitz-traits). Base types and traits for interoperability between modules
 pub use blitz_traits as traits;
 
+#[cfg(feature = "screenshot")]
+pub mod screenshot;
+#[cfg(feature = "screenshot")]
+pub use screenshot::render_to_image;
+
 #[cfg(feature = "net")]
 pub fn launch_url(url: &str) {
     // Assert that url is valid
@@ -59,20 +67,47 @@ pub fn launch_url(url: &str) {
     let event_loop = create_default_event_loop::<BlitzShellEvent>();
     let net_provider = create_net_provider(&event_loop);
 
-    let (url, bytes) = rt
-        .block_on(net_provider.fetch_async(Request::get(url)))
+    // Start rendering as soon as the response headers arrive and stream the body into the document
+    let mut response = rt
+        .block_on(
+            net_provider
+                .fetch_stream(Request::get(url).with_destination(RequestDestination::Document)),
+        )
         .unwrap();
-    let html = std::str::from_utf8(bytes.as_ref()).unwrap();
-
-    launch_internal(
-        html,
-        Config {
-            stylesheets: Vec::new(),
-            base_url: Some(url),
-        },
-        event_loop,
-        net_provider,
-    )
+
+    let config = Config {
+        stylesheets: Vec::new(),
+        base_url: Some(response.url.clone()),
+    };
+
+    let (doc, sender) = HtmlDocument::from_html_stream(
+        response.content_type.as_deref(),
+        document_config(config, net_provider),
+    );
+    rt.spawn(async move {
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => sender.send(&chunk),
+                Ok(None) => break,
+                Err(e) => {
+                    log_fetch_error(&response.url, &e);
+                    break;
+                }
+            }
+        }
+        sender.finish();
+    });
+
+    launch_internal(doc, event_loop)
+}
+
+/// Emit a `tracing` event for an error while streaming a response (if the `tracing` feature is enabled)
+#[cfg(feature = "net")]
+fn log_fetch_error(url: &str, error: &blitz_net::ProviderError) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("Error fetching {url}: {error:?}");
+    #[cfg(not(feature = "tracing"))]
+    let _ = (url, error);
 }
 
 pub fn launch_static_html(html: &str) {
@@ -92,24 +127,20 @@ pub fn launch_static_html_cfg(html: &str, cfg: Config) {
     let event_loop = create_default_event_loop::<BlitzShellEvent>();
     let net_provider = create_net_provider(&event_loop);
 
-    launch_internal(html, cfg, event_loop, net_provider)
+    let doc = HtmlDocument::from_html(html, document_config(cfg, net_provider));
+    launch_internal(doc, event_loop)
 }
 
-fn launch_internal(
-    html: &str,
-    cfg: Config,
-    event_loop: EventLoop<BlitzShellEvent>,
-    net_provider: Arc<dyn NetProvider<Resource>>,
-) {
-    let doc = HtmlDocument::from_html(
-        html,
-        DocumentConfig {
-            base_url: cfg.base_url,
-            ua_stylesheets: Some(cfg.stylesheets),
-            net_provider: Some(net_provider),
-            ..Default::default()
-        },
-    );
+fn document_config(cfg: Config, net_provider: Arc<dyn NetProvider<Resource>>) -> DocumentConfig {
+    DocumentConfig {
+        base_url: cfg.base_url,
+        ua_stylesheets: Some(cfg.stylesheets),
+        net_provider: Some(net_provider),
+        ..Default::default()
+    }
+}
+
+fn launch_internal(doc: HtmlDocument, event_loop: EventLoop<BlitzShellEvent>) {
     let renderer = WindowRenderer::new();
     let window = WindowConfig::new(Box::new(doc) as _, renderer);
 
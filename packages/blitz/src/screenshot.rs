@@ -0,0 +1,429 @@
+//! Headless rendering of documents to images
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use anyrender::render_to_buffer;
+use anyrender_vello_cpu::VelloCpuImageRenderer;
+use blitz_dom::{BaseDocument, DocumentConfig};
+use blitz_html::HtmlDocument;
+use blitz_paint::paint_scene;
+use blitz_traits::shell::{ColorScheme, Viewport};
+
+/// The document to render with [`render_to_image`]
+#[derive(Debug, Clone, Copy)]
+pub enum ScreenshotSource<'a> {
+    /// A string of HTML. Relative URLs are resolved against [`ScreenshotOptions::base_url`].
+    Html(&'a str),
+    /// The URL of a document to fetch (requires the `net` feature)
+    Url(&'a str),
+}
+
+/// Options for [`render_to_image`]
+#[derive(Debug, Clone)]
+pub struct ScreenshotOptions {
+    /// The width of the viewport in CSS pixels
+    pub width: u32,
+    /// The height of the viewport in CSS pixels
+    pub height: u32,
+    /// Capture the full height of the page (from the layout of the root element) rather than just the viewport
+    pub full_page: bool,
+    /// The maximum height (in CSS pixels) of a full page capture
+    pub max_height: u32,
+    /// Only capture the (border box of the) first element which matches this selector
+    pub clip: Option<String>,
+    /// The number of image pixels per CSS pixel
+    pub scale: f64,
+    pub color_scheme: ColorScheme,
+    /// The base url which relative URLs are resolved against when rendering [`ScreenshotSource::Html`]
+    pub base_url: Option<String>,
+    /// How long to wait for resources (stylesheets, images, fonts, etc) to load. The document is rendered
+    /// with whatever has loaded once the timeout elapses.
+    pub timeout: Duration,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 800,
+            full_page: false,
+            max_height: 16384,
+            clip: None,
+            scale: 1.0,
+            color_scheme: ColorScheme::Light,
+            base_url: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// An image rendered by [`render_to_image`]
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+    /// The width of the image in pixels
+    pub width: u32,
+    /// The height of the image in pixels
+    pub height: u32,
+    /// The pixels of the image as (non-premultiplied) 8-bit RGBA, row by row
+    pub rgba: Vec<u8>,
+    /// The number of image pixels per CSS pixel
+    pub scale: f64,
+}
+
+impl Screenshot {
+    /// Encode the image as a PNG, writing it to `writer`
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), ScreenshotError> {
+        // Pixels-per-meter at 96 DPI (1 CSS pixel) multiplied by the scale
+        let ppm = (96.0 * self.scale * 39.3701) as u32;
+
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_pixel_dims(Some(png::PixelDimensions {
+            xppu: ppm,
+            yppu: ppm,
+            unit: png::Unit::Meter,
+        }));
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.rgba)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// Encode the image as a PNG
+    pub fn to_png(&self) -> Result<Vec<u8>, ScreenshotError> {
+        let mut png = Vec::new();
+        self.write_png(&mut png)?;
+        Ok(png)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    /// The document could not be fetched
+    #[cfg(feature = "net")]
+    Net(blitz_net::ProviderError),
+    /// [`ScreenshotSource::Url`] was passed without the `net` feature enabled
+    #[cfg(not(feature = "net"))]
+    NetDisabled,
+    InvalidUrl(String),
+    /// The [`clip`](ScreenshotOptions::clip) selector could not be parsed
+    InvalidSelector(String),
+    /// No element matches the [`clip`](ScreenshotOptions::clip) selector, or the element has no area
+    ElementNotFound(String),
+    Png(png::EncodingError),
+}
+
+impl From<png::EncodingError> for ScreenshotError {
+    fn from(value: png::EncodingError) -> Self {
+        Self::Png(value)
+    }
+}
+
+/// Render a document to an image without opening a window.
+///
+/// Waits (up to [`ScreenshotOptions::timeout`]) for the document's resources to load before rendering it
+/// on the CPU. With the `net` feature enabled this starts (and blocks on) a tokio runtime, so it must
+/// not be called from within an async context.
+///
+/// ```no_run
+/// use blitz::screenshot::{ScreenshotOptions, ScreenshotSource, render_to_image};
+///
+/// let options = ScreenshotOptions {
+///     full_page: true,
+///     scale: 2.0,
+///     ..Default::default()
+/// };
+/// let screenshot = render_to_image(ScreenshotSource::Url("https://example.com"), &options).unwrap();
+/// std::fs::write("example.png", screenshot.to_png().unwrap()).unwrap();
+/// ```
+pub fn render_to_image(
+    source: ScreenshotSource,
+    options: &ScreenshotOptions,
+) -> Result<Screenshot, ScreenshotError> {
+    let deadline = Instant::now() + options.timeout;
+    let mut loader = ResourceLoader::new();
+
+    let mut document = match source {
+        ScreenshotSource::Html(html) => HtmlDocument::from_html(
+            html,
+            DocumentConfig {
+                base_url: options.base_url.clone(),
+                net_provider: loader.net_provider(),
+                ..Default::default()
+            },
+        ),
+        ScreenshotSource::Url(url) => loader.fetch_document(url)?,
+    };
+
+    // The viewport, the rendered image and the clip region are all converted to device pixels in the same way, so
+    // that the image matches the viewport and the clip region never extends past the edge of the image
+    let scale = options.scale;
+    let to_pixels = |value: f32| (value as f64 * scale).round() as u32;
+    let viewport = |height: u32| {
+        Viewport::new(
+            to_pixels(options.width as f32),
+            to_pixels(height as f32),
+            scale as f32,
+            options.color_scheme,
+        )
+    };
+    document.set_viewport(viewport(options.height));
+    loader.load_resources(&mut document, deadline);
+
+    // Resize the viewport to the height of the page, so that content (e.g. lazy-loaded images) which is
+    // outside of the initial viewport is loaded
+    let mut height = options.height;
+    if options.full_page {
+        let full_height = page_height(&document).clamp(options.height, options.max_height);
+        if full_height != height {
+            document.set_viewport(viewport(full_height));
+            loader.load_resources(&mut document, deadline);
+            height = page_height(&document).clamp(options.height, options.max_height);
+        }
+    }
+
+    // The region to capture (in CSS pixels)
+    let (mut x, mut y, mut width) = (0.0, 0.0, options.width as f32);
+    let mut region_height = height as f32;
+    if let Some(selector) = &options.clip {
+        let node_id = document
+            .query_selector(selector)
+            .map_err(|_| ScreenshotError::InvalidSelector(selector.clone()))?
+            .ok_or_else(|| ScreenshotError::ElementNotFound(selector.clone()))?;
+        let Some(rect) = document.get_bounding_client_rect(node_id) else {
+            return Err(ScreenshotError::ElementNotFound(selector.clone()));
+        };
+        (x, y) = (rect.x0.max(0.0) as f32, rect.y0.max(0.0) as f32);
+        width = (rect.x1 as f32).min(options.width as f32) - x;
+        region_height = (rect.y1 as f32).min(options.max_height as f32) - y;
+        height = height.max((y + region_height).ceil() as u32);
+    }
+    if width <= 0.0 || region_height <= 0.0 {
+        return Err(ScreenshotError::ElementNotFound(
+            options.clip.clone().unwrap_or_default(),
+        ));
+    }
+
+    let render_width = to_pixels(options.width as f32);
+    let render_height = to_pixels(height as f32);
+    let rgba = render_to_buffer::<VelloCpuImageRenderer, _>(
+        |scene| paint_scene(scene, &document, scale, render_width, render_height),
+        render_width,
+        render_height,
+    );
+
+    let (x, y) = (
+        to_pixels(x).min(render_width),
+        to_pixels(y).min(render_height),
+    );
+    let width = to_pixels(width).min(render_width - x);
+    let height = to_pixels(region_height).min(render_height - y);
+    Ok(Screenshot {
+        width,
+        height,
+        rgba: crop(&rgba, render_width, x, y, width, height),
+        scale,
+    })
+}
+
+/// The height (in CSS pixels) of the root element's layout
+fn page_height(document: &BaseDocument) -> u32 {
+    document.root_element().final_layout.size.height.ceil() as u32
+}
+
+fn crop(rgba: &[u8], stride: u32, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+    if x == 0 && width == stride {
+        let start = (y * stride * 4) as usize;
+        return rgba[start..start + (width * height * 4) as usize].to_vec();
+    }
+    (y..y + height)
+        .flat_map(|row| {
+            let start = ((row * stride + x) * 4) as usize;
+            &rgba[start..start + (width * 4) as usize]
+        })
+        .copied()
+        .collect()
+}
+
+/// Fetches the document and it's resources (using a tokio runtime owned by the loader)
+#[cfg(feature = "net")]
+struct ResourceLoader {
+    rt: tokio::runtime::Runtime,
+    provider: std::sync::Arc<blitz_net::Provider<blitz_dom::net::Resource>>,
+    recv: tokio::sync::mpsc::UnboundedReceiver<(usize, blitz_dom::net::Resource)>,
+}
+
+#[cfg(feature = "net")]
+impl ResourceLoader {
+    fn new() -> Self {
+        let rt = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let (recv, callback) = blitz_net::MpscCallback::new();
+        let provider = {
+            let _guard = rt.enter();
+            std::sync::Arc::new(blitz_net::Provider::new(std::sync::Arc::new(callback)))
+        };
+        Self { rt, provider, recv }
+    }
+
+    fn net_provider(&self) -> Option<blitz_traits::net::SharedProvider<blitz_dom::net::Resource>> {
+        Some(self.provider.clone())
+    }
+
+    fn fetch_document(&mut self, url: &str) -> Result<HtmlDocument, ScreenshotError> {
+        use blitz_traits::net::{Request, RequestDestination};
+
+        let url = url::Url::parse(url).map_err(|_| ScreenshotError::InvalidUrl(url.to_string()))?;
+        let request = Request::get(url).with_destination(RequestDestination::Document);
+        let response = self
+            .rt
+            .block_on(self.provider.fetch_async(request))
+            .map_err(ScreenshotError::Net)?;
+        Ok(HtmlDocument::from_html_bytes(
+            &response.body,
+            response.content_type.as_deref(),
+            DocumentConfig {
+                base_url: Some(response.url),
+                net_provider: self.net_provider(),
+                ..Default::default()
+            },
+        ))
+    }
+
+    /// Resolve the document, loading resources until none are pending (or the deadline passes)
+    fn load_resources(&mut self, document: &mut HtmlDocument, deadline: Instant) {
+        loop {
+            document.resolve();
+            if document.pending_resource_count() == 0 {
+                return;
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let next = self
+                .rt
+                .block_on(tokio::time::timeout(timeout, self.recv.recv()));
+            let Ok(Some((_, resource))) = next else {
+                return;
+            };
+            document.load_resource(resource);
+            while let Ok((_, resource)) = self.recv.try_recv() {
+                document.load_resource(resource);
+            }
+        }
+    }
+}
+
+/// Without the `net` feature there are no resources to load
+#[cfg(not(feature = "net"))]
+struct ResourceLoader;
+
+#[cfg(not(feature = "net"))]
+impl ResourceLoader {
+    fn new() -> Self {
+        Self
+    }
+
+    fn net_provider(&self) -> Option<blitz_traits::net::SharedProvider<blitz_dom::net::Resource>> {
+        None
+    }
+
+    fn fetch_document(&mut self, _url: &str) -> Result<HtmlDocument, ScreenshotError> {
+        Err(ScreenshotError::NetDisabled)
+    }
+
+    fn load_resources(&mut self, document: &mut HtmlDocument, _deadline: Instant) {
+        document.resolve();
+    }
+}
+
+#[test]
+fn renders_html_to_image() {
+    let html = r#"
+        <body style="margin: 0; background: white">
+            <div style="height: 1000px"></div>
+            <div id="box" style="margin-left: 10px; width: 20px; height: 30px; background: rgb(255, 0, 0)"></div>
+        </body>
+    "#;
+    let options = ScreenshotOptions {
+        width: 100,
+        height: 100,
+        scale: 2.0,
+        ..Default::default()
+    };
+
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &options).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (200, 200));
+    assert_eq!(screenshot.rgba.len(), 200 * 200 * 4);
+
+    let full_page = ScreenshotOptions {
+        full_page: true,
+        ..options.clone()
+    };
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &full_page).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (200, 2060));
+
+    let clipped = ScreenshotOptions {
+        clip: Some(String::from("#box")),
+        ..options
+    };
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &clipped).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (40, 60));
+    assert_eq!(&screenshot.rgba[..4], &[255, 0, 0, 255]);
+    assert!(screenshot.to_png().unwrap().starts_with(b"\x89PNG"));
+}
+
+#[test]
+fn clips_at_fractional_scales() {
+    let html = r#"
+        <body style="margin: 0">
+            <div id="edge" style="margin-left: 100px; width: 10px; height: 10px"></div>
+            <div id="box" style="margin-left: 10px; width: 21px; height: 10px"></div>
+        </body>
+    "#;
+    let options = ScreenshotOptions {
+        width: 101,
+        height: 50,
+        scale: 1.75,
+        clip: Some(String::from("#edge")),
+        ..Default::default()
+    };
+
+    // The last CSS pixel of the page spans device pixels 175..176.75, so the clip region is
+    // rounded to the same edge as the rendered image
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &options).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (2, 18));
+    assert_eq!(screenshot.rgba.len(), 2 * 18 * 4);
+
+    let clipped = ScreenshotOptions {
+        clip: Some(String::from("#box")),
+        ..options
+    };
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &clipped).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (37, 18));
+    assert_eq!(screenshot.rgba.len(), 37 * 18 * 4);
+}
+
+#[test]
+fn clips_to_transformed_elements() {
+    let html = r#"
+        <body style="margin: 0; background: white">
+            <div id="box" style="width: 20px; height: 10px; background: rgb(0, 0, 255); transform: translate(30px, 20px) scale(2)"></div>
+        </body>
+    "#;
+    let options = ScreenshotOptions {
+        width: 100,
+        height: 100,
+        clip: Some(String::from("#box")),
+        ..Default::default()
+    };
+
+    // The box is scaled around its center (to 40x20) and then moved to (20, 15)
+    let screenshot = render_to_image(ScreenshotSource::Html(html), &options).unwrap();
+    assert_eq!((screenshot.width, screenshot.height), (40, 20));
+    assert_eq!(&screenshot.rgba[..4], &[0, 0, 255, 255]);
+}
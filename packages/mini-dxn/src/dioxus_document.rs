@@ -18,6 +18,7 @@ use dioxus_core::{ElementId, Event, VirtualDom};
 use dioxus_html::{PlatformEventData, set_event_converter};
 
 use crate::events::{BlitzKeyboardData, NativeClickData, NativeConverter, NativeFormData};
+use crate::mounted::{BlitzMountedData, MountedQueries};
 use crate::mutation_writer::{DioxusState, MutationWriter};
 use crate::qual_name;
 
@@ -39,6 +40,7 @@ pub struct DioxusDocument {
     pub(crate) vdom: VirtualDom,
     vdom_state: DioxusState,
     inner: BaseDocument,
+    mounted_queries: MountedQueries,
 }
 
 impl DioxusDocument {
@@ -81,6 +83,7 @@ impl DioxusDocument {
             vdom,
             vdom_state,
             inner: doc,
+            mounted_queries: MountedQueries::default(),
         };
 
         doc.inner.set_base_url("dioxus://index.html");
@@ -93,6 +96,26 @@ impl DioxusDocument {
     pub fn initial_build(&mut self) {
         let mut writer = MutationWriter::new(&mut self.inner, &mut self.vdom_state);
         self.vdom.rebuild(&mut writer);
+        drop(writer);
+        self.dispatch_mounted_events();
+        self.inner.mark_content_loaded();
+    }
+
+    /// Dispatch `mounted` events to the elements with `onmounted` listeners which have been created
+    fn dispatch_mounted_events(&mut self) {
+        set_event_converter(Box::new(NativeConverter {}));
+        for id in std::mem::take(&mut self.vdom_state.mounted_elements) {
+            let Some(node_id) = self.vdom_state.try_element_to_node_id(id) else {
+                continue;
+            };
+            let data = wrap_event_data(BlitzMountedData {
+                node_id,
+                queries: self.mounted_queries.clone(),
+            });
+            self.vdom
+                .runtime()
+                .handle_event("mounted", Event::new(data, false), id);
+        }
     }
 }
 
@@ -112,6 +135,16 @@ impl Document for DioxusDocument {
         driver.handle_ui_event(event);
     }
 
+    fn handle_dom_event(&mut self, event: DomEvent) {
+        set_event_converter(Box::new(NativeConverter {}));
+        let handler = DioxusEventHandler {
+            vdom: &mut self.vdom,
+            vdom_state: &mut self.vdom_state,
+        };
+        let mut driver = EventDriver::new(self.inner.mutate(), handler);
+        driver.handle_dom_event(event);
+    }
+
     fn poll(&mut self, cx: Option<TaskContext>) -> bool {
         {
             let fut = self.vdom.wait_for_work();
@@ -119,6 +152,14 @@ impl Document for DioxusDocument {
 
             static NOOP_WAKER: LazyLock<Waker> = LazyLock::new(noop_waker);
             let mut cx = cx.unwrap_or_else(|| TaskContext::from_waker(&NOOP_WAKER));
+
+            // Answer geometry queries from `MountedData` (resolving first so that they see up-to-date layout)
+            self.mounted_queries.set_waker(cx.waker());
+            if !self.mounted_queries.is_empty() {
+                self.inner.resolve();
+                self.mounted_queries.answer(&self.inner);
+            }
+
             match fut.poll_unpin(&mut cx) {
                 std::task::Poll::Ready(_) => {}
                 std::task::Poll::Pending => return false,
@@ -127,6 +168,8 @@ impl Document for DioxusDocument {
 
         let mut writer = MutationWriter::new(&mut self.inner, &mut self.vdom_state);
         self.vdom.render_immediate(&mut writer);
+        drop(writer);
+        self.dispatch_mounted_events();
 
         true
     }
@@ -182,6 +225,9 @@ impl EventHandler for DioxusEventHandler<'_> {
 
             // TODO: Implement IME handling
             DomEventData::Ime(_) => None,
+
+            // TODO: Implement document lifecycle events
+            DomEventData::ContentLoaded | DomEventData::Load => None,
         };
 
         let Some(event_data) = event_data else {
@@ -15,6 +15,8 @@ use dioxus_html::{
 };
 use keyboard_types::{Code, Key, Location, Modifiers};
 
+use crate::mounted::BlitzMountedData;
+
 #[derive(Clone)]
 pub struct NativeClickData;
 
@@ -98,8 +100,9 @@ impl HtmlEventConverter for NativeConverter {
         todo!()
     }
 
-    fn convert_mounted_data(&self, _event: &PlatformEventData) -> MountedData {
-        todo!()
+    fn convert_mounted_data(&self, event: &PlatformEventData) -> MountedData {
+        let data = event.downcast::<BlitzMountedData>().unwrap().clone();
+        MountedData::new(data)
     }
 
     fn convert_mouse_data(&self, event: &PlatformEventData) -> MouseData {
@@ -10,6 +10,7 @@
 
 mod dioxus_document;
 mod events;
+mod mounted;
 mod mutation_writer;
 
 mod dioxus_renderer;
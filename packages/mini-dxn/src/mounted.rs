@@ -0,0 +1,134 @@
+//! Backing for Dioxus's `MountedData` (the data of `onmounted` events)
+//!
+//! The backing does not have access to the document, so queries are queued and answered
+//! the next time that the [`DioxusDocument`](crate::DioxusDocument) is polled.
+
+use std::cell::RefCell;
+use std::future::{Future, poll_fn};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Poll, Waker};
+
+use blitz_dom::BaseDocument;
+use dioxus_html::geometry::euclid::{Point2D, Rect, Size2D, Vector2D};
+use dioxus_html::geometry::{PixelsRect, PixelsSize, PixelsVector2D};
+use dioxus_html::{MountedError, MountedResult, RenderedElementBacking};
+
+type MountedFuture<T> = Pin<Box<dyn Future<Output = MountedResult<T>>>>;
+type Query = Box<dyn FnOnce(&BaseDocument)>;
+
+/// Queries made through [`BlitzMountedData`] which are waiting to be answered by the document
+#[derive(Clone, Default)]
+pub(crate) struct MountedQueries(Rc<RefCell<QueryQueue>>);
+
+#[derive(Default)]
+struct QueryQueue {
+    queries: Vec<Query>,
+    /// Wakes the document so that queued queries are answered
+    waker: Option<Waker>,
+}
+
+/// The answer to a query (and the waker of the task waiting for it)
+struct QuerySlot<T> {
+    result: Option<MountedResult<T>>,
+    waker: Option<Waker>,
+}
+
+impl MountedQueries {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.borrow().queries.is_empty()
+    }
+
+    pub(crate) fn set_waker(&self, waker: &Waker) {
+        self.0.borrow_mut().waker = Some(waker.clone());
+    }
+
+    /// Answer all queued queries
+    pub(crate) fn answer(&self, doc: &BaseDocument) {
+        let queries = std::mem::take(&mut self.0.borrow_mut().queries);
+        for query in queries {
+            query(doc);
+        }
+    }
+
+    /// Queue a query, returning a future which resolves once it has been answered
+    fn query<T: 'static>(
+        &self,
+        query: impl FnOnce(&BaseDocument) -> MountedResult<T> + 'static,
+    ) -> MountedFuture<T> {
+        let slot = Rc::new(RefCell::new(QuerySlot {
+            result: None,
+            waker: None,
+        }));
+
+        let query_slot = slot.clone();
+        let mut queue = self.0.borrow_mut();
+        queue.queries.push(Box::new(move |doc| {
+            let mut slot = query_slot.borrow_mut();
+            slot.result = Some(query(doc));
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        }));
+        if let Some(waker) = &queue.waker {
+            waker.wake_by_ref();
+        }
+
+        Box::pin(poll_fn(move |cx| {
+            let mut slot = slot.borrow_mut();
+            match slot.result.take() {
+                Some(result) => Poll::Ready(result),
+                None => {
+                    slot.waker = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        }))
+    }
+}
+
+/// The platform data of `mounted` events
+#[derive(Clone)]
+pub struct BlitzMountedData {
+    pub(crate) node_id: usize,
+    pub(crate) queries: MountedQueries,
+}
+
+fn not_rendered() -> MountedError {
+    MountedError::OperationFailed("The element is not rendered".into())
+}
+
+impl RenderedElementBacking for BlitzMountedData {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn get_scroll_offset(&self) -> MountedFuture<PixelsVector2D> {
+        let node_id = self.node_id;
+        self.queries.query(move |doc| {
+            let metrics = doc.element_metrics(node_id).ok_or_else(not_rendered)?;
+            Ok(Vector2D::new(metrics.scroll_left, metrics.scroll_top))
+        })
+    }
+
+    fn get_scroll_size(&self) -> MountedFuture<PixelsSize> {
+        let node_id = self.node_id;
+        self.queries.query(move |doc| {
+            let metrics = doc.element_metrics(node_id).ok_or_else(not_rendered)?;
+            Ok(Size2D::new(metrics.scroll_width, metrics.scroll_height))
+        })
+    }
+
+    fn get_client_rect(&self) -> MountedFuture<PixelsRect> {
+        let node_id = self.node_id;
+        self.queries.query(move |doc| {
+            let rect = doc
+                .get_bounding_client_rect(node_id)
+                .ok_or_else(not_rendered)?;
+            Ok(Rect::new(
+                Point2D::new(rect.x0, rect.y0),
+                Size2D::new(rect.width(), rect.height()),
+            ))
+        })
+    }
+}
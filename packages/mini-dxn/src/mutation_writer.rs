@@ -16,6 +16,8 @@ pub struct DioxusState {
     stack: Vec<NodeId>,
     /// Mapping from vdom ElementId -> rdom NodeId
     node_id_mapping: Vec<Option<NodeId>>,
+    /// Elements with `onmounted` listeners which have been created since `mounted` events were last dispatched
+    pub(crate) mounted_elements: Vec<ElementId>,
 }
 
 impl DioxusState {
@@ -25,6 +27,7 @@ impl DioxusState {
             templates: FxHashMap::default(),
             stack: vec![root_id],
             node_id_mapping: vec![Some(root_id)],
+            mounted_elements: Vec::new(),
         }
     }
 
@@ -254,6 +257,11 @@ impl WriteMutations for MutationWriter<'_> {
         let value = AttributeValue::Text(id.0.to_string());
         self.set_attribute("data-dioxus-id", None, &value, id);
 
+        // There is no DOM to fire `mounted` events, so queue them to be dispatched once the mutations have been applied
+        if name == "mounted" {
+            self.state.mounted_elements.push(id);
+        }
+
         // node.add_event_listener(name);
     }
 
@@ -25,10 +25,6 @@ impl<D: Send + Sync + 'static> WptNetProvider<D> {
         }
     }
 
-    pub fn pending_item_count(&self) -> usize {
-        self.queue.pending_item_count()
-    }
-
     pub fn log_pending_items(&self) {
         self.queue.log_pending_items();
     }
@@ -191,13 +187,6 @@ impl<T> InternalQueue<T> {
         }
     }
 
-    pub fn pending_item_count(&self) -> usize {
-        self.requests
-            .lock()
-            .unwrap_or_else(|err| err.into_inner())
-            .len()
-    }
-
     pub fn log_pending_items(&self) {
         let requests = self.requests.lock().unwrap_or_else(|err| err.into_inner());
         for (id, req) in requests.iter() {
@@ -120,7 +120,7 @@ fn parse_and_resolve_document(
     // Load resources.
     // Loop because loading a resource may result in further resources being requested
     let start = Instant::now();
-    while ctx.net_provider.pending_item_count() > 0 {
+    while document.pending_resource_count() > 0 {
         ctx.net_provider
             .for_each(|res| document.as_mut().load_resource(res));
         document.as_mut().resolve();
@@ -128,14 +128,10 @@ fn parse_and_resolve_document(
             ctx.net_provider.log_pending_items();
             panic!(
                 "Timeout. {} pending items.",
-                ctx.net_provider.pending_item_count()
+                document.pending_resource_count()
             );
         }
     }
 
-    ctx.net_provider
-        .for_each(|res| document.as_mut().load_resource(res));
-    document.as_mut().resolve();
-
     document.into()
 }